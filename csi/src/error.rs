@@ -0,0 +1,73 @@
+use std::fmt;
+
+/// Errors surfaced by the CSI driver's controller and node services.
+///
+/// These are mapped to gRPC status codes at the service boundary; callers
+/// inside the driver should prefer this type over raw `tonic::Status` so
+/// that logic stays testable without a gRPC context.
+#[derive(Debug)]
+pub enum CsiError {
+    InvalidArgument(String),
+    NotFound(String),
+    Internal(String),
+    /// A call to the Roset API didn't complete within its configured
+    /// timeout — maps to `Status::deadline_exceeded` rather than
+    /// `Internal`, so a caller (or kubelet, a few hops up) can tell a
+    /// slow backend apart from a broken one.
+    DeadlineExceeded(String),
+    /// A call to the Roset API couldn't even connect — maps to
+    /// `Status::unavailable`, which external-provisioner/kubelet retry
+    /// rather than giving up on outright.
+    Unavailable(String),
+}
+
+impl fmt::Display for CsiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsiError::InvalidArgument(msg) => write!(f, "invalid argument: {msg}"),
+            CsiError::NotFound(msg) => write!(f, "not found: {msg}"),
+            CsiError::Internal(msg) => write!(f, "internal error: {msg}"),
+            CsiError::DeadlineExceeded(msg) => write!(f, "deadline exceeded: {msg}"),
+            CsiError::Unavailable(msg) => write!(f, "unavailable: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for CsiError {}
+
+/// Maps a failed Roset API call to the [`CsiError`] variant a caller (or
+/// kubelet/external-provisioner, a few hops up a real gRPC boundary) should
+/// react to differently: a request that timed out should be retried later,
+/// while one that couldn't connect at all means the backend is down.
+pub(crate) fn classify_reqwest_error(error: reqwest::Error) -> CsiError {
+    if error.is_timeout() {
+        CsiError::DeadlineExceeded(error.to_string())
+    } else if error.is_connect() {
+        CsiError::Unavailable(error.to_string())
+    } else {
+        CsiError::Internal(error.to_string())
+    }
+}
+
+/// Maps a non-2xx Roset API response to the [`CsiError`] variant a caller
+/// should react to differently, mirroring how `fuse::client::RosetClient`
+/// disambiguates status codes rather than folding every failure into one
+/// generic error. `context` names the call that failed (e.g. `"create
+/// volume"`), for an error message a kubelet log line can attribute to the
+/// right RPC.
+pub(crate) fn classify_response_status(
+    status: reqwest::StatusCode,
+    context: &str,
+    body: &str,
+) -> CsiError {
+    match status {
+        reqwest::StatusCode::NOT_FOUND => CsiError::NotFound(format!("{context}: {body}")),
+        reqwest::StatusCode::BAD_REQUEST | reqwest::StatusCode::UNPROCESSABLE_ENTITY => {
+            CsiError::InvalidArgument(format!("{context}: {body}"))
+        }
+        reqwest::StatusCode::SERVICE_UNAVAILABLE | reqwest::StatusCode::GATEWAY_TIMEOUT => {
+            CsiError::Unavailable(format!("{context}: {body}"))
+        }
+        _ => CsiError::Internal(format!("{context}: unexpected status {status}: {body}")),
+    }
+}
@@ -0,0 +1,6 @@
+pub mod controller;
+pub mod error;
+pub mod identity;
+pub mod node;
+pub mod shutdown;
+pub mod volume_context;
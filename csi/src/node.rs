@@ -0,0 +1,1244 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::{classify_reqwest_error, CsiError};
+use crate::shutdown::ShutdownCoordinator;
+use crate::volume_context::VolumeContext;
+
+/// How long [`NodeService::node_get_volume_stats`] waits for a `stat()` on
+/// the mount point before treating it as wedged rather than merely slow.
+const STAT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// `getxattr`/`setxattr` name a `roset-fuse` process publishes/accepts on
+/// its mount root reflecting `RosetFs::ready_xattr`/`RosetFs::handle_recover_xattr`
+/// — the closest thing to a `/readyz` endpoint and a remediation lever
+/// available without a side-channel control protocol between this
+/// supervisor and the mount it's watching over.
+const READY_XATTR: &str = "user.roset.ready";
+const RECOVER_XATTR: &str = "user.roset.recover";
+
+/// How long a mount may report not-ready (API-unreachable) before
+/// [`NodeService::node_get_volume_stats`] treats it as unhealthy and asks
+/// it to recover. A transient blip that clears within this window
+/// shouldn't flush a mount's caches for nothing.
+const SUSTAINED_UNREADY_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// Directory [`NodeService::spawn_fuse_process`] passes each mount's
+/// `--log-file` under, named by volume id so logs from different
+/// volumes on the same node never collide. The same directory also
+/// holds each volume's raw stdout/stderr capture file (see
+/// [`open_stdio_capture_file`]), which keeps what `roset-fuse` prints
+/// outside its `--log-file` (a panic, say) for a post-mortem.
+const FUSE_LOG_DIR: &str = "/var/log/roset-csi/fuse";
+
+/// Cap on a volume's stdio capture file (see [`open_stdio_capture_file`])
+/// before it's rotated aside on the *next* spawn. `roset-fuse`'s own
+/// `--log-file` rotates live while the process runs (see its
+/// `logging::RotatingFileWriter`); this file is a raw OS-level
+/// stdout/stderr redirect instead, so it can only be rotated between
+/// process lifetimes, not mid-write.
+const STDIO_CAPTURE_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Default timeout for the shared HTTP client [`NodeService`] uses for
+/// any direct Roset API call (see [`NodeService::probe_backend`]).
+/// Without one, a hung backend could block a `NodeStageVolume`/
+/// `NodePublishVolume` gRPC call — and the kubelet/external-provisioner
+/// worker slot behind it — indefinitely. Overridden via
+/// [`NodeService::with_api_timeout`]; this crate has no CLI entrypoint
+/// of its own, so no flag or env var sets it here.
+pub const DEFAULT_API_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default bound [`NodeService::wait_for_drain`] is given to let in-flight
+/// requests finish during shutdown before the process exits anyway.
+/// Overridden by a future `--shutdown-timeout-secs` CLI flag once there's
+/// an actual driver entrypoint to carry one.
+pub const DEFAULT_SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Opens (appending) the stdio capture file at `path` for
+/// [`NodeService::spawn_fuse_process`], first rotating it aside to
+/// `<path>.1` (clobbering any previous `.1`) if it already exceeds
+/// [`STDIO_CAPTURE_MAX_BYTES`] — the file itself can only be rotated
+/// between process lifetimes since it's a single `File` handed to the
+/// child for the whole time it runs, unlike `roset-fuse`'s own
+/// `--log-file`, which rotates live.
+fn open_stdio_capture_file(path: &Path) -> io::Result<File> {
+    if let Ok(metadata) = fs::metadata(path) {
+        if metadata.len() > STDIO_CAPTURE_MAX_BYTES {
+            let mut rotated = path.as_os_str().to_os_string();
+            rotated.push(".1");
+            fs::rename(path, PathBuf::from(rotated))?;
+        }
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn build_http_client(timeout: Duration) -> reqwest::blocking::Client {
+    reqwest::blocking::Client::builder()
+        .timeout(timeout)
+        .build()
+        .unwrap_or_else(|_| reqwest::blocking::Client::new())
+}
+
+/// Health of a mounted volume, mirroring the CSI
+/// `NodeGetVolumeStatsResponse.VolumeCondition` message closely enough to
+/// convert directly at the gRPC boundary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeCondition {
+    pub abnormal: bool,
+    pub message: String,
+}
+
+impl VolumeCondition {
+    fn healthy() -> Self {
+        Self {
+            abnormal: false,
+            message: "volume is healthy".to_string(),
+        }
+    }
+}
+
+/// `roset-fuse` CLI flags a `mountOptions`/`fuseArgs` volume-context entry
+/// is allowed to request. Anything outside this list is rejected rather
+/// than passed through, since the value ultimately reaches `Command::arg`
+/// for a privileged process.
+const ALLOWED_FUSE_ARGS: &[&str] = &[
+    "--read-only",
+    "--cache-ttl",
+    "--direct-io",
+    "--max-readahead-kb",
+    "--no-default-permissions",
+];
+
+/// `volume_id` -> the path it's staged at and the pid of the `roset-fuse`
+/// process backing it, tracked together under one lock.
+///
+/// This used to be two separate maps (`volume_path` -> `volume_id` and
+/// `volume_id` -> `pid`) updated one after the other — under concurrent
+/// stage/unstage of the same volume, a reader could observe the first map
+/// updated but not the second, so a mount would briefly (or, on a
+/// crash between the two inserts, permanently) look tracked-but-not-running
+/// or running-but-not-tracked. Consolidating into a single map behind a
+/// single lock makes `register`/`unregister` atomic, so that can't happen.
+/// Tracked by pid rather than by holding the `Child` so liveness can be
+/// checked (`kill(pid, 0)`) without taking ownership away from whatever
+/// ends up supervising the process's exit status.
+#[derive(Default)]
+struct MountRegistry {
+    by_volume: Mutex<HashMap<String, (String, u32)>>,
+}
+
+impl MountRegistry {
+    fn register(&self, volume_path: &str, volume_id: &str, pid: u32) {
+        self.by_volume
+            .lock()
+            .unwrap()
+            .insert(volume_id.to_string(), (volume_path.to_string(), pid));
+    }
+
+    fn unregister_by_path(&self, volume_path: &str) {
+        self.by_volume.lock().unwrap().retain(|_, (path, _)| path != volume_path);
+    }
+
+    /// Looks up the `(volume_id, pid)` mounted at `volume_path`, if any, in
+    /// a single lock acquisition so the pair can't be read half-updated.
+    fn lookup_by_path(&self, volume_path: &str) -> Option<(String, u32)> {
+        self.by_volume
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, (path, _))| path == volume_path)
+            .map(|(volume_id, (_, pid))| (volume_id.clone(), *pid))
+    }
+
+    /// Whether `volume_id` is already staged at `volume_path` with a
+    /// process `is_alive` reports as still running — the idempotency check
+    /// [`NodeService::stage_volume_with`] uses to avoid spawning a second
+    /// `roset-fuse` for a volume that's already staged.
+    fn is_staged_and_alive(&self, volume_id: &str, volume_path: &str, is_alive: impl FnOnce(u32) -> bool) -> bool {
+        match self.by_volume.lock().unwrap().get(volume_id) {
+            Some((path, pid)) if path == volume_path => is_alive(*pid),
+            _ => false,
+        }
+    }
+}
+
+pub struct NodeService {
+    mounts: MountRegistry,
+    /// Per-`volume_id` locks serializing [`Self::stage_volume`]/
+    /// [`Self::unregister_mount`] for the same volume, so concurrent
+    /// kubelet calls for it can't race into a double-spawn or
+    /// double-unmount. Entries accumulate for the life of the process —
+    /// bounded by the number of distinct volumes ever staged on this
+    /// node, which is small enough not to need reclaiming.
+    volume_locks: Mutex<HashMap<String, Arc<Mutex<()>>>>,
+    /// Shared client for direct Roset API calls (see [`Self::probe_backend`]),
+    /// built once with [`DEFAULT_API_TIMEOUT`] rather than via
+    /// `reqwest::blocking::Client::new()` per call, which would both skip
+    /// the timeout and waste a connection pool per request.
+    http: reqwest::blocking::Client,
+    /// Gates the mutating RPC handlers (`stage_volume`,
+    /// `node_publish_volume`) against new work once shutdown has begun —
+    /// see [`Self::begin_shutdown`].
+    shutdown: ShutdownCoordinator,
+    /// `volume_path` -> when [`Self::node_get_volume_stats`] first
+    /// observed that mount reporting not-ready. Cleared as soon as it
+    /// reports ready again, so only a *sustained* outage (past
+    /// [`SUSTAINED_UNREADY_THRESHOLD`]) triggers a soft recovery rather
+    /// than every transient blip.
+    unready_since: Mutex<HashMap<String, Instant>>,
+}
+
+impl Default for NodeService {
+    fn default() -> Self {
+        Self {
+            mounts: MountRegistry::default(),
+            volume_locks: Mutex::new(HashMap::new()),
+            http: build_http_client(DEFAULT_API_TIMEOUT),
+            shutdown: ShutdownCoordinator::new(),
+            unready_since: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl NodeService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the timeout applied to [`Self::probe_backend`] and any
+    /// other direct Roset API call `NodeService` makes. Intended for the
+    /// driver's `--api-timeout-secs` CLI flag / `ROSET_CSI_API_TIMEOUT_SECS`
+    /// env var.
+    pub fn with_api_timeout(mut self, timeout: Duration) -> Self {
+        self.http = build_http_client(timeout);
+        self
+    }
+
+    /// Probes `api_url`'s `/v1/ping` endpoint, so a caller can fail fast
+    /// with a distinguishable [`CsiError`] (see [`classify_reqwest_error`])
+    /// instead of an API call hanging for the lifetime of a
+    /// `NodeStageVolume`/`NodePublishVolume` gRPC handler.
+    pub fn probe_backend(&self, api_url: &str) -> Result<(), CsiError> {
+        self.http
+            .get(format!("{api_url}/v1/ping"))
+            .send()
+            .map_err(classify_reqwest_error)?;
+        Ok(())
+    }
+
+    /// Stops admitting new `stage_volume`/`node_publish_volume` calls.
+    /// Idempotent — the eventual `main.rs` entrypoint calls this from its
+    /// SIGTERM handler before calling [`Self::wait_for_drain`] and exiting.
+    pub fn begin_shutdown(&self) {
+        self.shutdown.begin_shutdown();
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_shutting_down()
+    }
+
+    /// Waits up to `timeout` for every request already in flight when
+    /// [`Self::begin_shutdown`] was called to finish. See
+    /// [`crate::shutdown::ShutdownCoordinator::wait_for_drain`].
+    pub fn wait_for_drain(&self, timeout: Duration) -> bool {
+        self.shutdown.wait_for_drain(timeout)
+    }
+
+    /// Records that `volume_id`, backed by the `roset-fuse` process `pid`,
+    /// is mounted at `volume_path`, so later `node_get_volume_stats` calls
+    /// for that path can find it.
+    pub fn register_mount(&self, volume_path: &str, volume_id: &str, pid: u32) {
+        self.mounts.register(volume_path, volume_id, pid);
+    }
+
+    /// Forgets a mount, e.g. on `node_unstage_volume`/`node_unpublish_volume`.
+    /// Serialized against a concurrent [`Self::stage_volume`] for the same
+    /// volume the same way `stage_volume` is against itself.
+    pub fn unregister_mount(&self, volume_path: &str) {
+        let volume_id = match self.mounts.lookup_by_path(volume_path) {
+            Some((volume_id, _)) => volume_id,
+            None => return,
+        };
+        let volume_lock = self.lock_volume(&volume_id);
+        let _volume_guard = volume_lock.lock().unwrap();
+        self.mounts.unregister_by_path(volume_path);
+    }
+
+    /// Acquires the per-volume lock serializing [`Self::stage_volume`] and
+    /// [`Self::unregister_mount`] for `volume_id`.
+    fn lock_volume(&self, volume_id: &str) -> Arc<Mutex<()>> {
+        self.volume_locks
+            .lock()
+            .unwrap()
+            .entry(volume_id.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(())))
+            .clone()
+    }
+
+    /// Reports the health of the volume mounted at `volume_path` for
+    /// `NodeGetVolumeStats`, so a wedged or dead mount surfaces to kubelet
+    /// as an abnormal `VolumeCondition` instead of going unnoticed.
+    ///
+    /// Three independent signals can make a volume abnormal: the
+    /// `roset-fuse` process backing it has exited, a `stat()` against the
+    /// mount point doesn't return within [`STAT_TIMEOUT`] (the mount is
+    /// wedged badly enough that even a liveness check can't get an answer
+    /// from it), or the mount has sustained [`READY_XATTR`] `"0"` for
+    /// longer than [`SUSTAINED_UNREADY_THRESHOLD`] — the process is alive
+    /// and responsive, but every op is failing with `EIO` because it's
+    /// lost its connection to the Roset API.
+    ///
+    /// That last case doesn't kill and restage the mount the way the
+    /// first two might eventually lead to — it instead asks the process
+    /// to soft-recover (see [`RECOVER_XATTR`]) by flushing caches and
+    /// re-resolving its root, on the chance the partition has since
+    /// healed and a restart isn't needed at all.
+    pub fn node_get_volume_stats(&self, volume_path: &str) -> Result<VolumeCondition, CsiError> {
+        let (volume_id, pid) = self
+            .mounts
+            .lookup_by_path(volume_path)
+            .ok_or_else(|| CsiError::NotFound(format!("no volume staged at '{volume_path}'")))?;
+
+        if !Self::process_is_alive(pid) {
+            self.unready_since.lock().unwrap().remove(volume_path);
+            return Ok(VolumeCondition {
+                abnormal: true,
+                message: format!("roset-fuse process for volume '{volume_id}' is not running"),
+            });
+        }
+
+        if !Self::mount_is_responsive(Path::new(volume_path)) {
+            self.unready_since.lock().unwrap().remove(volume_path);
+            return Ok(VolumeCondition {
+                abnormal: true,
+                message: format!("mount at '{volume_path}' is not responding to stat()"),
+            });
+        }
+
+        if Self::mount_is_ready(Path::new(volume_path)) {
+            self.unready_since.lock().unwrap().remove(volume_path);
+            return Ok(VolumeCondition::healthy());
+        }
+
+        let sustained = {
+            let mut unready_since = self.unready_since.lock().unwrap();
+            let since = *unready_since
+                .entry(volume_path.to_string())
+                .or_insert_with(Instant::now);
+            since.elapsed() >= SUSTAINED_UNREADY_THRESHOLD
+        };
+
+        if !sustained {
+            return Ok(VolumeCondition::healthy());
+        }
+
+        if let Err(e) = Self::trigger_soft_recovery(Path::new(volume_path)) {
+            eprintln!("roset-csi: failed to trigger soft recovery for volume '{volume_id}': {e}");
+        }
+
+        Ok(VolumeCondition {
+            abnormal: true,
+            message: format!(
+                "volume '{volume_id}' has been unable to reach the Roset API for over {}s",
+                SUSTAINED_UNREADY_THRESHOLD.as_secs()
+            ),
+        })
+    }
+
+    /// Reads [`READY_XATTR`] off `mount_point`, defaulting to ready when
+    /// it's missing (an older `roset-fuse` that doesn't publish it yet,
+    /// or — in tests — a plain directory standing in for a mount) rather
+    /// than treating absence itself as an outage.
+    fn mount_is_ready(mount_point: &Path) -> bool {
+        match Self::getxattr(mount_point, READY_XATTR) {
+            Some(value) => value.as_slice() != b"0",
+            None => true,
+        }
+    }
+
+    /// Asks the `roset-fuse` process backing `mount_point` to soft-recover
+    /// (see [`RosetFs::handle_recover_xattr`]) by setting [`RECOVER_XATTR`]
+    /// on it.
+    fn trigger_soft_recovery(mount_point: &Path) -> Result<(), CsiError> {
+        Self::setxattr(mount_point, RECOVER_XATTR, b"1")
+    }
+
+    fn getxattr(path: &Path, name: &str) -> Option<Vec<u8>> {
+        let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let name = CString::new(name).ok()?;
+        let mut buf = [0u8; 8];
+        let n = unsafe {
+            libc::getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+            )
+        };
+        if n < 0 {
+            return None;
+        }
+        Some(buf[..n as usize].to_vec())
+    }
+
+    fn setxattr(path: &Path, name: &str, value: &[u8]) -> Result<(), CsiError> {
+        let path_c = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| CsiError::Internal(format!("invalid mount path: {e}")))?;
+        let name_c = CString::new(name).map_err(|e| CsiError::Internal(format!("invalid xattr name: {e}")))?;
+        let ret = unsafe {
+            libc::setxattr(
+                path_c.as_ptr(),
+                name_c.as_ptr(),
+                value.as_ptr() as *const libc::c_void,
+                value.len(),
+                0,
+            )
+        };
+        if ret != 0 {
+            return Err(CsiError::Internal(format!(
+                "failed to setxattr {name} on {}: {}",
+                path.display(),
+                std::io::Error::last_os_error()
+            )));
+        }
+        Ok(())
+    }
+
+    fn process_is_alive(pid: u32) -> bool {
+        // Signal 0 sends no signal but still performs the permission/existence
+        // checks, so this is the standard way to probe liveness by pid.
+        unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+    }
+
+    /// `stat()`s `mount_point` on a helper thread and waits up to
+    /// [`STAT_TIMEOUT`] for it, so a FUSE mount whose daemon is wedged
+    /// (and therefore never answers the kernel's getattr) can't block
+    /// `node_get_volume_stats` forever.
+    fn mount_is_responsive(mount_point: &Path) -> bool {
+        let mount_point = mount_point.to_path_buf();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(std::fs::metadata(&mount_point).is_ok());
+        });
+        rx.recv_timeout(STAT_TIMEOUT).unwrap_or(false)
+    }
+
+    /// Parses the `mountOptions`/`fuseArgs` volume-context key into a list
+    /// of extra `roset-fuse` arguments.
+    ///
+    /// The value is a comma-separated list of `--flag` or `--flag=value`
+    /// entries. Only flags in [`ALLOWED_FUSE_ARGS`] are accepted; anything
+    /// else is rejected so a StorageClass can't smuggle arbitrary argv
+    /// into the mount helper.
+    fn parse_fuse_args(volume_context: &HashMap<String, String>) -> Result<Vec<String>, CsiError> {
+        let raw = match volume_context
+            .get("mountOptions")
+            .or_else(|| volume_context.get("fuseArgs"))
+        {
+            Some(raw) if !raw.trim().is_empty() => raw,
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut args = Vec::new();
+        for entry in raw.split(',') {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let flag = entry.split('=').next().unwrap_or(entry);
+            if !ALLOWED_FUSE_ARGS.contains(&flag) {
+                return Err(CsiError::InvalidArgument(format!(
+                    "mount option '{flag}' is not allow-listed"
+                )));
+            }
+            args.push(entry.to_string());
+        }
+        Ok(args)
+    }
+
+    /// Whether `error` (from a `stat()`/`metadata()` call against a mount
+    /// point) is the signature of a stale FUSE mount: the kernel still has
+    /// a filesystem mounted at that path, but the daemon behind it is gone,
+    /// so every operation fails immediately with `ENOTCONN` rather than
+    /// hanging the way a merely wedged-but-alive daemon would (contrast
+    /// [`Self::mount_is_responsive`], which covers that hang case via a
+    /// timeout instead).
+    fn is_stale_mount_error(error: &std::io::Error) -> bool {
+        error.raw_os_error() == Some(libc::ENOTCONN)
+    }
+
+    /// `stat()`s `mount_point` and reports whether it's a stale mount (see
+    /// [`Self::is_stale_mount_error`]).
+    fn mount_is_stale(mount_point: &Path) -> bool {
+        match std::fs::metadata(mount_point) {
+            Ok(_) => false,
+            Err(e) => Self::is_stale_mount_error(&e),
+        }
+    }
+
+    /// Force-unmounts `mount_point` via `fusermount -u -z` (lazy unmount:
+    /// detaches the mount immediately rather than `-u` alone, which would
+    /// wait for in-flight requests that a dead daemon can never complete).
+    fn force_unmount(mount_point: &Path) -> Result<(), CsiError> {
+        let status = Command::new("fusermount")
+            .arg("-u")
+            .arg("-z")
+            .arg(mount_point)
+            .status()
+            .map_err(|e| CsiError::Internal(format!("failed to run fusermount: {e}")))?;
+        if !status.success() {
+            return Err(CsiError::Internal(format!(
+                "fusermount -u -z {} exited with {status}",
+                mount_point.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// `node_stage_volume`'s mount-point preparation: if a previous
+    /// `roset-fuse` process died without unmounting, `staging_target_path`
+    /// can be left as a stale mount that a plain `is_mounted`-style check
+    /// would wrongly call healthy (`stat` just returns `ENOTCONN`, not a
+    /// clean "not mounted"), leaving the path permanently broken instead of
+    /// re-staged. Detects that case and force-unmounts before re-staging,
+    /// via [`Self::stage_volume_with`].
+    ///
+    /// `volume_context` is validated up front via [`VolumeContext::parse`]
+    /// (the controller's `create_volume` takes no volume context and does
+    /// not run this parse), so a malformed value (e.g. a non-numeric `cacheSizeGi`) is rejected
+    /// with `InvalidArgument` here rather than surfacing later as an
+    /// opaque `roset-fuse` argument-parse failure.
+    ///
+    /// Refuses to start once [`Self::begin_shutdown`] has been called (see
+    /// [`crate::shutdown::ShutdownCoordinator`]) — `_guard` is held for the
+    /// rest of the call so a shutdown started mid-flight waits for this one
+    /// to finish instead of racing it.
+    ///
+    /// Idempotent and safe under concurrent calls for the same
+    /// `volume_id` — see [`Self::stage_volume_with`].
+    pub fn stage_volume(
+        &self,
+        volume_id: &str,
+        staging_target_path: &str,
+        volume_context: &HashMap<String, String>,
+    ) -> Result<(), CsiError> {
+        let _guard = self.shutdown.admit()?;
+        VolumeContext::parse(volume_context)?;
+        self.stage_volume_with(
+            volume_id,
+            staging_target_path,
+            Self::mount_is_stale,
+            Self::force_unmount,
+            || self.spawn_fuse_process(volume_id, staging_target_path, volume_context),
+        )
+    }
+
+    /// [`Self::stage_volume`] with the staleness check, the force-unmount,
+    /// and the actual (re-)staging all injected, so the stale-mount
+    /// recovery path is testable without a real FUSE mount or a
+    /// `fusermount` binary on hand.
+    ///
+    /// Holds `volume_id`'s per-volume lock for the whole call, so two
+    /// concurrent stagings of the same volume can't interleave their
+    /// staleness check and spawn. Once that lock is held, re-checks
+    /// whether the volume is already staged at `staging_target_path` with
+    /// a live process and, if so, returns `Ok(())` without spawning again
+    /// — `NodeStageVolume` must be idempotent, and without this a second
+    /// concurrent (or retried) call would double-spawn `roset-fuse`.
+    fn stage_volume_with(
+        &self,
+        volume_id: &str,
+        staging_target_path: &str,
+        is_stale: impl FnOnce(&Path) -> bool,
+        force_unmount: impl FnOnce(&Path) -> Result<(), CsiError>,
+        spawn: impl FnOnce() -> Result<(), CsiError>,
+    ) -> Result<(), CsiError> {
+        let volume_lock = self.lock_volume(volume_id);
+        let _volume_guard = volume_lock.lock().unwrap();
+        if self.mounts.is_staged_and_alive(volume_id, staging_target_path, Self::process_is_alive) {
+            return Ok(());
+        }
+
+        let mount_point = Path::new(staging_target_path);
+        if is_stale(mount_point) {
+            force_unmount(mount_point)?;
+        }
+        spawn()
+    }
+
+    /// Spawns the `roset-fuse` mount helper for `node_stage_volume`,
+    /// appending any validated extra arguments from the volume context on
+    /// top of the fixed base command line.
+    ///
+    /// Also re-parses `volume_context` via [`VolumeContext::parse`] (the
+    /// same parse `stage_volume` already ran up front to validate it) to
+    /// pull out `readAhead`, `cacheDir`, and `cacheSizeGi`, passed through
+    /// as `--read-ahead-kb`/`--max-readahead-kb`/`--cache-dir`/`--cache-size-mb`
+    /// — the structured [`VolumeContext`] fields this command line needs,
+    /// as opposed to the free-text `mountOptions`/`fuseArgs` passthrough
+    /// covered by [`Self::parse_fuse_args`] and [`ALLOWED_FUSE_ARGS`].
+    /// `readAhead` feeds both `--read-ahead-kb` (the application-level
+    /// sequential-prefetch window) and `--max-readahead-kb` (the
+    /// kernel-level readahead bound negotiated at `init`) — the single
+    /// volume-context knob sizes both.
+    pub fn spawn_fuse_process(
+        &self,
+        volume_id: &str,
+        staging_target_path: &str,
+        volume_context: &HashMap<String, String>,
+    ) -> Result<(), CsiError> {
+        let extra_args = Self::parse_fuse_args(volume_context)?;
+        let parsed_context = VolumeContext::parse(volume_context)?;
+
+        let mut cmd = Command::new("roset-fuse");
+        cmd.arg("--volume-id")
+            .arg(volume_id)
+            .arg("--mount-point")
+            .arg(staging_target_path)
+            .arg("--log-file")
+            .arg(format!("{FUSE_LOG_DIR}/{volume_id}.log"));
+
+        if let Some(read_ahead_kb) = parsed_context.read_ahead {
+            cmd.arg("--read-ahead-kb").arg(read_ahead_kb.to_string());
+            cmd.arg("--max-readahead-kb").arg(read_ahead_kb.to_string());
+        }
+        if let Some(cache_dir) = &parsed_context.cache_dir {
+            cmd.arg("--cache-dir").arg(cache_dir);
+        }
+        if let Some(cache_size_gi) = parsed_context.cache_size_gi {
+            cmd.arg("--cache-size-mb").arg((cache_size_gi * 1024).to_string());
+        }
+
+        for arg in &extra_args {
+            cmd.arg(arg);
+        }
+
+        // `--log-file` only captures what `roset-fuse` explicitly logs
+        // through it; a panic or anything printed before that's set up
+        // still goes to raw stdout/stderr, which is otherwise inherited
+        // from this (detached) process and lost. Redirecting both into
+        // one capture file, rather than the structured `--log-file`
+        // itself, keeps the two channels independent so neither writer
+        // can corrupt the other's output by interleaving into the same
+        // file without coordination.
+        let stdio_capture_path = PathBuf::from(format!("{FUSE_LOG_DIR}/{volume_id}.stdio.log"));
+        let stdout_capture = open_stdio_capture_file(&stdio_capture_path)
+            .map_err(|e| CsiError::Internal(format!("failed to open stdio capture log for {volume_id}: {e}")))?;
+        let stderr_capture = stdout_capture
+            .try_clone()
+            .map_err(|e| CsiError::Internal(format!("failed to duplicate stdio capture log handle for {volume_id}: {e}")))?;
+        cmd.stdout(stdout_capture).stderr(stderr_capture);
+
+        let child: Child = cmd
+            .spawn()
+            .map_err(|e| CsiError::Internal(format!("failed to spawn roset-fuse: {e}")))?;
+
+        self.register_mount(staging_target_path, volume_id, child.id());
+        Ok(())
+    }
+
+    /// Whether `NodePublishVolume`'s `readonly` flag should be enforced by
+    /// (re-)staging the volume with a FUSE-level `--read-only` mount,
+    /// rather than by a bind remount alone.
+    ///
+    /// `mount -o remount,ro,bind` only narrows *this* publish's view of
+    /// the underlying staging mount — the right tool when that staging
+    /// mount is `shared` with other publishes of the same volume that may
+    /// still be read-write, since making the shared mount itself
+    /// read-only would break those. When this publish is the only
+    /// consumer (`shared` is `false`), though, a bind remount is the
+    /// *only* enforcement in play: the FUSE mount underneath stays fully
+    /// writable and reachable by anything else that can get to the
+    /// staging path, so the bind layer is bypassable in some
+    /// configurations. There, the FUSE layer itself should reject writes.
+    pub fn should_use_readonly_fuse_mount(read_only: bool, shared: bool) -> bool {
+        read_only && !shared
+    }
+
+    /// `NodePublishVolume`: bind-mounts the already-staged volume at
+    /// `target_path`.
+    ///
+    /// See [`Self::should_use_readonly_fuse_mount`] for the choice between
+    /// re-staging with `--read-only` first (the exclusive case) versus a
+    /// plain bind mount plus `remount,ro,bind` (the shared case).
+    ///
+    /// Refuses to start once [`Self::begin_shutdown`] has been called, the
+    /// same as [`Self::stage_volume`].
+    pub fn node_publish_volume(
+        &self,
+        volume_id: &str,
+        staging_target_path: &str,
+        target_path: &str,
+        volume_context: &HashMap<String, String>,
+        read_only: bool,
+        shared: bool,
+    ) -> Result<(), CsiError> {
+        let _guard = self.shutdown.admit()?;
+        self.node_publish_volume_with(
+            staging_target_path,
+            target_path,
+            volume_context,
+            read_only,
+            shared,
+            |path, context| self.stage_volume(volume_id, path, context),
+            Self::bind_mount,
+            Self::remount_readonly,
+        )
+    }
+
+    /// Merges a forced `--read-only` into `volume_context`'s
+    /// `mountOptions`/`fuseArgs` entry, for re-staging a volume that must
+    /// come up FUSE-level read-only regardless of what the original
+    /// staging request asked for.
+    fn with_forced_read_only(volume_context: &HashMap<String, String>) -> HashMap<String, String> {
+        let mut context = volume_context.clone();
+        let existing = context
+            .remove("mountOptions")
+            .or_else(|| context.remove("fuseArgs"))
+            .unwrap_or_default();
+        let merged = if existing.trim().is_empty() {
+            "--read-only".to_string()
+        } else {
+            format!("{existing},--read-only")
+        };
+        context.insert("mountOptions".to_string(), merged);
+        context
+    }
+
+    /// [`Self::node_publish_volume`] with the FUSE-level re-stage, the bind
+    /// mount, and the remount all going through injected callbacks, so the
+    /// enforcement choice is testable without a real `mount`/`roset-fuse`
+    /// binary or kernel mount namespace.
+    #[allow(clippy::too_many_arguments)]
+    fn node_publish_volume_with(
+        &self,
+        staging_target_path: &str,
+        target_path: &str,
+        volume_context: &HashMap<String, String>,
+        read_only: bool,
+        shared: bool,
+        restage: impl FnOnce(&str, &HashMap<String, String>) -> Result<(), CsiError>,
+        bind_mount: impl FnOnce(&Path, &Path) -> Result<(), CsiError>,
+        remount_readonly: impl FnOnce(&Path) -> Result<(), CsiError>,
+    ) -> Result<(), CsiError> {
+        if Self::should_use_readonly_fuse_mount(read_only, shared) {
+            let context = Self::with_forced_read_only(volume_context);
+            restage(staging_target_path, &context)?;
+            return bind_mount(Path::new(staging_target_path), Path::new(target_path));
+        }
+
+        bind_mount(Path::new(staging_target_path), Path::new(target_path))?;
+        if read_only {
+            remount_readonly(Path::new(target_path))?;
+        }
+        Ok(())
+    }
+
+    /// `mount --bind source target`, publishing an already-staged FUSE
+    /// mount at a pod's `target_path`.
+    fn bind_mount(source: &Path, target: &Path) -> Result<(), CsiError> {
+        let status = Command::new("mount")
+            .arg("--bind")
+            .arg(source)
+            .arg(target)
+            .status()
+            .map_err(|e| CsiError::Internal(format!("failed to run mount --bind: {e}")))?;
+        if !status.success() {
+            return Err(CsiError::Internal(format!(
+                "mount --bind {} {} exited with {status}",
+                source.display(),
+                target.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// `mount -o remount,ro,bind target`, narrowing a just-bind-mounted
+    /// `target_path` to read-only without affecting the underlying
+    /// staging mount other publishes may still be writing through.
+    fn remount_readonly(target: &Path) -> Result<(), CsiError> {
+        let status = Command::new("mount")
+            .arg("-o")
+            .arg("remount,ro,bind")
+            .arg(target)
+            .status()
+            .map_err(|e| CsiError::Internal(format!("failed to run mount -o remount,ro,bind: {e}")))?;
+        if !status.success() {
+            return Err(CsiError::Internal(format!(
+                "mount -o remount,ro,bind {} exited with {status}",
+                target.display()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_listed_options_are_appended() {
+        let mut ctx = HashMap::new();
+        ctx.insert(
+            "mountOptions".to_string(),
+            "--direct-io,--cache-ttl=30s".to_string(),
+        );
+        let args = NodeService::parse_fuse_args(&ctx).unwrap();
+        assert_eq!(args, vec!["--direct-io", "--cache-ttl=30s"]);
+    }
+
+    #[test]
+    fn disallowed_options_are_rejected() {
+        let mut ctx = HashMap::new();
+        ctx.insert("mountOptions".to_string(), "--exec=/bin/sh".to_string());
+        let err = NodeService::parse_fuse_args(&ctx).unwrap_err();
+        assert!(matches!(err, CsiError::InvalidArgument(_)));
+    }
+
+    #[test]
+    fn a_dead_fuse_process_yields_an_abnormal_volume_condition() {
+        let mut child = Command::new("true").spawn().expect("failed to spawn helper process");
+        let pid = child.id();
+        child.wait().expect("failed to wait for helper process");
+
+        let node = NodeService::new();
+        node.register_mount("/mnt/vol-1", "vol-1", pid);
+
+        let condition = node.node_get_volume_stats("/mnt/vol-1").unwrap();
+        assert!(condition.abnormal);
+        assert!(condition.message.contains("vol-1"));
+    }
+
+    #[test]
+    fn an_unregistered_path_is_not_found() {
+        let node = NodeService::new();
+        assert!(matches!(
+            node.node_get_volume_stats("/mnt/unknown"),
+            Err(CsiError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn enotconn_is_recognized_as_a_stale_mount_but_other_errors_are_not() {
+        let stale = std::io::Error::from_raw_os_error(libc::ENOTCONN);
+        assert!(NodeService::is_stale_mount_error(&stale));
+
+        let missing = std::io::Error::from_raw_os_error(libc::ENOENT);
+        assert!(!NodeService::is_stale_mount_error(&missing));
+    }
+
+    #[test]
+    fn a_stale_mount_is_force_unmounted_before_re_staging() {
+        let node = NodeService::new();
+        let unmounted = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let unmounted_for_closure = unmounted.clone();
+        let spawned = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let spawned_for_closure = spawned.clone();
+
+        let result = node.stage_volume_with(
+            "vol-1",
+            "/mnt/vol-1",
+            |_| true,
+            move |_| {
+                unmounted_for_closure.store(true, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            },
+            move || {
+                spawned_for_closure.store(true, std::sync::atomic::Ordering::SeqCst);
+                Command::new("true")
+                    .spawn()
+                    .map(|_| ())
+                    .map_err(|e| CsiError::Internal(e.to_string()))
+            },
+        );
+
+        assert!(result.is_ok());
+        assert!(unmounted.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(spawned.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn stage_volume_rejects_a_malformed_volume_context_before_spawning() {
+        let node = NodeService::new();
+        let mut ctx = HashMap::new();
+        ctx.insert("mountId".to_string(), "vol-1".to_string());
+        ctx.insert("rootPath".to_string(), "/datasets/vol-1".to_string());
+        ctx.insert("cacheSizeGi".to_string(), "not-a-number".to_string());
+
+        let err = node.stage_volume("vol-1", "/mnt/vol-1", &ctx).unwrap_err();
+        assert!(matches!(err, CsiError::InvalidArgument(msg) if msg.contains("cacheSizeGi")));
+    }
+
+    #[test]
+    fn an_in_flight_request_finishes_during_shutdown_while_new_stage_calls_are_refused() {
+        let node = NodeService::new();
+        // Simulate a request already in flight when shutdown begins.
+        let guard = node.shutdown.admit().unwrap();
+        node.begin_shutdown();
+
+        let mut ctx = HashMap::new();
+        ctx.insert("mountId".to_string(), "vol-1".to_string());
+        ctx.insert("rootPath".to_string(), "/datasets/vol-1".to_string());
+        let err = node.stage_volume("vol-1", "/mnt/vol-1", &ctx).unwrap_err();
+        assert!(matches!(err, CsiError::Unavailable(_)));
+
+        // The in-flight request finishes, e.g. its RPC handler returning.
+        drop(guard);
+        assert!(node.wait_for_drain(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn a_healthy_mount_is_staged_without_force_unmounting() {
+        let node = NodeService::new();
+        let unmounted = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let unmounted_for_closure = unmounted.clone();
+
+        let result = node.stage_volume_with(
+            "vol-1",
+            "/mnt/vol-1",
+            |_| false,
+            move |_| {
+                unmounted_for_closure.store(true, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            },
+            || {
+                Command::new("true")
+                    .spawn()
+                    .map(|_| ())
+                    .map_err(|e| CsiError::Internal(e.to_string()))
+            },
+        );
+
+        assert!(result.is_ok());
+        assert!(!unmounted.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn concurrent_stage_calls_for_the_same_volume_spawn_exactly_once() {
+        let node = Arc::new(NodeService::new());
+        let spawn_count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let node = node.clone();
+                let spawn_count = spawn_count.clone();
+                thread::spawn(move || {
+                    node.stage_volume_with(
+                        "vol-1",
+                        "/mnt/vol-1",
+                        |_| false,
+                        |_| Ok(()),
+                        || {
+                            spawn_count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                            // Long-lived enough that every other thread's
+                            // idempotency check sees it as still alive for
+                            // the rest of the test.
+                            let child = Command::new("sleep")
+                                .arg("1")
+                                .spawn()
+                                .map_err(|e| CsiError::Internal(e.to_string()))?;
+                            node.register_mount("/mnt/vol-1", "vol-1", child.id());
+                            Ok(())
+                        },
+                    )
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap().unwrap();
+        }
+
+        // Every call but the first should have found the volume already
+        // staged and alive (see `MountRegistry::is_staged_and_alive`) and
+        // skipped spawning, rather than racing into a second `roset-fuse`.
+        assert_eq!(spawn_count.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn readonly_fuse_mount_is_only_used_when_the_staging_mount_is_exclusive() {
+        assert!(NodeService::should_use_readonly_fuse_mount(true, false));
+        assert!(!NodeService::should_use_readonly_fuse_mount(true, true));
+        assert!(!NodeService::should_use_readonly_fuse_mount(false, false));
+        assert!(!NodeService::should_use_readonly_fuse_mount(false, true));
+    }
+
+    #[test]
+    fn an_exclusive_readonly_publish_restages_with_read_only_instead_of_remounting() {
+        let node = NodeService::new();
+        let restage_options = std::sync::Arc::new(Mutex::new(None));
+        let restage_options_for_closure = restage_options.clone();
+        let remounted = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let remounted_for_closure = remounted.clone();
+        let bound = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let bound_for_closure = bound.clone();
+
+        let result = node.node_publish_volume_with(
+            "/var/lib/roset/staging/vol-1",
+            "/mnt/pod-1",
+            &HashMap::new(),
+            true,
+            false,
+            move |_path, context| {
+                *restage_options_for_closure.lock().unwrap() = context.get("mountOptions").cloned();
+                Command::new("true").spawn().map(|_child| ()).map_err(|e| CsiError::Internal(e.to_string()))
+            },
+            move |_source, _target| {
+                bound_for_closure.store(true, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            },
+            move |_target| {
+                remounted_for_closure.store(true, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            },
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(
+            restage_options.lock().unwrap().as_deref(),
+            Some("--read-only")
+        );
+        assert!(bound.load(std::sync::atomic::Ordering::SeqCst));
+        // A plain bind mount, never narrowed by a remount, is enough here
+        // since the FUSE mount underneath is already read-only — there's
+        // nothing left for `remount,ro,bind` to additionally enforce.
+        assert!(!remounted.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn a_shared_readonly_publish_bind_mounts_then_remounts_read_only() {
+        let node = NodeService::new();
+        let remounted = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let remounted_for_closure = remounted.clone();
+        let bound = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let bound_for_closure = bound.clone();
+
+        let result = node.node_publish_volume_with(
+            "/var/lib/roset/staging/vol-1",
+            "/mnt/pod-1",
+            &HashMap::new(),
+            true,
+            true,
+            |_path, _context| panic!("shared publish must not re-stage the volume"),
+            move |_source, _target| {
+                bound_for_closure.store(true, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            },
+            move |_target| {
+                remounted_for_closure.store(true, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            },
+        );
+
+        assert!(result.is_ok());
+        assert!(bound.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(remounted.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn a_shared_read_write_publish_only_bind_mounts() {
+        let node = NodeService::new();
+        let remounted = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let remounted_for_closure = remounted.clone();
+        let bound = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let bound_for_closure = bound.clone();
+
+        let result = node.node_publish_volume_with(
+            "/var/lib/roset/staging/vol-1",
+            "/mnt/pod-1",
+            &HashMap::new(),
+            false,
+            true,
+            |_path, _context| panic!("a read-write publish must not re-stage the volume"),
+            move |_source, _target| {
+                bound_for_closure.store(true, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            },
+            move |_target| {
+                remounted_for_closure.store(true, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            },
+        );
+
+        assert!(result.is_ok());
+        assert!(bound.load(std::sync::atomic::Ordering::SeqCst));
+        assert!(!remounted.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn a_live_process_with_a_responsive_mount_is_healthy() {
+        let node = NodeService::new();
+        let mount_point = std::env::temp_dir();
+        node.register_mount(mount_point.to_str().unwrap(), "vol-2", std::process::id());
+
+        let condition = node
+            .node_get_volume_stats(mount_point.to_str().unwrap())
+            .unwrap();
+        assert!(!condition.abnormal);
+    }
+
+    #[test]
+    fn a_sustained_unready_mount_is_marked_abnormal_and_triggers_recovery() {
+        let mount_point = std::env::temp_dir().join("roset-csi-test-sustained-unready");
+        std::fs::create_dir_all(&mount_point).expect("failed to create test mount point");
+        let mount_point_str = mount_point.to_str().unwrap();
+        NodeService::setxattr(&mount_point, READY_XATTR, b"0").expect("failed to set test xattr");
+
+        let node = NodeService::new();
+        node.register_mount(mount_point_str, "vol-3", std::process::id());
+
+        // Not-ready that hasn't been sustained yet shouldn't trip abnormal.
+        let first = node.node_get_volume_stats(mount_point_str).unwrap();
+        assert!(!first.abnormal);
+
+        // Back-date the first-seen timestamp past the threshold instead
+        // of sleeping in the test.
+        node.unready_since.lock().unwrap().insert(
+            mount_point_str.to_string(),
+            Instant::now() - SUSTAINED_UNREADY_THRESHOLD - Duration::from_secs(1),
+        );
+
+        let second = node.node_get_volume_stats(mount_point_str).unwrap();
+        assert!(second.abnormal);
+        assert!(second.message.contains("vol-3"));
+        assert_eq!(
+            NodeService::getxattr(&mount_point, RECOVER_XATTR),
+            Some(b"1".to_vec())
+        );
+
+        let _ = std::fs::remove_dir_all(&mount_point);
+    }
+
+    #[test]
+    fn a_spawned_process_s_stdio_lands_in_its_per_volume_capture_file() {
+        let dir = std::env::temp_dir().join("roset-csi-test-stdio-capture");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("vol-4.stdio.log");
+
+        let stdout_capture = open_stdio_capture_file(&path).unwrap();
+        let stderr_capture = stdout_capture.try_clone().unwrap();
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg("echo from-stdout; echo from-stderr 1>&2")
+            .stdout(stdout_capture)
+            .stderr(stderr_capture)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let captured = std::fs::read_to_string(&path).unwrap();
+        assert!(captured.contains("from-stdout"));
+        assert!(captured.contains("from-stderr"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_stdio_capture_file_past_its_size_cap_is_rotated_aside_before_reopening() {
+        let dir = std::env::temp_dir().join("roset-csi-test-stdio-capture-rotate");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("vol-5.stdio.log");
+        std::fs::write(&path, vec![0u8; (STDIO_CAPTURE_MAX_BYTES + 1) as usize]).unwrap();
+
+        drop(open_stdio_capture_file(&path).unwrap());
+
+        let mut rotated = path.as_os_str().to_os_string();
+        rotated.push(".1");
+        assert!(std::path::Path::new(&rotated).exists());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_non_responding_backend_yields_a_deadline_exceeded_promptly_instead_of_hanging() {
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind test listener");
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            // Accept the connection but never write a response, so the
+            // client's only way out is its own timeout.
+            if let Ok((_stream, _)) = listener.accept() {
+                thread::sleep(Duration::from_secs(30));
+            }
+        });
+
+        let node = NodeService::new().with_api_timeout(Duration::from_millis(200));
+        let started = std::time::Instant::now();
+        let result = node.probe_backend(&format!("http://{addr}"));
+        let elapsed = started.elapsed();
+
+        assert!(matches!(result, Err(CsiError::DeadlineExceeded(_))));
+        assert!(elapsed < Duration::from_secs(2), "took {elapsed:?} to time out");
+    }
+
+    #[test]
+    fn repeated_backend_probes_reuse_a_single_connection_instead_of_opening_one_per_call() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind test listener");
+        let addr = listener.local_addr().unwrap();
+        let connections_accepted = Arc::new(AtomicUsize::new(0));
+        let accepted_for_server = connections_accepted.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                accepted_for_server.fetch_add(1, Ordering::SeqCst);
+                // A single accepted connection is kept alive and serves
+                // every request sent over it, mirroring a real keep-alive
+                // HTTP server; `reqwest`'s pooling is what's under test,
+                // not this stub's parsing, so requests are only skimmed
+                // for their terminating blank line.
+                thread::spawn(move || loop {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    loop {
+                        let mut line = String::new();
+                        match reader.read_line(&mut line) {
+                            Ok(0) => return,
+                            Ok(_) if line == "\r\n" => break,
+                            Ok(_) => continue,
+                            Err(_) => return,
+                        }
+                    }
+                    let response = "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: keep-alive\r\n\r\n";
+                    if stream.write_all(response.as_bytes()).is_err() {
+                        return;
+                    }
+                });
+            }
+        });
+
+        let node = NodeService::new().with_api_timeout(Duration::from_secs(2));
+        for _ in 0..5 {
+            node.probe_backend(&format!("http://{addr}")).expect("probe should succeed");
+        }
+
+        // All 5 probes go out through the one shared `reqwest::Client`, so
+        // its connection pool should serve them over far fewer TCP
+        // connections than calls made — a fresh `Client::new()` per call
+        // would instead accept one connection per probe.
+        assert!(
+            connections_accepted.load(Ordering::SeqCst) < 5,
+            "expected connection reuse, but {} separate connections were accepted",
+            connections_accepted.load(Ordering::SeqCst)
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::CsiError;
+
+/// Coordinates a graceful drain of in-flight [`crate::node::NodeService`]
+/// requests.
+///
+/// There's no `main.rs`/`tonic` server in this crate yet to catch SIGTERM
+/// and call `serve_with_incoming_shutdown` — but the pattern that server
+/// will need is exactly the one the mutating RPC handlers
+/// (`NodeService::stage_volume`, `NodeService::node_publish_volume`) need
+/// on their own regardless: once shutdown has begun, refuse new requests
+/// and let whatever's already in flight finish, bounded by a timeout,
+/// rather than being killed mid-`NodeStageVolume` and leaving a
+/// half-created mount or orphaned key file behind.
+#[derive(Debug, Default)]
+pub struct ShutdownCoordinator {
+    state: Mutex<State>,
+    drained: Condvar,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    shutting_down: bool,
+    in_flight: u64,
+}
+
+/// Held for the duration of one admitted request. Dropping it — on
+/// success, on an early error return, or on panic-unwind — always
+/// decrements the in-flight count and wakes anyone blocked in
+/// [`ShutdownCoordinator::wait_for_drain`].
+#[derive(Debug)]
+pub struct InFlightGuard<'a> {
+    coordinator: &'a ShutdownCoordinator,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.coordinator.state.lock().unwrap();
+        state.in_flight -= 1;
+        if state.in_flight == 0 {
+            self.coordinator.drained.notify_all();
+        }
+    }
+}
+
+impl ShutdownCoordinator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Admits a new request, unless shutdown has already begun — in which
+    /// case the gRPC boundary should map this to `Unavailable` rather than
+    /// starting work that's about to be torn down mid-flight.
+    pub fn admit(&self) -> Result<InFlightGuard<'_>, CsiError> {
+        let mut state = self.state.lock().unwrap();
+        if state.shutting_down {
+            return Err(CsiError::Unavailable(
+                "node service is shutting down, not accepting new requests".to_string(),
+            ));
+        }
+        state.in_flight += 1;
+        Ok(InFlightGuard { coordinator: self })
+    }
+
+    /// Stops admitting new requests. Idempotent.
+    pub fn begin_shutdown(&self) {
+        self.state.lock().unwrap().shutting_down = true;
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.state.lock().unwrap().shutting_down
+    }
+
+    /// Waits up to `timeout` for every already-admitted request to finish.
+    /// Returns `false` if the timeout elapsed with requests still in
+    /// flight — the caller exits anyway rather than hanging the pod's
+    /// termination grace period forever, but can at least log that the
+    /// drain wasn't clean.
+    pub fn wait_for_drain(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock().unwrap();
+        while state.in_flight > 0 {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return false;
+            }
+            let (next, result) = self.drained.wait_timeout(state, remaining).unwrap();
+            state = next;
+            if result.timed_out() {
+                return state.in_flight == 0;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn a_request_is_admitted_normally_before_shutdown_begins() {
+        let coordinator = ShutdownCoordinator::new();
+        let guard = coordinator.admit().unwrap();
+        drop(guard);
+        assert!(coordinator.wait_for_drain(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn new_requests_are_rejected_once_shutdown_has_begun() {
+        let coordinator = ShutdownCoordinator::new();
+        coordinator.begin_shutdown();
+        let err = coordinator.admit().unwrap_err();
+        assert!(matches!(err, CsiError::Unavailable(_)));
+    }
+
+    #[test]
+    fn an_in_flight_request_finishes_during_shutdown_while_new_ones_are_refused() {
+        let coordinator = Arc::new(ShutdownCoordinator::new());
+        let guard = coordinator.admit().unwrap();
+
+        // Shutdown begins while the request above is still in flight.
+        coordinator.begin_shutdown();
+        assert!(matches!(coordinator.admit(), Err(CsiError::Unavailable(_))));
+
+        let waiter = {
+            let coordinator = Arc::clone(&coordinator);
+            thread::spawn(move || coordinator.wait_for_drain(Duration::from_secs(5)))
+        };
+
+        // The in-flight request finishes (e.g. an RPC handler returning).
+        drop(guard);
+
+        assert!(waiter.join().unwrap());
+    }
+
+    #[test]
+    fn wait_for_drain_times_out_if_a_request_never_finishes() {
+        let coordinator = ShutdownCoordinator::new();
+        let _guard = coordinator.admit().unwrap();
+        coordinator.begin_shutdown();
+
+        assert!(!coordinator.wait_for_drain(Duration::from_millis(50)));
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use crate::error::CsiError;
+
+/// Parsed, validated contents of a CSI `volume_context`/`parameters` map.
+///
+/// These keys used to be read ad hoc wherever a handler happened to need
+/// one, with no shared defaulting or validation — a non-numeric
+/// `cacheSizeGi`, say, would sail through and only fail once `roset-fuse`
+/// itself tried to parse its cache-size argument, long after the
+/// gRPC call that supplied it had already returned success. Parsing
+/// through [`VolumeContext::parse`] at the top of `node_stage_volume`
+/// rejects a malformed value immediately with `invalid_argument`
+/// instead. The controller's `create_volume` takes no volume context
+/// and does not call this parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeContext {
+    pub mount_id: String,
+    pub root_path: String,
+    pub read_only: bool,
+    pub cache_dir: Option<String>,
+    pub cache_size_gi: Option<u64>,
+    pub read_ahead: Option<u64>,
+    pub subdir: Option<String>,
+    pub git_ref: Option<String>,
+    pub commit_id: Option<String>,
+}
+
+impl VolumeContext {
+    /// `mountId` and `rootPath` are required; everything else is optional
+    /// and defaults to `None`, leaving the caller free to apply its own
+    /// default (e.g. `roset-fuse`'s built-in cache size) rather than this
+    /// type guessing one on its behalf.
+    pub fn parse(context: &HashMap<String, String>) -> Result<Self, CsiError> {
+        Ok(Self {
+            mount_id: Self::required(context, "mountId")?,
+            root_path: Self::required(context, "rootPath")?,
+            read_only: Self::parse_bool(context, "readOnly")?.unwrap_or(false),
+            cache_dir: context.get("cacheDir").cloned(),
+            cache_size_gi: Self::parse_u64(context, "cacheSizeGi")?,
+            read_ahead: Self::parse_u64(context, "readAhead")?,
+            subdir: context.get("subdir").cloned(),
+            git_ref: context.get("ref").cloned(),
+            commit_id: context.get("commitId").cloned(),
+        })
+    }
+
+    fn required(context: &HashMap<String, String>, key: &str) -> Result<String, CsiError> {
+        context
+            .get(key)
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(str::to_string)
+            .ok_or_else(|| CsiError::InvalidArgument(format!("volume context is missing required '{key}'")))
+    }
+
+    fn parse_bool(context: &HashMap<String, String>, key: &str) -> Result<Option<bool>, CsiError> {
+        match context.get(key) {
+            None => Ok(None),
+            Some(raw) => raw.trim().parse::<bool>().map(Some).map_err(|_| {
+                CsiError::InvalidArgument(format!("volume context '{key}' must be 'true' or 'false', got '{raw}'"))
+            }),
+        }
+    }
+
+    fn parse_u64(context: &HashMap<String, String>, key: &str) -> Result<Option<u64>, CsiError> {
+        match context.get(key) {
+            None => Ok(None),
+            Some(raw) => raw.trim().parse::<u64>().map(Some).map_err(|_| {
+                CsiError::InvalidArgument(format!(
+                    "volume context '{key}' must be a non-negative integer, got '{raw}'"
+                ))
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn a_full_valid_context_parses_every_field() {
+        let ctx = context(&[
+            ("mountId", "vol-1"),
+            ("rootPath", "/datasets/vol-1"),
+            ("readOnly", "true"),
+            ("cacheDir", "/var/cache/roset"),
+            ("cacheSizeGi", "10"),
+            ("readAhead", "128"),
+            ("subdir", "train"),
+            ("ref", "main"),
+            ("commitId", "abc123"),
+        ]);
+
+        let parsed = VolumeContext::parse(&ctx).unwrap();
+        assert_eq!(parsed.mount_id, "vol-1");
+        assert_eq!(parsed.root_path, "/datasets/vol-1");
+        assert!(parsed.read_only);
+        assert_eq!(parsed.cache_dir, Some("/var/cache/roset".to_string()));
+        assert_eq!(parsed.cache_size_gi, Some(10));
+        assert_eq!(parsed.read_ahead, Some(128));
+        assert_eq!(parsed.subdir, Some("train".to_string()));
+        assert_eq!(parsed.git_ref, Some("main".to_string()));
+        assert_eq!(parsed.commit_id, Some("abc123".to_string()));
+    }
+
+    #[test]
+    fn a_minimal_context_defaults_the_optional_fields() {
+        let ctx = context(&[("mountId", "vol-1"), ("rootPath", "/datasets/vol-1")]);
+
+        let parsed = VolumeContext::parse(&ctx).unwrap();
+        assert!(!parsed.read_only);
+        assert_eq!(parsed.cache_size_gi, None);
+        assert_eq!(parsed.read_ahead, None);
+    }
+
+    #[test]
+    fn a_missing_required_field_is_rejected() {
+        let ctx = context(&[("rootPath", "/datasets/vol-1")]);
+        let err = VolumeContext::parse(&ctx).unwrap_err();
+        assert!(matches!(err, CsiError::InvalidArgument(msg) if msg.contains("mountId")));
+    }
+
+    #[test]
+    fn a_non_numeric_cache_size_is_rejected_instead_of_reaching_fuse() {
+        let ctx = context(&[
+            ("mountId", "vol-1"),
+            ("rootPath", "/datasets/vol-1"),
+            ("cacheSizeGi", "not-a-number"),
+        ]);
+        let err = VolumeContext::parse(&ctx).unwrap_err();
+        assert!(matches!(err, CsiError::InvalidArgument(msg) if msg.contains("cacheSizeGi")));
+    }
+
+    #[test]
+    fn a_non_numeric_read_ahead_is_rejected() {
+        let ctx = context(&[
+            ("mountId", "vol-1"),
+            ("rootPath", "/datasets/vol-1"),
+            ("readAhead", "lots"),
+        ]);
+        let err = VolumeContext::parse(&ctx).unwrap_err();
+        assert!(matches!(err, CsiError::InvalidArgument(msg) if msg.contains("readAhead")));
+    }
+
+    #[test]
+    fn a_non_boolean_read_only_is_rejected() {
+        let ctx = context(&[
+            ("mountId", "vol-1"),
+            ("rootPath", "/datasets/vol-1"),
+            ("readOnly", "yes"),
+        ]);
+        let err = VolumeContext::parse(&ctx).unwrap_err();
+        assert!(matches!(err, CsiError::InvalidArgument(msg) if msg.contains("readOnly")));
+    }
+}
@@ -0,0 +1,27 @@
+//! Runs the upstream `csi-sanity` conformance suite
+//! (github.com/kubernetes-csi/csi-test) against a live instance of this
+//! driver's gRPC endpoint.
+//!
+//! `csi-sanity` is a separate Go binary, not a Cargo dependency, so this
+//! is `#[ignore]`d by default and meant for the `csi-sanity` CI lane,
+//! which installs the binary and runs:
+//!   cargo test --test sanity -- --ignored
+//! against a driver bound to `$CSI_ENDPOINT` (a unix socket).
+
+use std::env;
+use std::process::Command;
+
+#[test]
+#[ignore = "requires the external csi-sanity binary and a running driver"]
+fn csi_sanity_suite_passes_against_the_running_driver() {
+    let endpoint = env::var("CSI_ENDPOINT")
+        .expect("CSI_ENDPOINT must point at the driver's unix socket for this test");
+
+    let status = Command::new("csi-sanity")
+        .arg("--csi.endpoint")
+        .arg(&endpoint)
+        .status()
+        .expect("failed to exec csi-sanity (is it installed and on PATH?)");
+
+    assert!(status.success(), "csi-sanity reported failures");
+}
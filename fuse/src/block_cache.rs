@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::cache::CachePolicy;
+
+/// Default size of one cached block. Large enough that a sequential
+/// reader of a dataset or checkpoint file amortizes one backend round
+/// trip over a meaningful amount of data, without caching so coarsely
+/// that a handful of small random reads each pull in megabytes they'll
+/// never revisit.
+pub const DEFAULT_BLOCK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Default TTL a mutable node's cached blocks are trusted for under
+/// `--read-cache-policy all` (see [`crate::fs::ReadCachePolicy`]), before
+/// [`BlockCache::get_or_fetch`] treats them as a miss and re-fetches.
+/// Immutable (committed) nodes' blocks are never subject to this — their
+/// content can't change underneath the cache, so they're cached
+/// indefinitely regardless of this TTL.
+pub const DEFAULT_MUTABLE_BLOCK_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Which block covers byte `offset`, given `block_size`.
+pub fn block_index_for(offset: u64, block_size: u64) -> u64 {
+    offset / block_size
+}
+
+/// The absolute byte range `[start, start + len)` covered by
+/// `block_index`, clamped to `file_size` for the file's final (possibly
+/// short) block.
+pub fn block_range(block_index: u64, block_size: u64, file_size: u64) -> (u64, u64) {
+    let start = block_index * block_size;
+    let end = (start + block_size).min(file_size);
+    (start, end.saturating_sub(start))
+}
+
+struct Entry {
+    size: u64,
+    last_used: Instant,
+    policy: CachePolicy,
+    /// `None` for an [`CachePolicy::Immutable`] entry, which never
+    /// expires by age. `Some` for a [`CachePolicy::Ttl`] entry.
+    expires_at: Option<Instant>,
+}
+
+/// Per-mount, disk-backed cache of file content blocks, keyed by
+/// `(node_id, block_index)`.
+///
+/// Unlike [`crate::shared_cache::SharedBlockCache`] — which is shared
+/// across every `roset-fuse` process on a node, keyed by content hash,
+/// and has no size budget of its own — this cache belongs to a single
+/// mount and enforces `capacity_bytes` itself, evicting the
+/// least-recently-used block once a newly-cached one would exceed it.
+/// Fronts [`crate::client::RosetClient::download_range`] in the read
+/// path so repeated reads of the same dataset (e.g. across training
+/// epochs) are served from local disk instead of re-fetched.
+///
+/// A committed/immutable node's blocks are correct for as long as they
+/// stay cached, so [`CachePolicy::Immutable`] entries are never
+/// proactively expired by age — only [`Self::invalidate_node`] or
+/// ordinary LRU pressure removes one. A [`CachePolicy::Ttl`] entry (a
+/// mutable node cached under `--read-cache-policy all`) is also treated
+/// as a miss once `mutable_ttl` elapses, the same way
+/// [`crate::cache::AttrCache`] expires its own `Ttl`-policy entries.
+pub struct BlockCache {
+    dir: PathBuf,
+    block_size: u64,
+    capacity_bytes: u64,
+    mutable_ttl: Duration,
+    entries: Mutex<HashMap<(String, u64), Entry>>,
+}
+
+impl BlockCache {
+    pub fn new(dir: impl Into<PathBuf>, block_size: u64, capacity_bytes: u64) -> io::Result<Self> {
+        Self::with_mutable_ttl(dir, block_size, capacity_bytes, DEFAULT_MUTABLE_BLOCK_CACHE_TTL)
+    }
+
+    /// Like [`Self::new`], overriding the default [`DEFAULT_MUTABLE_BLOCK_CACHE_TTL`].
+    pub fn with_mutable_ttl(
+        dir: impl Into<PathBuf>,
+        block_size: u64,
+        capacity_bytes: u64,
+        mutable_ttl: Duration,
+    ) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, block_size, capacity_bytes, mutable_ttl, entries: Mutex::new(HashMap::new()) })
+    }
+
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    fn path_for(&self, node_id: &str, block_index: u64) -> PathBuf {
+        self.dir.join(format!("{node_id}__{block_index}"))
+    }
+
+    /// Returns the cached block for `(node_id, block_index)` if present
+    /// and not expired under `policy`, otherwise calls `fetch` to produce
+    /// it, writes it to disk under `policy`, and returns it. Touches the
+    /// entry's LRU recency either way.
+    ///
+    /// `policy` is the caller's current belief about the node (e.g.
+    /// [`CachePolicy::Immutable`] for a committed node) and is re-applied
+    /// to the entry on every call, so a node that transitions from
+    /// mutable to immutable (or vice versa) doesn't stay stuck under its
+    /// first-seen policy.
+    pub fn get_or_fetch(
+        &self,
+        node_id: &str,
+        block_index: u64,
+        policy: CachePolicy,
+        fetch: impl FnOnce() -> io::Result<Vec<u8>>,
+    ) -> io::Result<Vec<u8>> {
+        let key = (node_id.to_string(), block_index);
+        let path = self.path_for(node_id, block_index);
+        if let Some(data) = self.touch(&key, &path, policy) {
+            return Ok(data);
+        }
+        let data = fetch()?;
+        self.insert(key, &path, &data, policy)?;
+        Ok(data)
+    }
+
+    fn touch(&self, key: &(String, u64), path: &Path, policy: CachePolicy) -> Option<Vec<u8>> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get_mut(key)?;
+        if let Some(expires_at) = entry.expires_at {
+            if expires_at < Instant::now() {
+                return None;
+            }
+        }
+        let data = fs::read(path).ok()?;
+        entry.last_used = Instant::now();
+        if entry.policy != policy {
+            entry.policy = policy;
+            entry.expires_at = match policy {
+                CachePolicy::Immutable => None,
+                CachePolicy::Ttl => Some(Instant::now() + self.mutable_ttl),
+            };
+        }
+        Some(data)
+    }
+
+    fn insert(&self, key: (String, u64), path: &Path, data: &[u8], policy: CachePolicy) -> io::Result<()> {
+        fs::write(path, data)?;
+        let now = Instant::now();
+        let expires_at = match policy {
+            CachePolicy::Immutable => None,
+            CachePolicy::Ttl => Some(now + self.mutable_ttl),
+        };
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key, Entry { size: data.len() as u64, last_used: now, policy, expires_at });
+        self.evict_to_capacity(&mut entries);
+        Ok(())
+    }
+
+    fn evict_to_capacity(&self, entries: &mut HashMap<(String, u64), Entry>) {
+        let mut total: u64 = entries.values().map(|e| e.size).sum();
+        while total > self.capacity_bytes {
+            let Some(lru_key) = entries.iter().min_by_key(|(_, e)| e.last_used).map(|(k, _)| k.clone()) else {
+                break;
+            };
+            if let Some(entry) = entries.remove(&lru_key) {
+                total -= entry.size;
+                let _ = fs::remove_file(self.path_for(&lru_key.0, lru_key.1));
+            }
+        }
+    }
+
+    /// Drops every cached block for `node_id`, e.g. after a write to a
+    /// mutable node invalidates the content previously cached for it.
+    pub fn invalidate_node(&self, node_id: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        let keys: Vec<_> = entries.keys().filter(|(id, _)| id == node_id).cloned().collect();
+        for key in keys {
+            entries.remove(&key);
+            let _ = fs::remove_file(self.path_for(&key.0, key.1));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn temp_cache_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("roset-fuse-block-cache-test-{name}-{}", std::process::id()))
+    }
+
+    #[test]
+    fn block_index_and_range_cover_a_files_blocks_including_a_short_final_one() {
+        assert_eq!(block_index_for(0, 10), 0);
+        assert_eq!(block_index_for(9, 10), 0);
+        assert_eq!(block_index_for(10, 10), 1);
+        assert_eq!(block_range(0, 10, 25), (0, 10));
+        assert_eq!(block_range(2, 10, 25), (20, 5));
+    }
+
+    #[test]
+    fn a_second_fetch_of_the_same_block_is_served_from_disk_without_calling_fetch_again() {
+        let dir = temp_cache_dir("hit");
+        let _ = fs::remove_dir_all(&dir);
+        let cache = BlockCache::new(&dir, DEFAULT_BLOCK_SIZE, 64 * 1024 * 1024).unwrap();
+
+        let fetch_calls = Arc::new(AtomicUsize::new(0));
+        let calls = fetch_calls.clone();
+        let data = cache.get_or_fetch("node-1", 0, CachePolicy::Immutable, move || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(b"block data".to_vec())
+        }).unwrap();
+        assert_eq!(data, b"block data");
+
+        let data = cache
+            .get_or_fetch("node-1", 0, CachePolicy::Immutable, || panic!("should not be called — block is already cached"))
+            .unwrap();
+        assert_eq!(data, b"block data");
+        assert_eq!(fetch_calls.load(Ordering::SeqCst), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn invalidating_a_node_forces_its_blocks_to_be_re_fetched() {
+        let dir = temp_cache_dir("invalidate");
+        let _ = fs::remove_dir_all(&dir);
+        let cache = BlockCache::new(&dir, DEFAULT_BLOCK_SIZE, 64 * 1024 * 1024).unwrap();
+
+        cache.get_or_fetch("node-1", 0, CachePolicy::Immutable, || Ok(b"v1".to_vec())).unwrap();
+        cache.invalidate_node("node-1");
+
+        let data = cache.get_or_fetch("node-1", 0, CachePolicy::Immutable, || Ok(b"v2".to_vec())).unwrap();
+        assert_eq!(data, b"v2");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn inserting_past_capacity_evicts_the_least_recently_used_block() {
+        let dir = temp_cache_dir("evict");
+        let _ = fs::remove_dir_all(&dir);
+        // Room for exactly one 10-byte block at a time.
+        let cache = BlockCache::new(&dir, 10, 10).unwrap();
+
+        cache.get_or_fetch("node-1", 0, CachePolicy::Immutable, || Ok(vec![1u8; 10])).unwrap();
+        cache.get_or_fetch("node-1", 1, CachePolicy::Immutable, || Ok(vec![2u8; 10])).unwrap();
+
+        // Block 0 should have been evicted to make room for block 1.
+        let fetch_calls = Arc::new(AtomicUsize::new(0));
+        let calls = fetch_calls.clone();
+        cache.get_or_fetch("node-1", 0, CachePolicy::Immutable, move || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![1u8; 10])
+        }).unwrap();
+        assert_eq!(fetch_calls.load(Ordering::SeqCst), 1, "evicted block should have been re-fetched");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_mutable_block_expires_on_its_ttl_while_an_immutable_one_persists() {
+        let dir = temp_cache_dir("ttl");
+        let _ = fs::remove_dir_all(&dir);
+        let cache = BlockCache::with_mutable_ttl(&dir, DEFAULT_BLOCK_SIZE, 64 * 1024 * 1024, Duration::from_millis(1)).unwrap();
+
+        cache.get_or_fetch("mutable", 0, CachePolicy::Ttl, || Ok(b"v1".to_vec())).unwrap();
+        cache.get_or_fetch("immutable", 0, CachePolicy::Immutable, || Ok(b"v1".to_vec())).unwrap();
+        std::thread::sleep(Duration::from_millis(10));
+
+        let data = cache.get_or_fetch("mutable", 0, CachePolicy::Ttl, || Ok(b"v2".to_vec())).unwrap();
+        assert_eq!(data, b"v2", "expired mutable block should have been re-fetched");
+
+        let fetch_calls = Arc::new(AtomicUsize::new(0));
+        let calls = fetch_calls.clone();
+        cache.get_or_fetch("immutable", 0, CachePolicy::Immutable, move || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Ok(b"v2".to_vec())
+        }).unwrap();
+        assert_eq!(fetch_calls.load(Ordering::SeqCst), 0, "immutable block should never expire");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
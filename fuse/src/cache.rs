@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::node::Node;
+
+/// How aggressively cached entries may be trusted.
+///
+/// `Immutable` is used for snapshot-backed (committed) mounts, where the
+/// backend content can't change underneath the cache, so entries never
+/// need to be revalidated once fetched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    Ttl,
+    Immutable,
+}
+
+struct Entry {
+    node: Option<Node>,
+    expires_at: Instant,
+    policy: CachePolicy,
+    checked_at: Instant,
+}
+
+/// How often a still-unexpired negative entry (a cached `ENOENT`) should
+/// be probabilistically re-checked against the backend instead of trusted
+/// outright, so a file created out-of-band by another process surfaces
+/// before the negative TTL would otherwise expire.
+///
+/// Disabled (`probability: 0.0`) by default: revalidating trades extra
+/// backend calls for fresher negative results, so it's opt-in rather than
+/// a cost every mount pays.
+#[derive(Debug, Clone, Copy)]
+pub struct NegativeRevalidationPolicy {
+    /// Chance, per lookup that hits a negative entry past `min_interval`,
+    /// that it's revalidated instead of served from cache.
+    pub probability: f64,
+    /// Floor between revalidation attempts for the same path, so a name
+    /// that's `stat`'d in a loop doesn't hit the backend on every call.
+    pub min_interval: Duration,
+}
+
+impl Default for NegativeRevalidationPolicy {
+    fn default() -> Self {
+        Self {
+            probability: 0.0,
+            min_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+/// A path-prefix rule overriding the cache's default TTL/immutability for
+/// everything under it, e.g. a huge TTL for an immutable `datasets/`
+/// prefix alongside a near-zero TTL for a volatile `scratch/` prefix
+/// served by the same mount. The longest matching prefix wins.
+#[derive(Debug, Clone)]
+pub struct PrefixPolicy {
+    pub prefix: String,
+    pub policy: CachePolicy,
+    pub ttl: Duration,
+}
+
+/// TTL cache of resolved nodes, keyed by path.
+///
+/// A `None` entry is a negative cache entry (a previous lookup that
+/// resulted in `ENOENT`), so repeated misses for the same path don't each
+/// hit the API.
+pub struct AttrCache {
+    ttl: Duration,
+    policy: Mutex<CachePolicy>,
+    prefix_policies: Mutex<Vec<PrefixPolicy>>,
+    negative_revalidation: Mutex<NegativeRevalidationPolicy>,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl AttrCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            policy: Mutex::new(CachePolicy::Ttl),
+            prefix_policies: Mutex::new(Vec::new()),
+            negative_revalidation: Mutex::new(NegativeRevalidationPolicy::default()),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Overrides the default (disabled) [`NegativeRevalidationPolicy`].
+    pub fn set_negative_revalidation_policy(&self, policy: NegativeRevalidationPolicy) {
+        *self.negative_revalidation.lock().unwrap() = policy;
+    }
+
+    pub fn set_policy(&self, policy: CachePolicy) {
+        *self.policy.lock().unwrap() = policy;
+    }
+
+    /// Installs path-prefix overrides, consulted by [`Self::put`] for
+    /// every node stored from then on. Replaces any previously configured
+    /// overrides.
+    pub fn set_prefix_policies(&self, policies: Vec<PrefixPolicy>) {
+        *self.prefix_policies.lock().unwrap() = policies;
+    }
+
+    /// The `(policy, ttl)` that applies to `path`: the longest matching
+    /// prefix override, or this cache's default otherwise.
+    fn effective_policy_for(&self, path: &str) -> (CachePolicy, Duration) {
+        let overrides = self.prefix_policies.lock().unwrap();
+        overrides
+            .iter()
+            .filter(|p| path.starts_with(p.prefix.as_str()))
+            .max_by_key(|p| p.prefix.len())
+            .map(|p| (p.policy, p.ttl))
+            .unwrap_or_else(|| (*self.policy.lock().unwrap(), self.ttl))
+    }
+
+    pub fn get(&self, path: &str) -> Option<Option<Node>> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(path)?;
+        if entry.policy == CachePolicy::Ttl && entry.expires_at < Instant::now() {
+            return None;
+        }
+        Some(entry.node.clone())
+    }
+
+    /// Returns a cached entry regardless of whether its TTL has expired.
+    /// Used for `--allow-offline` degraded-mode reads, where serving
+    /// stale data beats failing the whole op with `EIO`.
+    pub fn get_allow_stale(&self, path: &str) -> Option<Option<Node>> {
+        self.entries.lock().unwrap().get(path).map(|e| e.node.clone())
+    }
+
+    pub fn put(&self, path: String, node: Option<Node>) {
+        let (policy, ttl) = self.effective_policy_for(&path);
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(
+            path,
+            Entry {
+                node,
+                expires_at: Instant::now() + ttl,
+                policy,
+                checked_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Whether a cached negative entry for `path` should be revalidated
+    /// against the backend right now rather than trusted, per the
+    /// configured [`NegativeRevalidationPolicy`]. Callers that get `true`
+    /// back are expected to immediately refetch and [`Self::put`] the
+    /// result, so this resets `path`'s revalidation clock as it returns —
+    /// concurrent lookups for the same still-missing path within
+    /// `min_interval` won't each trigger their own backend call.
+    ///
+    /// Always `false` for a positive entry or an `Immutable`-policy one;
+    /// there's nothing to revalidate a known-present node against, and an
+    /// immutable mount's negatives are as permanent as its positives.
+    pub fn should_revalidate_negative(&self, path: &str) -> bool {
+        let policy = *self.negative_revalidation.lock().unwrap();
+        if policy.probability <= 0.0 {
+            return false;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let Some(entry) = entries.get_mut(path) else {
+            return false;
+        };
+        if entry.node.is_some() || entry.policy == CachePolicy::Immutable {
+            return false;
+        }
+        if entry.checked_at.elapsed() < policy.min_interval {
+            return false;
+        }
+        if rand::random::<f64>() >= policy.probability {
+            return false;
+        }
+        entry.checked_at = Instant::now();
+        true
+    }
+
+    /// Drops the cached entry for `path`, if any.
+    pub fn invalidate_node(&self, path: &str) {
+        self.entries.lock().unwrap().remove(path);
+    }
+
+    /// Drops `path` and every cached entry nested under it (including
+    /// negative entries), for use when a directory's contents may have
+    /// changed out from under the cache.
+    pub fn invalidate_children(&self, path: &str) {
+        let prefix = if path.ends_with('/') {
+            path.to_string()
+        } else {
+            format!("{path}/")
+        };
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|key, _| key != path && !key.starts_with(&prefix));
+    }
+
+    /// Returns every cached entry at `path` or nested under it — positive
+    /// or negative, regardless of TTL — paired with their paths. Unlike
+    /// [`Self::invalidate_children`], this only reads; used by
+    /// [`crate::fs::RosetFs::verify_subtree`], which needs to inspect the
+    /// cache's own belief about a subtree without disturbing it the way a
+    /// live lookup through [`Self::get`] would.
+    pub fn snapshot_with_prefix(&self, path: &str) -> Vec<(String, Option<Node>)> {
+        let prefix = if path.ends_with('/') {
+            path.to_string()
+        } else {
+            format!("{path}/")
+        };
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .filter(|(key, _)| key.as_str() != path && key.starts_with(&prefix))
+            .map(|(key, entry)| (key.clone(), entry.node.clone()))
+            .collect()
+    }
+
+    /// Drops every cached entry, positive or negative. Used by a soft
+    /// recovery from a network partition (see
+    /// [`crate::fs::RosetFs::handle_recover_xattr`]), where stale data
+    /// cached while the backend was unreachable shouldn't outlive the
+    /// partition rather than being trusted for the rest of its TTL.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_policy_gives_an_immutable_huge_ttl_and_a_volatile_prefix_expires_quickly() {
+        let cache = AttrCache::new(Duration::from_secs(30));
+        cache.set_prefix_policies(vec![
+            PrefixPolicy {
+                prefix: "/datasets/".to_string(),
+                policy: CachePolicy::Immutable,
+                ttl: Duration::from_secs(3600),
+            },
+            PrefixPolicy {
+                prefix: "/scratch/".to_string(),
+                policy: CachePolicy::Ttl,
+                ttl: Duration::from_millis(1),
+            },
+        ]);
+
+        cache.put("/datasets/big.bin".to_string(), None);
+        cache.put("/scratch/tmp.bin".to_string(), None);
+
+        std::thread::sleep(Duration::from_millis(10));
+
+        // Immutable prefix: still served even though its (huge) TTL
+        // hasn't elapsed, and would be served regardless since it's
+        // immutable.
+        assert!(cache.get("/datasets/big.bin").is_some());
+        // Volatile prefix: its near-zero TTL has already elapsed.
+        assert!(cache.get("/scratch/tmp.bin").is_none());
+    }
+
+    #[test]
+    fn unconfigured_paths_fall_back_to_the_cache_wide_default() {
+        let cache = AttrCache::new(Duration::from_secs(60));
+        cache.set_prefix_policies(vec![PrefixPolicy {
+            prefix: "/datasets/".to_string(),
+            policy: CachePolicy::Immutable,
+            ttl: Duration::from_secs(3600),
+        }]);
+
+        cache.put("/other/file.txt".to_string(), None);
+
+        assert!(cache.get("/other/file.txt").is_some());
+    }
+
+    #[test]
+    fn negative_revalidation_is_disabled_by_default() {
+        let cache = AttrCache::new(Duration::from_secs(60));
+        cache.put("/missing".to_string(), None);
+        assert!(!cache.should_revalidate_negative("/missing"));
+    }
+
+    #[test]
+    fn a_certain_policy_always_revalidates_past_min_interval() {
+        let cache = AttrCache::new(Duration::from_secs(60));
+        cache.set_negative_revalidation_policy(NegativeRevalidationPolicy {
+            probability: 1.0,
+            min_interval: Duration::from_millis(1),
+        });
+        cache.put("/missing".to_string(), None);
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert!(cache.should_revalidate_negative("/missing"));
+        // The clock reset when the first call decided to revalidate, so a
+        // second call right away shouldn't also trigger one.
+        assert!(!cache.should_revalidate_negative("/missing"));
+    }
+
+    #[test]
+    fn revalidation_never_applies_to_a_positive_or_immutable_entry() {
+        let cache = AttrCache::new(Duration::from_secs(60));
+        cache.set_negative_revalidation_policy(NegativeRevalidationPolicy {
+            probability: 1.0,
+            min_interval: Duration::from_millis(0),
+        });
+        cache.set_policy(CachePolicy::Immutable);
+        cache.put("/immutable-missing".to_string(), None);
+        assert!(!cache.should_revalidate_negative("/immutable-missing"));
+
+        cache.set_policy(CachePolicy::Ttl);
+        cache.put("/present".to_string(), Some(test_node("present")));
+        assert!(!cache.should_revalidate_negative("/present"));
+    }
+
+    fn test_node(name: &str) -> Node {
+        Node {
+            id: name.to_string(),
+            name: name.to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn invalidate_children_drops_nested_and_negative_entries() {
+        let cache = AttrCache::new(Duration::from_secs(60));
+        cache.put("/dir".to_string(), None);
+        cache.put("/dir/a".to_string(), None);
+        cache.put("/dir/b".to_string(), None);
+        cache.put("/other".to_string(), None);
+
+        cache.invalidate_children("/dir");
+
+        assert!(cache.get("/dir/a").is_none());
+        assert!(cache.get("/dir/b").is_none());
+        assert!(cache.get("/other").is_some());
+    }
+
+    #[test]
+    fn snapshot_with_prefix_includes_nested_and_negative_entries_but_not_siblings() {
+        let cache = AttrCache::new(Duration::from_secs(60));
+        cache.put("/dir".to_string(), Some(test_node("dir")));
+        cache.put("/dir/a".to_string(), Some(test_node("a")));
+        cache.put("/dir/b".to_string(), None);
+        cache.put("/other".to_string(), None);
+
+        let mut snapshot = cache.snapshot_with_prefix("/dir");
+        snapshot.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].0, "/dir/a");
+        assert!(snapshot[0].1.is_some());
+        assert_eq!(snapshot[1].0, "/dir/b");
+        assert!(snapshot[1].1.is_none());
+    }
+
+    #[test]
+    fn clear_drops_every_entry_regardless_of_path() {
+        let cache = AttrCache::new(Duration::from_secs(60));
+        cache.put("/a".to_string(), Some(test_node("a")));
+        cache.put("/b".to_string(), None);
+
+        cache.clear();
+
+        assert!(cache.get("/a").is_none());
+        assert!(cache.get("/b").is_none());
+    }
+}
@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, Ordering};
+use std::time::{Duration, Instant};
+
+/// Protects the mount (and the struggling backend) from a broad API
+/// outage: instead of every FUSE op attempting its full retry schedule
+/// against a backend that's failing wholesale, the circuit opens after a
+/// run of consecutive failures and fast-fails new requests for a
+/// cooldown, then half-opens to let a single probe through.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: AtomicU8,
+    consecutive_failures: AtomicU32,
+    opened_at_millis: AtomicU64,
+    start: Instant,
+}
+
+const STATE_CLOSED: u8 = 0;
+const STATE_OPEN: u8 = 1;
+const STATE_HALF_OPEN: u8 = 2;
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            state: AtomicU8::new(STATE_CLOSED),
+            consecutive_failures: AtomicU32::new(0),
+            opened_at_millis: AtomicU64::new(0),
+            start: Instant::now(),
+        }
+    }
+
+    fn now_millis(&self) -> u64 {
+        self.start.elapsed().as_millis() as u64
+    }
+
+    /// Whether a new request should be allowed through right now. Moves
+    /// an expired `Open` circuit to `HalfOpen` as a side effect, allowing
+    /// exactly the caller that observes this transition to send the
+    /// probe request.
+    pub fn allow_request(&self) -> bool {
+        match self.state.load(Ordering::SeqCst) {
+            STATE_CLOSED => true,
+            STATE_HALF_OPEN => false,
+            _ => {
+                let opened_at = self.opened_at_millis.load(Ordering::SeqCst);
+                self.now_millis().saturating_sub(opened_at) >= self.cooldown.as_millis() as u64
+                    && self
+                        .state
+                        .compare_exchange(STATE_OPEN, STATE_HALF_OPEN, Ordering::SeqCst, Ordering::SeqCst)
+                        .is_ok()
+            }
+        }
+    }
+
+    /// Records a successful call: closes the circuit and clears the
+    /// failure streak.
+    pub fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::SeqCst);
+        self.state.store(STATE_CLOSED, Ordering::SeqCst);
+    }
+
+    /// Records a failed call. Opens the circuit once
+    /// `failure_threshold` consecutive failures accrue, or immediately
+    /// re-opens it if the failing call was the half-open probe.
+    pub fn record_failure(&self) {
+        let state = self.state.load(Ordering::SeqCst);
+        if state == STATE_HALF_OPEN {
+            self.open();
+            return;
+        }
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures >= self.failure_threshold {
+            self.open();
+        }
+    }
+
+    fn open(&self) {
+        self.opened_at_millis.store(self.now_millis(), Ordering::SeqCst);
+        self.state.store(STATE_OPEN, Ordering::SeqCst);
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == STATE_OPEN
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_n_consecutive_failures_and_fast_fails() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        assert!(breaker.allow_request());
+
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(!breaker.is_open());
+        breaker.record_failure();
+
+        assert!(breaker.is_open());
+        assert!(!breaker.allow_request());
+    }
+
+    #[test]
+    fn a_success_between_failures_resets_the_streak() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+        breaker.record_failure();
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        breaker.record_failure();
+
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn half_open_probe_failure_reopens_the_circuit() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(50));
+        breaker.record_failure();
+        assert!(breaker.is_open());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.allow_request()); // cooldown elapsed -> half-open probe allowed
+        breaker.record_failure();
+
+        // Reopened immediately by the failed probe, with a fresh cooldown
+        // window, so a request right after should be denied again.
+        assert!(breaker.is_open());
+        assert!(!breaker.allow_request());
+    }
+}
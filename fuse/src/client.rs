@@ -0,0 +1,2218 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::circuit_breaker::CircuitBreaker;
+use crate::retry::{BackoffConfig, Deadline, ErrorClass};
+
+/// Callback invoked when an operation (and its retries) takes longer
+/// than [`RosetClient::with_slow_op_threshold`]'s configured threshold.
+type SlowOpHook = Arc<dyn Fn(&str, Duration) + Send + Sync>;
+
+/// A lease held on a node to guard against concurrent writers.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Lease {
+    pub node_id: String,
+    pub lease_id: String,
+    /// Unix timestamp the backend will let this lease lapse at, if it
+    /// reported one. `None` means the lease doesn't expire on its own
+    /// (e.g. a backend that only releases leases explicitly), so nothing
+    /// needs to renew it — see [`RosetClient::renew_lease`].
+    #[serde(default)]
+    pub expires_at_unix_secs: Option<u64>,
+}
+
+/// Tunables for the underlying `reqwest::Client` connection pool.
+///
+/// Defaults are sized for the ML read fan-out case (many small files
+/// read by many threads against a handful of hosts), which wants more
+/// idle connections held open than reqwest's own defaults.
+#[derive(Debug, Clone, Copy)]
+pub struct HttpPoolConfig {
+    pub max_idle_per_host: usize,
+    pub idle_timeout: Duration,
+}
+
+impl Default for HttpPoolConfig {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: 32,
+            idle_timeout: Duration::from_secs(90),
+        }
+    }
+}
+
+/// The outcome of a request sent through [`RosetClient::execute_request`]:
+/// the final status code, response headers, and the (already-consumed)
+/// response body.
+#[derive(Debug)]
+pub struct ApiResponse {
+    pub status: reqwest::StatusCode,
+    pub headers: reqwest::header::HeaderMap,
+    pub body: bytes::Bytes,
+}
+
+/// Thin wrapper around the Roset HTTP API used by the FUSE layer.
+///
+/// Cheaply `Clone`able (the underlying `reqwest::Client` and circuit
+/// breaker are both already reference-counted internally) so one
+/// process serving several mounts can share a single connection pool —
+/// see [`crate::mount::build_mount`] — instead of each mount opening its
+/// own.
+#[derive(Clone)]
+pub struct RosetClient {
+    base_url: String,
+    http: reqwest::Client,
+    backoff: BackoffConfig,
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+    slow_op_threshold: Option<Duration>,
+    on_slow_op: Option<SlowOpHook>,
+    /// When set, every outbound request's URL host must match one of
+    /// these patterns (exact host, or `*.domain` suffix) or the request
+    /// is rejected before it's ever sent — see
+    /// [`Self::with_allowed_storage_hosts`].
+    allowed_storage_hosts: Option<Vec<String>>,
+    /// Sent as the [`MOUNT_ID_HEADER`] on every request (see
+    /// [`Self::with_mount_id`]), so an endpoint with no body to carry a
+    /// mount id in (`get_node`, `list_children`, `download_range`, ...)
+    /// is still scoped to the right mount. `"unknown"` until a mount
+    /// calls `with_mount_id`, matching [`build_user_agent`]'s fallback.
+    mount_id: String,
+}
+
+/// Header carrying the mount id on every request, so a multi-tenant
+/// backend can scope a node id lookup to the right mount even on
+/// endpoints (`GET` requests, mostly) with no JSON body to embed it in.
+/// Set centrally in [`RosetClient::execute_request_inner`] from
+/// [`RosetClient::with_mount_id`] rather than left to each call site, so
+/// no new endpoint can forget it.
+pub const MOUNT_ID_HEADER: &str = "X-Roset-Mount-Id";
+
+/// Default consecutive-failure threshold before the circuit opens.
+pub const DEFAULT_CIRCUIT_BREAKER_THRESHOLD: u32 = 5;
+
+/// Default cooldown before an open circuit allows a half-open probe.
+pub const DEFAULT_CIRCUIT_BREAKER_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Builds the `User-Agent` sent on every request: `roset-fuse/<version>`,
+/// plus `(mount_id=...)` context and any operator-supplied suffix so
+/// server-side logs and rate-limiting can tell FUSE traffic apart from
+/// CSI control-plane traffic and identify the client version.
+pub fn build_user_agent(mount_id: &str, suffix: Option<&str>) -> String {
+    let mut ua = format!("roset-fuse/{} (mount_id={mount_id})", env!("CARGO_PKG_VERSION"));
+    if let Some(suffix) = suffix {
+        ua.push(' ');
+        ua.push_str(suffix);
+    }
+    ua
+}
+
+impl RosetClient {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_pool_config(base_url, HttpPoolConfig::default())
+    }
+
+    pub fn with_pool_config(base_url: impl Into<String>, pool: HttpPoolConfig) -> Self {
+        Self::with_pool_config_and_user_agent(base_url, pool, build_user_agent("unknown", None))
+    }
+
+    pub fn with_pool_config_and_user_agent(
+        base_url: impl Into<String>,
+        pool: HttpPoolConfig,
+        user_agent: String,
+    ) -> Self {
+        let http = Self::build_http_client(pool, &user_agent);
+        Self {
+            base_url: base_url.into(),
+            http,
+            backoff: BackoffConfig::default(),
+            circuit_breaker: None,
+            slow_op_threshold: None,
+            on_slow_op: None,
+            allowed_storage_hosts: None,
+            mount_id: "unknown".to_string(),
+        }
+    }
+
+    /// Redirects are always followed manually (see
+    /// [`Self::execute_with_redirects`]) rather than by `reqwest` itself,
+    /// so headers like `Range` survive a redirect hop instead of being
+    /// silently dropped, and so [`Self::with_allowed_storage_hosts`] gets
+    /// a chance to validate the redirect target too.
+    fn build_http_client(pool: HttpPoolConfig, user_agent: &str) -> reqwest::Client {
+        reqwest::Client::builder()
+            .pool_max_idle_per_host(pool.max_idle_per_host)
+            .pool_idle_timeout(pool.idle_timeout)
+            .user_agent(user_agent.to_string())
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .expect("reqwest client config is valid")
+    }
+
+    /// Restricts every outbound request (including redirect targets — see
+    /// [`Self::execute_with_redirects`]) to hosts matching `hosts` (an
+    /// exact host, or a `*.domain` suffix pattern), rejecting anything
+    /// else with an error before a request is ever sent — an SSRF guard
+    /// against a compromised or misconfigured backend steering
+    /// [`Self::download_range`]/[`Self::get_inline_content`] at an
+    /// unexpected (e.g. internal) host. A no-op (the allow-list stays
+    /// disabled) if `hosts` is empty.
+    pub fn with_allowed_storage_hosts(mut self, hosts: Vec<String>) -> Self {
+        if hosts.is_empty() {
+            return self;
+        }
+        self.allowed_storage_hosts = Some(hosts);
+        self
+    }
+
+    /// Whether `host` matches one of `patterns`: either exactly (case
+    /// insensitively), or as a subdomain of a `*.domain` pattern.
+    fn host_is_allowed(patterns: &[String], host: &str) -> bool {
+        patterns.iter().any(|pattern| match pattern.strip_prefix("*.") {
+            Some(suffix) => host.eq_ignore_ascii_case(suffix) || host.to_ascii_lowercase().ends_with(&format!(".{}", suffix.to_ascii_lowercase())),
+            None => host.eq_ignore_ascii_case(pattern),
+        })
+    }
+
+    /// Scopes every request this client sends to `mount_id` via the
+    /// [`MOUNT_ID_HEADER`]. Cheap to call on a clone of a
+    /// connection-pool-sharing client (see [`crate::mount::build_mount`]),
+    /// since only this field differs between mounts sharing one pool.
+    pub fn with_mount_id(mut self, mount_id: impl Into<String>) -> Self {
+        self.mount_id = mount_id.into();
+        self
+    }
+
+    pub fn with_backoff_config(mut self, backoff: BackoffConfig) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Enables a circuit breaker so a broad backend outage fast-fails
+    /// new requests instead of letting every FUSE op burn its full retry
+    /// schedule against a backend that's already down.
+    pub fn with_circuit_breaker(mut self, failure_threshold: u32, cooldown: Duration) -> Self {
+        self.circuit_breaker = Some(Arc::new(CircuitBreaker::new(failure_threshold, cooldown)));
+        self
+    }
+
+    /// Warns whenever a call through [`Self::execute_request`] (including
+    /// its retries) takes longer than `threshold`, so "the mount is slow"
+    /// reports can be traced back to a specific backend operation instead
+    /// of staying opaque. `on_slow_op`, when given, replaces the default
+    /// `eprintln!` so a caller (e.g. a test, or a future metrics sink) can
+    /// observe the warning instead of just reading stderr.
+    pub fn with_slow_op_threshold(
+        mut self,
+        threshold: Duration,
+        on_slow_op: Option<SlowOpHook>,
+    ) -> Self {
+        self.slow_op_threshold = Some(threshold);
+        self.on_slow_op = on_slow_op;
+        self
+    }
+
+    /// Creates a commit (snapshot) of `node_id`'s current state, mirroring
+    /// the CSI controller's `create_snapshot` flow but reachable from the
+    /// FUSE side. Returns the new commit id.
+    pub async fn create_commit(&self, node_id: &str, message: &str) -> Result<String, String> {
+        #[derive(serde::Deserialize)]
+        struct CommitResponse {
+            id: String,
+        }
+        let url = format!("{}/v1/commits", self.base_url);
+        let resp = self
+            .http
+            .post(url)
+            .header(MOUNT_ID_HEADER, self.mount_id.as_str())
+            .json(&serde_json::json!({ "node_id": node_id, "message": message }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        resp.json::<CommitResponse>()
+            .await
+            .map(|c| c.id)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Sends `request`, retrying on 429/5xx/network errors with
+    /// exponential backoff capped per [`ErrorClass`] (see
+    /// [`BackoffConfig`]). A 4xx response is normally fatal, but if its
+    /// JSON body's `code` field appears in `retryable_client_error_codes`
+    /// it's treated as transient too (e.g. "upload session not ready"),
+    /// since some eventual-consistency conditions surface as 4xx. Gives
+    /// up and returns the last response/error after `max_attempts`. The
+    /// body is read eagerly (rather than returning a live
+    /// `reqwest::Response`) so it can be inspected here for
+    /// classification without consuming it out from under the caller.
+    ///
+    /// `deadline`, when set, bounds the total wall-clock time spent
+    /// retrying regardless of `max_attempts` — pass the *same* deadline
+    /// into every sub-call that makes up one FUSE operation (e.g. a
+    /// download-URL refresh followed by the download itself) so the op
+    /// as a whole can't outlive its budget. Once expired, no further
+    /// attempts are made and a timeout error is returned immediately,
+    /// even if an earlier attempt already got a (retryable) response.
+    ///
+    /// `op` names the call for [`Self::with_slow_op_threshold`]'s
+    /// warning; it plays no role in retry/circuit-breaker behavior.
+    pub async fn execute_request(
+        &self,
+        op: &str,
+        build_request: impl FnMut() -> reqwest::RequestBuilder,
+        max_attempts: u32,
+        retryable_client_error_codes: &[&str],
+        deadline: Option<&Deadline>,
+    ) -> Result<ApiResponse, String> {
+        let start = std::time::Instant::now();
+        let result = self
+            .execute_request_inner(build_request, max_attempts, retryable_client_error_codes, deadline)
+            .await;
+        self.check_slow_op(op, start.elapsed());
+        result
+    }
+
+    /// Logs (or, if [`Self::with_slow_op_threshold`] was given a hook,
+    /// invokes the hook instead of logging) when `elapsed` exceeds the
+    /// configured threshold, so a slow backend call surfaces with the
+    /// operation name and duration rather than just a vague "the mount
+    /// is slow" report.
+    fn check_slow_op(&self, op: &str, elapsed: Duration) {
+        let Some(threshold) = self.slow_op_threshold else {
+            return;
+        };
+        if elapsed < threshold {
+            return;
+        }
+        match &self.on_slow_op {
+            Some(hook) => hook(op, elapsed),
+            None => eprintln!(
+                "roset-fuse: slow operation '{op}' took {elapsed:?} (threshold {threshold:?})"
+            ),
+        }
+    }
+
+    async fn execute_request_inner(
+        &self,
+        mut build_request: impl FnMut() -> reqwest::RequestBuilder,
+        max_attempts: u32,
+        retryable_client_error_codes: &[&str],
+        deadline: Option<&Deadline>,
+    ) -> Result<ApiResponse, String> {
+        if let Some(breaker) = &self.circuit_breaker {
+            if !breaker.allow_request() {
+                return Err("circuit breaker open: backend is failing, fast-failing".to_string());
+            }
+        }
+
+        let mut attempt = 0;
+        loop {
+            if deadline.is_some_and(Deadline::is_expired) {
+                self.record_circuit_failure();
+                return Err("operation deadline exceeded".to_string());
+            }
+
+            let sent: Result<reqwest::Response, String> = match build_request()
+                .header(MOUNT_ID_HEADER, self.mount_id.as_str())
+                .build()
+            {
+                Ok(request) => match self.check_allowed_host(&request) {
+                    Ok(()) => self.execute_with_redirects(request).await,
+                    Err(e) => {
+                        self.record_circuit_failure();
+                        return Err(e);
+                    }
+                },
+                Err(e) => Err(e.to_string()),
+            };
+            let (status, headers, body) = match sent {
+                Ok(resp) => {
+                    let status = resp.status();
+                    let headers = resp.headers().clone();
+                    let body = resp.bytes().await.map_err(|e| e.to_string())?;
+                    (status, headers, body)
+                }
+                Err(e) if attempt + 1 >= max_attempts => {
+                    self.record_circuit_failure();
+                    return Err(e);
+                }
+                Err(_) => {
+                    tokio::time::sleep(self.backoff.backoff_for(ErrorClass::Network, attempt))
+                        .await;
+                    attempt += 1;
+                    continue;
+                }
+            };
+
+            let class = if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+                Some(ErrorClass::RateLimit)
+            } else if status.is_server_error() {
+                Some(ErrorClass::ServerError)
+            } else if status.is_client_error() {
+                let code = serde_json::from_slice::<serde_json::Value>(&body)
+                    .ok()
+                    .and_then(|v| v.get("code").and_then(|c| c.as_str()).map(str::to_string));
+                code.as_deref()
+                    .filter(|c| retryable_client_error_codes.contains(c))
+                    .map(|_| ErrorClass::Network)
+            } else {
+                None
+            };
+
+            if status.is_success() && Self::looks_like_proxy_error_page(&headers, &body) {
+                // `execute_request` is shared by JSON metadata endpoints
+                // and raw-content endpoints (downloads), so this can't
+                // reject every non-JSON 2xx — that would break every
+                // binary download. An HTML body on a "successful" response
+                // is instead almost always a misconfigured proxy/load
+                // balancer serving an error page instead of forwarding to
+                // the backend, worth one retry as a one-off hiccup rather
+                // than the cryptic `serde_json` decode error a JSON
+                // caller's `from_slice::<T>` would otherwise surface.
+                if attempt == 0 {
+                    tokio::time::sleep(self.backoff.backoff_for(ErrorClass::ServerError, attempt))
+                        .await;
+                    attempt += 1;
+                    continue;
+                }
+                self.record_circuit_failure();
+                return Err(format!(
+                    "server returned a successful ({status}) response that looks like an HTML error page, not the expected API response: {}",
+                    Self::redact_unexpected_body(&body)
+                ));
+            }
+
+            match class {
+                None => {
+                    self.record_circuit_success();
+                    return Ok(ApiResponse { status, headers, body });
+                }
+                Some(_) if attempt + 1 >= max_attempts => {
+                    self.record_circuit_failure();
+                    return Ok(ApiResponse { status, headers, body });
+                }
+                Some(class) => {
+                    tokio::time::sleep(self.backoff.backoff_for(class, attempt)).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    fn record_circuit_success(&self) {
+        if let Some(breaker) = &self.circuit_breaker {
+            breaker.record_success();
+        }
+    }
+
+    fn record_circuit_failure(&self) {
+        if let Some(breaker) = &self.circuit_breaker {
+            breaker.record_failure();
+        }
+    }
+
+    /// Whether the backend currently looks unreachable: the circuit
+    /// breaker has opened after a run of consecutive failures (see
+    /// [`CircuitBreaker::is_open`]). `false` when no circuit breaker is
+    /// configured, since there's then no sustained-failure signal to
+    /// report. This is the reachability probe [`crate::fs::RosetFs::is_ready`]
+    /// exposes to a supervisor that can't otherwise see past the mount
+    /// point into this process's view of the backend.
+    pub fn is_unreachable(&self) -> bool {
+        self.circuit_breaker.as_ref().is_some_and(|breaker| breaker.is_open())
+    }
+
+    /// When [`Self::with_allowed_storage_hosts`] is in effect, rejects
+    /// `request` before it's sent if its URL's host isn't in the
+    /// allow-list. A no-op (always `Ok`) when no allow-list was
+    /// configured, which is the default.
+    fn check_allowed_host(&self, request: &reqwest::Request) -> Result<(), String> {
+        let Some(hosts) = &self.allowed_storage_hosts else {
+            return Ok(());
+        };
+        match request.url().host_str() {
+            Some(host) if Self::host_is_allowed(hosts, host) => Ok(()),
+            host => Err(format!(
+                "refusing to request disallowed host '{}' (not in --allowed-storage-hosts)",
+                host.unwrap_or("<no host>")
+            )),
+        }
+    }
+
+    /// Upper bound on redirect hops [`Self::execute_with_redirects`] will
+    /// follow before giving up, matching `reqwest`'s own default limit.
+    const MAX_REDIRECTS: u8 = 10;
+
+    /// Sends `request`, following any redirect response itself rather
+    /// than relying on `reqwest`'s (disabled, see [`Self::build_http_client`])
+    /// automatic redirect handling.
+    ///
+    /// The point of doing this manually: re-sending the *same* request
+    /// (same method, headers, and body) at the new URL means a header
+    /// like `Range` survives the hop, where some HTTP clients' default
+    /// redirect policies drop it — a signed-URL backend that redirects a
+    /// ranged `download_range` GET would otherwise have the range
+    /// request silently turn into a full-object fetch partway through.
+    /// Each redirect target is also re-checked against
+    /// [`Self::with_allowed_storage_hosts`], so a redirect can't be used
+    /// to steer a request at a host the allow-list would have rejected
+    /// outright.
+    async fn execute_with_redirects(&self, mut request: reqwest::Request) -> Result<reqwest::Response, String> {
+        for _ in 0..=Self::MAX_REDIRECTS {
+            let attempt = request
+                .try_clone()
+                .ok_or_else(|| "request body cannot be replayed across a redirect".to_string())?;
+            let response = self.http.execute(attempt).await.map_err(|e| e.to_string())?;
+            if !response.status().is_redirection() {
+                return Ok(response);
+            }
+
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| format!("redirect response ({}) had no Location header", response.status()))?;
+            let new_url = request
+                .url()
+                .join(location)
+                .map_err(|e| format!("invalid redirect Location '{location}': {e}"))?;
+
+            if let Some(hosts) = &self.allowed_storage_hosts {
+                match new_url.host_str() {
+                    Some(host) if Self::host_is_allowed(hosts, host) => {}
+                    host => {
+                        return Err(format!(
+                            "refusing to follow redirect to disallowed host '{}' (not in --allowed-storage-hosts)",
+                            host.unwrap_or("<no host>")
+                        ));
+                    }
+                }
+            }
+
+            *request.url_mut() = new_url;
+        }
+        Err(format!("exceeded {} redirects", Self::MAX_REDIRECTS))
+    }
+
+    /// Whether a nominally-successful response is actually a misconfigured
+    /// proxy/load balancer's HTML error page: either the `Content-Type`
+    /// says so outright, or the body itself starts with the `<!doctype`/
+    /// `<html` preamble every such page opens with. Deliberately narrow —
+    /// [`Self::execute_request_inner`] is shared by raw-content endpoints
+    /// (downloads) that are never JSON, so flagging every non-JSON 2xx
+    /// would reject legitimate binary responses; an HTML body is a
+    /// reliable, content-type-of-the-payload-independent signal that
+    /// something in front of the backend intercepted the request instead.
+    fn looks_like_proxy_error_page(headers: &reqwest::header::HeaderMap, body: &bytes::Bytes) -> bool {
+        let content_type_says_html = headers
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.contains("html"));
+        if content_type_says_html {
+            return true;
+        }
+        let sniffed = String::from_utf8_lossy(&body[..body.len().min(512)])
+            .trim_start()
+            .to_ascii_lowercase();
+        sniffed.starts_with("<!doctype") || sniffed.starts_with("<html")
+    }
+
+    /// A short snippet of an unexpected response body for an error
+    /// message — long enough to identify the culprit (an nginx error
+    /// page, a captive-portal redirect, ...) without dumping an entire
+    /// HTML document into the log, control characters stripped so it
+    /// can't corrupt the log line it's embedded in.
+    fn redact_unexpected_body(body: &bytes::Bytes) -> String {
+        const MAX_SNIPPET_LEN: usize = 200;
+        let sanitized: String = String::from_utf8_lossy(body)
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+        if sanitized.chars().count() > MAX_SNIPPET_LEN {
+            format!("{}…", sanitized.chars().take(MAX_SNIPPET_LEN).collect::<String>())
+        } else {
+            sanitized
+        }
+    }
+
+    /// Disambiguates a `409` response body's `code` field into the right
+    /// [`ApiError`] variant. The backend returns `409` for a plain name
+    /// collision (`"already_exists"`, -> `EEXIST` at the FUSE layer), for
+    /// another writer holding a conflicting lease (`"lease_conflict"`,
+    /// -> `EBUSY`), for a [`RosetClient::rename_node`] that would cross a
+    /// boundary the backend won't move a node across
+    /// (`"cross_device"`, -> `EXDEV`), and for a `rename_node` whose
+    /// destination is inside an immutable subtree
+    /// (`"immutable_target"`, -> `EROFS`); collapsing all four into one
+    /// meaning is wrong for `create_directory`/`create_file`/
+    /// `rename_node`'s callers, so every 409 goes through this one place
+    /// instead of each call site guessing independently.
+    fn classify_conflict(body: &bytes::Bytes) -> ApiError {
+        let code = serde_json::from_slice::<serde_json::Value>(body)
+            .ok()
+            .and_then(|v| v.get("code").and_then(|c| c.as_str()).map(str::to_string));
+        match code.as_deref() {
+            Some("lease_conflict") => ApiError::LeaseConflict,
+            Some("cross_device") => ApiError::CrossDevice,
+            Some("immutable_target") => ApiError::ImmutableTarget,
+            _ => ApiError::AlreadyExists,
+        }
+    }
+
+    /// Creates a directory under `parent_id`. See [`Self::classify_conflict`]
+    /// for how a `409` is disambiguated.
+    pub async fn create_directory(
+        &self,
+        parent_id: &str,
+        name: &str,
+    ) -> Result<crate::node::Node, ApiError> {
+        let url = format!("{}/v1/nodes/{parent_id}/children", self.base_url);
+        let response = self
+            .execute_request(
+                "create_directory",
+                || self.http.post(&url).json(&serde_json::json!({ "name": name, "node_type": "directory" })),
+                3,
+                &[],
+                None,
+            )
+            .await
+            .map_err(ApiError::Other)?;
+
+        if response.status.is_success() {
+            return serde_json::from_slice(&response.body)
+                .map_err(|e| ApiError::Other(e.to_string()));
+        }
+        if response.status == reqwest::StatusCode::CONFLICT {
+            return Err(Self::classify_conflict(&response.body));
+        }
+        Err(ApiError::Other(format!(
+            "unexpected status {}",
+            response.status
+        )))
+    }
+
+    /// Creates an empty file node under `parent_id`, for `create`'s
+    /// `create_node` step. Whether `create` also immediately stages an
+    /// upload session for the result is [`crate::fs::RosetFs::handle_create`]'s
+    /// call, not this one's — this just produces the committed, zero-byte
+    /// node. See [`Self::classify_conflict`] for how a `409` is
+    /// disambiguated.
+    pub async fn create_file(&self, parent_id: &str, name: &str) -> Result<crate::node::Node, ApiError> {
+        let url = format!("{}/v1/nodes/{parent_id}/children", self.base_url);
+        let response = self
+            .execute_request(
+                "create_file",
+                || self.http.post(&url).json(&serde_json::json!({ "name": name, "node_type": "file" })),
+                3,
+                &[],
+                None,
+            )
+            .await
+            .map_err(ApiError::Other)?;
+
+        if response.status.is_success() {
+            return serde_json::from_slice(&response.body)
+                .map_err(|e| ApiError::Other(e.to_string()));
+        }
+        if response.status == reqwest::StatusCode::CONFLICT {
+            return Err(Self::classify_conflict(&response.body));
+        }
+        Err(ApiError::Other(format!(
+            "unexpected status {}",
+            response.status
+        )))
+    }
+
+    /// Creates a symlink node under `parent_id`, storing `target` verbatim
+    /// in the `symlinkTarget` metadata key for [`crate::node::Node::symlink_target`]
+    /// to read back. See [`Self::classify_conflict`] for how a `409` is
+    /// disambiguated.
+    pub async fn create_symlink(
+        &self,
+        parent_id: &str,
+        name: &str,
+        target: &str,
+    ) -> Result<crate::node::Node, ApiError> {
+        let url = format!("{}/v1/nodes/{parent_id}/children", self.base_url);
+        let response = self
+            .execute_request(
+                "create_symlink",
+                || {
+                    self.http.post(&url).json(&serde_json::json!({
+                        "name": name,
+                        "node_type": "symlink",
+                        "metadata": { "symlinkTarget": target },
+                    }))
+                },
+                3,
+                &[],
+                None,
+            )
+            .await
+            .map_err(ApiError::Other)?;
+
+        if response.status.is_success() {
+            return serde_json::from_slice(&response.body)
+                .map_err(|e| ApiError::Other(e.to_string()));
+        }
+        if response.status == reqwest::StatusCode::CONFLICT {
+            return Err(Self::classify_conflict(&response.body));
+        }
+        Err(ApiError::Other(format!(
+            "unexpected status {}",
+            response.status
+        )))
+    }
+
+    /// Renames/moves `node_id` to `new_name` under `new_parent_id`. A
+    /// `409` here means the destination name is already taken (unless
+    /// `code` says it's a lease conflict on the destination parent, a
+    /// cross-device move, or an immutable destination), decoded by the
+    /// same [`Self::classify_conflict`] that [`Self::create_directory`] uses.
+    pub async fn rename_node(
+        &self,
+        node_id: &str,
+        new_parent_id: &str,
+        new_name: &str,
+    ) -> Result<crate::node::Node, ApiError> {
+        let url = format!("{}/v1/nodes/{node_id}/rename", self.base_url);
+        let response = self
+            .execute_request(
+                "rename_node",
+                || {
+                    self.http.post(&url).json(&serde_json::json!({
+                        "new_parent_id": new_parent_id,
+                        "new_name": new_name,
+                    }))
+                },
+                3,
+                &[],
+                None,
+            )
+            .await
+            .map_err(ApiError::Other)?;
+
+        if response.status.is_success() {
+            return serde_json::from_slice(&response.body)
+                .map_err(|e| ApiError::Other(e.to_string()));
+        }
+        if response.status == reqwest::StatusCode::CONFLICT {
+            return Err(Self::classify_conflict(&response.body));
+        }
+        Err(ApiError::Other(format!(
+            "unexpected status {}",
+            response.status
+        )))
+    }
+
+    /// Lists nodes currently in the trash (soft-deleted via
+    /// `delete_node`), surfaced under the virtual `.roset-trash`
+    /// directory so an accidental `rm` can be recovered without leaving
+    /// the mount.
+    pub async fn list_trash(&self) -> Result<Vec<crate::node::Node>, String> {
+        #[derive(serde::Deserialize)]
+        struct TrashListing {
+            nodes: Vec<crate::node::Node>,
+        }
+        let url = format!("{}/v1/trash", self.base_url);
+        let response = self
+            .execute_request("list_trash", || self.http.get(&url), 3, &[], None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(format!("unexpected status {}", response.status));
+        }
+        serde_json::from_slice::<TrashListing>(&response.body)
+            .map(|t| t.nodes)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Restores a soft-deleted node back to its original location,
+    /// analogous to moving a file out of a recycle bin.
+    pub async fn restore_node(&self, node_id: &str) -> Result<crate::node::Node, String> {
+        let url = format!("{}/v1/trash/{node_id}/restore", self.base_url);
+        let response = self
+            .execute_request("restore_node", || self.http.post(&url), 3, &[], None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(format!("unexpected status {}", response.status));
+        }
+        serde_json::from_slice(&response.body).map_err(|e| e.to_string())
+    }
+
+    /// Applies `patch` as a server-side metadata merge: existing keys not
+    /// present in `patch` are left untouched, so setting several xattrs
+    /// on one node (common with `cp -a` preserving multiple attributes)
+    /// is one call instead of a get-then-update read-modify-write per
+    /// attribute, which both trims round-trips and avoids the race where
+    /// two concurrent single-key updates each read the same base
+    /// metadata and clobber each other's write.
+    ///
+    /// `expected_version`, when set, is sent as an `If-Match` precondition
+    /// so the merge is rejected with [`PatchError::VersionMismatch`]
+    /// rather than silently applied if the node moved on since the
+    /// version was captured — belt-and-suspenders on top of the merge
+    /// semantics above, for callers (like
+    /// [`crate::fs::RosetFs::set_binary_xattrs`]) that want to detect a
+    /// concurrent modification and retry rather than just let it merge.
+    pub async fn update_node_metadata_patch(
+        &self,
+        node_id: &str,
+        patch: std::collections::HashMap<String, String>,
+        expected_version: Option<&str>,
+    ) -> Result<crate::node::Node, PatchError> {
+        let url = format!("{}/v1/nodes/{node_id}/metadata", self.base_url);
+        let response = self
+            .execute_request(
+                "update_node_metadata_patch",
+                || {
+                    let mut req = self.http.patch(&url).json(&serde_json::json!({ "patch": &patch }));
+                    if let Some(version) = expected_version {
+                        req = req.header("If-Match", version);
+                    }
+                    req
+                },
+                3,
+                &[],
+                None,
+            )
+            .await
+            .map_err(PatchError::Other)?;
+        if response.status.is_success() {
+            return serde_json::from_slice(&response.body)
+                .map_err(|e| PatchError::Other(e.to_string()));
+        }
+        if response.status == reqwest::StatusCode::PRECONDITION_FAILED {
+            return Err(PatchError::VersionMismatch);
+        }
+        Err(PatchError::Other(format!(
+            "unexpected status {}",
+            response.status
+        )))
+    }
+
+    /// Overwrites `data` into `node_id`'s content starting at `offset`,
+    /// leaving the rest of the file untouched, for a small in-place edit
+    /// that [`crate::upload::plan_upload_strategy`] decided didn't
+    /// warrant re-uploading the whole file — see
+    /// [`crate::fs::RosetFs::should_skip_patch`] for the backend-support
+    /// tracking built on top of this.
+    ///
+    /// `expected_version`, when set, is sent as an `If-Match`
+    /// precondition the same way [`Self::update_node_metadata_patch`]
+    /// does, so a concurrent write on the same node is caught as
+    /// [`PatchContentError::VersionMismatch`] instead of silently
+    /// clobbering bytes the caller never read. Not every backend
+    /// implements partial content patches at all, so a `404` or `501`
+    /// is reported as [`PatchContentError::Unsupported`] rather than a
+    /// generic failure, letting the caller remember not to try again.
+    pub async fn patch_content(
+        &self,
+        node_id: &str,
+        offset: u64,
+        data: &[u8],
+        expected_version: Option<&str>,
+    ) -> Result<crate::node::Node, PatchContentError> {
+        let url = format!("{}/v1/nodes/{node_id}/content", self.base_url);
+        let end = offset + data.len() as u64;
+        let response = self
+            .execute_request(
+                "patch_content",
+                || {
+                    let mut req = self
+                        .http
+                        .patch(&url)
+                        .header("Content-Range", format!("bytes {offset}-{}/*", end.saturating_sub(1)))
+                        .body(data.to_vec());
+                    if let Some(version) = expected_version {
+                        req = req.header("If-Match", version);
+                    }
+                    req
+                },
+                1,
+                &[],
+                None,
+            )
+            .await
+            .map_err(PatchContentError::Other)?;
+        if response.status.is_success() {
+            return serde_json::from_slice(&response.body)
+                .map_err(|e| PatchContentError::Other(e.to_string()));
+        }
+        if response.status == reqwest::StatusCode::PRECONDITION_FAILED {
+            return Err(PatchContentError::VersionMismatch);
+        }
+        if response.status == reqwest::StatusCode::NOT_FOUND
+            || response.status == reqwest::StatusCode::NOT_IMPLEMENTED
+        {
+            return Err(PatchContentError::Unsupported);
+        }
+        Err(PatchContentError::Other(format!(
+            "unexpected status {}",
+            response.status
+        )))
+    }
+
+    /// Replaces `node_id`'s entire content with `data` in a single PUT,
+    /// the fallback [`crate::upload::plan_upload_strategy`] reaches for
+    /// whenever a [`crate::upload::UploadStrategy::FullRewrite`] is called for: a patch
+    /// wasn't attempted, the backend has proven it doesn't support one
+    /// (see [`PatchContentError::Unsupported`]), or the edit touches too
+    /// much of the file for a patch to be worth it. [`crate::staging::StagingManager`]'s
+    /// upload worker uses this for anything under [`crate::staging::MULTIPART_MIN_SIZE`];
+    /// a buffer at or above that threshold goes through
+    /// [`Self::initiate_multipart_upload`]/[`Self::upload_part`]/[`Self::complete_multipart_upload`]
+    /// instead (see [`crate::staging::client_upload_hook`]).
+    pub async fn upload_content(&self, node_id: &str, data: Vec<u8>) -> Result<crate::node::Node, String> {
+        let url = format!("{}/v1/nodes/{node_id}/content", self.base_url);
+        let response = self
+            .execute_request("upload_content", || self.http.put(&url).body(data.clone()), 3, &[], None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(format!("unexpected status {}", response.status));
+        }
+        serde_json::from_slice(&response.body).map_err(|e| e.to_string())
+    }
+
+    /// Starts a multipart upload session for `node_id`, returning the
+    /// upload id [`Self::upload_part`]/[`Self::complete_multipart_upload`]/
+    /// [`Self::abort_multipart_upload`] address it by. The multipart
+    /// counterpart to a single [`Self::upload_content`] PUT, for a buffer
+    /// large enough that [`crate::upload::plan_parts_for_upload`] plans it
+    /// as several parts rather than one request body.
+    pub async fn initiate_multipart_upload(&self, node_id: &str) -> Result<String, String> {
+        let url = format!("{}/v1/nodes/{node_id}/multipart", self.base_url);
+        let response = self
+            .execute_request("initiate_multipart_upload", || self.http.post(&url), 3, &[], None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(format!("unexpected status {}", response.status));
+        }
+        #[derive(serde::Deserialize)]
+        struct InitiateMultipartResponse {
+            upload_id: String,
+        }
+        let parsed: InitiateMultipartResponse =
+            serde_json::from_slice(&response.body).map_err(|e| e.to_string())?;
+        Ok(parsed.upload_id)
+    }
+
+    /// Uploads one part of the multipart session `upload_id`, returning
+    /// the ETag [`Self::complete_multipart_upload`] needs for it.
+    pub async fn upload_part(
+        &self,
+        node_id: &str,
+        upload_id: &str,
+        part: &crate::upload::Part,
+        data: Vec<u8>,
+    ) -> Result<String, String> {
+        let url = format!(
+            "{}/v1/nodes/{node_id}/multipart/{upload_id}/parts/{}",
+            self.base_url, part.number
+        );
+        let response = self
+            .execute_request("upload_part", || self.http.put(&url).body(data.clone()), 3, &[], None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(format!("unexpected status {}", response.status));
+        }
+        #[derive(serde::Deserialize)]
+        struct UploadPartResponse {
+            etag: String,
+        }
+        let parsed: UploadPartResponse = serde_json::from_slice(&response.body).map_err(|e| e.to_string())?;
+        Ok(parsed.etag)
+    }
+
+    /// Finishes multipart session `upload_id`, handing the backend every
+    /// part's ETag so it can assemble the final object. Callers must run
+    /// [`crate::upload::validate_parts_contiguous`] over `parts` first —
+    /// the backend trusts this list outright and has no way of its own to
+    /// catch a silently incomplete set of parts.
+    pub async fn complete_multipart_upload(
+        &self,
+        node_id: &str,
+        upload_id: &str,
+        parts: &[crate::upload::UploadedPart],
+    ) -> Result<crate::node::Node, String> {
+        let url = format!("{}/v1/nodes/{node_id}/multipart/{upload_id}/complete", self.base_url);
+        let body = serde_json::json!({
+            "parts": parts
+                .iter()
+                .map(|p| serde_json::json!({"part_number": p.part.number, "etag": p.etag}))
+                .collect::<Vec<_>>(),
+        });
+        let response = self
+            .execute_request("complete_multipart_upload", || self.http.post(&url).json(&body), 3, &[], None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(format!("unexpected status {}", response.status));
+        }
+        serde_json::from_slice(&response.body).map_err(|e| e.to_string())
+    }
+
+    /// Cancels multipart session `upload_id` after a part upload failed,
+    /// so the backend doesn't keep an orphaned session around for an
+    /// upload that will never complete. Best-effort: the caller already
+    /// has the real upload error to report and only logs this one.
+    pub async fn abort_multipart_upload(&self, node_id: &str, upload_id: &str) -> Result<(), String> {
+        let url = format!("{}/v1/nodes/{node_id}/multipart/{upload_id}", self.base_url);
+        let response = self
+            .execute_request("abort_multipart_upload", || self.http.delete(&url), 3, &[], None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(format!("unexpected status {}", response.status));
+        }
+        Ok(())
+    }
+
+    /// Fetches `node_id` in full, e.g. to re-read its current version
+    /// after a [`PatchError::VersionMismatch`] before retrying a patch.
+    pub async fn get_node(&self, node_id: &str) -> Result<crate::node::Node, String> {
+        let url = format!("{}/v1/nodes/{node_id}", self.base_url);
+        let response = self
+            .execute_request("get_node", || self.http.get(&url), 3, &[], None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(format!("unexpected status {}", response.status));
+        }
+        serde_json::from_slice(&response.body).map_err(|e| e.to_string())
+    }
+
+    /// Fetches `node_id`'s raw content directly, for a file small enough
+    /// that [`crate::fs::RosetFs::read_small_file_inline`] has decided to
+    /// skip the normal signed-URL-then-range-read dance and fetch it in
+    /// one round trip instead.
+    pub async fn get_inline_content(&self, node_id: &str) -> Result<Vec<u8>, String> {
+        let url = format!("{}/v1/nodes/{node_id}/content", self.base_url);
+        let response = self
+            .execute_request("get_inline_content", || self.http.get(&url), 3, &[], None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(format!("unexpected status {}", response.status));
+        }
+        if let Some(expected_crc32) = Self::expected_crc32(&response) {
+            let actual_crc32 = base64_encode(&crc32(&response.body).to_be_bytes());
+            if actual_crc32 != expected_crc32 {
+                return Err(format!(
+                    "crc32 mismatch: expected {expected_crc32}, computed {actual_crc32}"
+                ));
+            }
+        }
+        Ok(response.body.to_vec())
+    }
+
+    /// Fetches the inclusive byte range `[start, end]` of `node_id`'s
+    /// content, verifying what came back before handing it to the
+    /// caller: a truncated or corrupted transfer fails closed instead of
+    /// silently serving bad bytes to an application reading a dataset
+    /// file through the mount.
+    ///
+    /// Verification is layered: the response must be `206 Partial
+    /// Content` rather than a full `200` (see [`Self::verify_range_response`]
+    /// — [`Self::execute_with_redirects`] re-sends the `Range` header on
+    /// every redirect hop, but this is the backstop in case some backend
+    /// still drops it), the response length must match the requested
+    /// range (a short read is tolerated only when it's a legitimate EOF,
+    /// per `Content-Range`'s reported total size), and if the backend
+    /// sent a trailing `x-amz-checksum-crc32` header the body must hash
+    /// to it. Other `x-amz-checksum-*` algorithms aren't verified since
+    /// this client has no dependency that computes them; length-plus-CRC32
+    /// is already enough to catch the truncation and bit-flip cases this
+    /// exists for.
+    ///
+    /// A mismatched range is attempted up to `MAX_RANGE_ATTEMPTS` times in
+    /// all (the first try plus retries), same as
+    /// [`Self::execute_request`]'s own transport-level retries but one
+    /// layer up, since the request itself came back as an HTTP success —
+    /// there's nothing for `execute_request` to retry on.
+    pub async fn download_range(&self, node_id: &str, start: u64, end: u64) -> Result<Vec<u8>, String> {
+        const MAX_RANGE_ATTEMPTS: u32 = 3;
+        let expected_len = end.saturating_sub(start) + 1;
+        let url = format!("{}/v1/nodes/{node_id}/content", self.base_url);
+        let range = format!("bytes={start}-{end}");
+
+        let mut attempt = 0;
+        loop {
+            let response = self
+                .execute_request(
+                    "download_range",
+                    || self.http.get(&url).header(reqwest::header::RANGE, range.clone()),
+                    3,
+                    &[],
+                    None,
+                )
+                .await?;
+            if !response.status.is_success() {
+                return Err(format!("unexpected status {}", response.status));
+            }
+
+            match Self::verify_range_response(&response, start, expected_len) {
+                Ok(()) => return Ok(response.body.to_vec()),
+                Err(reason) if attempt + 1 < MAX_RANGE_ATTEMPTS => {
+                    attempt += 1;
+                    eprintln!(
+                        "roset-fuse: download_range({node_id}, {start}-{end}) retrying after {reason} (attempt {attempt}/{MAX_RANGE_ATTEMPTS})"
+                    );
+                }
+                Err(reason) => return Err(reason),
+            }
+        }
+    }
+
+    /// The length/checksum checks [`Self::download_range`] applies to a
+    /// successful range response. `Ok(())` means the body is trustworthy;
+    /// `Err` carries a human-readable mismatch reason for the retry log.
+    fn verify_range_response(response: &ApiResponse, start: u64, expected_len: u64) -> Result<(), String> {
+        // A ranged GET that comes back `200 OK` instead of `206 Partial
+        // Content` means the server served the full object — most likely
+        // because the `Range` header was lost somewhere along the way
+        // (e.g. a redirect a less careful client followed without
+        // reapplying it). Treating this as a verification failure routes
+        // it through the same retry as a truncated/corrupt transfer,
+        // rather than risking silently serving the whole file back as if
+        // it were the requested slice.
+        if response.status == reqwest::StatusCode::OK {
+            return Err(
+                "expected 206 Partial Content for a ranged request but got 200 (full body) \
+                 — the Range header may have been dropped across a redirect"
+                    .to_string(),
+            );
+        }
+
+        let received_len = response.body.len() as u64;
+        if received_len != expected_len {
+            let total = Self::content_range_total(response);
+            let is_legitimate_eof = total.is_some_and(|total| start + received_len == total);
+            if !is_legitimate_eof {
+                return Err(format!(
+                    "expected {expected_len} bytes, got {received_len}"
+                ));
+            }
+        }
+
+        if let Some(expected_crc32) = Self::expected_crc32(response) {
+            let actual_crc32 = base64_encode(&crc32(&response.body).to_be_bytes());
+            if actual_crc32 != expected_crc32 {
+                return Err(format!(
+                    "crc32 mismatch: expected {expected_crc32}, computed {actual_crc32}"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a `Content-Range: bytes start-end/total` header's `total`,
+    /// when the backend sent one and it isn't `*` (unknown).
+    fn content_range_total(response: &ApiResponse) -> Option<u64> {
+        let header = response.headers.get(reqwest::header::CONTENT_RANGE)?.to_str().ok()?;
+        header.rsplit('/').next()?.parse().ok()
+    }
+
+    fn expected_crc32(response: &ApiResponse) -> Option<String> {
+        response
+            .headers
+            .get("x-amz-checksum-crc32")?
+            .to_str()
+            .ok()
+            .map(str::to_string)
+    }
+
+    /// Fetches the authoritative size for `node_id` directly, for a node
+    /// whose `size` is still `None` (the backend hasn't finished
+    /// computing it yet). Used by [`crate::fs::RosetFs::resolve_size`] so
+    /// a `read` right after upload completion doesn't see a defaulted
+    /// size of `0` and return empty.
+    pub async fn refresh_size(&self, node_id: &str) -> Result<u64, String> {
+        #[derive(serde::Deserialize)]
+        struct SizeResponse {
+            size: u64,
+        }
+        let url = format!("{}/v1/nodes/{node_id}", self.base_url);
+        let response = self
+            .execute_request("refresh_size", || self.http.get(&url), 3, &[], None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(format!("unexpected status {}", response.status));
+        }
+        serde_json::from_slice::<SizeResponse>(&response.body)
+            .map(|r| r.size)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Acquires an exclusive write lease on `node_id`, failing with
+    /// [`ApiError::LeaseConflict`] if another writer already holds one.
+    /// Shares [`Self::classify_conflict`]'s `409` decoding even though a
+    /// name collision can't happen here, since the backend uses the same
+    /// envelope for every lease-guarded conflict.
+    pub async fn acquire_lease(&self, node_id: &str) -> Result<Lease, ApiError> {
+        let url = format!("{}/v1/nodes/{node_id}/lease", self.base_url);
+        let response = self
+            .execute_request("acquire_lease", || self.http.post(&url).json(&serde_json::json!({})), 3, &[], None)
+            .await
+            .map_err(ApiError::Other)?;
+
+        if response.status.is_success() {
+            return serde_json::from_slice(&response.body)
+                .map_err(|e| ApiError::Other(e.to_string()));
+        }
+        if response.status == reqwest::StatusCode::CONFLICT {
+            return Err(Self::classify_conflict(&response.body));
+        }
+        Err(ApiError::Other(format!(
+            "unexpected status {}",
+            response.status
+        )))
+    }
+
+    /// Renews a lease that's about to expire, keeping the same
+    /// `lease_id` but extending `expires_at_unix_secs`. Fails with
+    /// [`ApiError::LeaseConflict`] if the lease already lapsed and
+    /// another writer grabbed it first — callers should stop writing and
+    /// surface that the same way a fresh [`Self::acquire_lease`] conflict
+    /// would be.
+    pub async fn renew_lease(&self, lease: &Lease) -> Result<Lease, ApiError> {
+        let url = format!("{}/v1/nodes/{}/lease/{}/renew", self.base_url, lease.node_id, lease.lease_id);
+        let response = self
+            .execute_request("renew_lease", || self.http.post(&url).json(&serde_json::json!({})), 3, &[], None)
+            .await
+            .map_err(ApiError::Other)?;
+
+        if response.status.is_success() {
+            return serde_json::from_slice(&response.body)
+                .map_err(|e| ApiError::Other(e.to_string()));
+        }
+        if response.status == reqwest::StatusCode::CONFLICT {
+            return Err(Self::classify_conflict(&response.body));
+        }
+        Err(ApiError::Other(format!(
+            "unexpected status {}",
+            response.status
+        )))
+    }
+
+    /// Releases a previously-acquired lease. Best-effort: callers on the
+    /// teardown path log and continue rather than propagate failures,
+    /// since there's nothing further to do once the mount is going away.
+    pub async fn release_lease(&self, lease: &Lease) -> Result<(), String> {
+        let url = format!("{}/v1/nodes/{}/lease/{}", self.base_url, lease.node_id, lease.lease_id);
+        self.http
+            .delete(url)
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Failure modes shared by [`RosetClient::create_directory`] and
+/// [`RosetClient::rename_node`], kept distinct so the FUSE layer can map
+/// them to different errnos (`EEXIST` vs `EBUSY` vs `EXDEV` vs `EROFS`)
+/// instead of collapsing every `409` into one generic conflict.
+#[derive(Debug)]
+pub enum ApiError {
+    AlreadyExists,
+    LeaseConflict,
+    /// [`RosetClient::rename_node`] can't move the node across the
+    /// boundary the backend rejected it at (e.g. between mounts, or into
+    /// a different top-level dataset) — maps to `EXDEV` so `mv` falls
+    /// back to copy+delete instead of failing outright.
+    CrossDevice,
+    /// [`RosetClient::rename_node`]'s destination is inside a committed
+    /// or otherwise immutable subtree that can't accept a new child —
+    /// maps to `EROFS`.
+    ImmutableTarget,
+    Other(String),
+}
+
+/// Failure modes for [`RosetClient::update_node_metadata_patch`].
+#[derive(Debug)]
+pub enum PatchError {
+    /// The `If-Match` precondition didn't match: the node's version moved
+    /// on since it was captured. Callers should re-fetch and retry.
+    VersionMismatch,
+    Other(String),
+}
+
+/// Failure modes for [`RosetClient::patch_content`]. Kept separate from
+/// [`PatchError`] even though both carry a `VersionMismatch` case: the
+/// two calls patch entirely different things (metadata vs. file bytes),
+/// have no shared call site, and only `patch_content` can fail with
+/// [`Self::Unsupported`] — a backend without partial-content support has
+/// no equivalent failure mode for a metadata merge.
+#[derive(Debug)]
+pub enum PatchContentError {
+    /// The `If-Match` precondition didn't match: the node's version moved
+    /// on since it was captured. Callers should re-fetch and retry, or
+    /// fall back to a full rewrite.
+    VersionMismatch,
+    /// The backend doesn't support partial content patches at all (404
+    /// or 501). Callers should remember this and skip straight to a full
+    /// rewrite next time — see
+    /// [`crate::fs::RosetFs::mark_patch_unsupported`].
+    Unsupported,
+    Other(String),
+}
+
+/// Result of [`RosetClient::list_all_children`]: the children collected
+/// so far, and whether the listing is complete or was truncated after a
+/// page kept failing.
+pub struct ChildListing {
+    pub children: Vec<crate::node::Node>,
+    pub truncated: bool,
+}
+
+impl RosetClient {
+    /// Pages through a directory's children. Each page gets its own
+    /// retry budget (reusing [`RosetClient::execute_request`]'s backoff);
+    /// if a page still fails after exhausting its retries, the pages
+    /// already collected are returned with `truncated: true` rather than
+    /// discarding everything fetched so far — a transient error on page
+    /// 7 of 50 shouldn't turn into an empty `readdir`.
+    pub async fn list_all_children(&self, node_id: &str) -> ChildListing {
+        #[derive(serde::Deserialize)]
+        struct Page {
+            children: Vec<crate::node::Node>,
+            next_cursor: Option<String>,
+        }
+
+        let mut children = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let url = format!("{}/v1/nodes/{node_id}/children", self.base_url);
+            let cursor_for_request = cursor.clone();
+            let response = self
+                .execute_request(
+                    "list_all_children",
+                    || {
+                        let mut req = self.http.get(&url);
+                        if let Some(c) = &cursor_for_request {
+                            req = req.query(&[("cursor", c.as_str())]);
+                        }
+                        req
+                    },
+                    5,
+                    &[],
+                    None,
+                )
+                .await;
+
+            let page: Option<Page> = match response {
+                Ok(resp) if resp.status.is_success() => {
+                    serde_json::from_slice(&resp.body).ok()
+                }
+                _ => None,
+            };
+
+            match page {
+                Some(page) => {
+                    children.extend(page.children);
+                    match page.next_cursor {
+                        Some(next) => cursor = Some(next),
+                        None => return ChildListing { children, truncated: false },
+                    }
+                }
+                None => return ChildListing { children, truncated: true },
+            }
+        }
+    }
+
+    /// Fetches just the entry count of a subtree's manifest, without the
+    /// manifest body itself, so [`RosetFs`](crate::fs::RosetFs) can decide
+    /// whether a full [`Self::get_manifest`] bulk load is safe before
+    /// paying for it.
+    pub async fn get_manifest_summary(&self, node_id: &str) -> Result<ManifestSummary, String> {
+        let url = format!("{}/v1/nodes/{node_id}/manifest/summary", self.base_url);
+        let response = self
+            .execute_request("get_manifest_summary", || self.http.get(&url), 3, &[], None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(format!("unexpected status {}", response.status));
+        }
+        serde_json::from_slice(&response.body).map_err(|e| e.to_string())
+    }
+
+    /// Fetches the full manifest (every node in a committed subtree) in
+    /// one call. Callers should check [`Self::get_manifest_summary`]
+    /// first and skip this in favor of [`Self::list_all_children`] when
+    /// the subtree is too large to hold in memory at once.
+    pub async fn get_manifest(&self, node_id: &str) -> Result<Vec<crate::node::Node>, String> {
+        #[derive(serde::Deserialize)]
+        struct ManifestResponse {
+            nodes: Vec<crate::node::Node>,
+        }
+        let url = format!("{}/v1/nodes/{node_id}/manifest", self.base_url);
+        let response = self
+            .execute_request("get_manifest", || self.http.get(&url), 3, &[], None)
+            .await?;
+        if !response.status.is_success() {
+            return Err(format!("unexpected status {}", response.status));
+        }
+        serde_json::from_slice::<ManifestResponse>(&response.body)
+            .map(|r| r.nodes)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Response from [`RosetClient::get_manifest_summary`]: the number of
+/// entries a full [`RosetClient::get_manifest`] call for the same subtree
+/// would return, without paying to transfer them.
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+pub struct ManifestSummary {
+    pub node_count: usize,
+}
+
+/// The standard (IEEE 802.3) CRC-32, matching `x-amz-checksum-crc32`'s
+/// algorithm. Computed bit-by-bit rather than via a lookup table since
+/// this is only run on the small/medium reads [`RosetClient::download_range`]
+/// serves, not a hot inner loop worth the table's setup cost.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xffff_ffff;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn pool_config_defaults_are_tuned_for_read_fan_out() {
+        let defaults = HttpPoolConfig::default();
+        assert_eq!(defaults.max_idle_per_host, 32);
+        assert_eq!(defaults.idle_timeout, Duration::from_secs(90));
+    }
+
+    #[test]
+    fn custom_pool_config_is_accepted_by_the_client_builder() {
+        let pool = HttpPoolConfig {
+            max_idle_per_host: 4,
+            idle_timeout: Duration::from_secs(10),
+        };
+        let _client = RosetClient::with_pool_config("https://api.roset.dev", pool);
+    }
+
+    #[test]
+    fn host_allow_list_accepts_exact_and_wildcard_matches() {
+        let patterns = vec!["cdn.roset.dev".to_string(), "*.storage.roset.dev".to_string()];
+        assert!(RosetClient::host_is_allowed(&patterns, "cdn.roset.dev"));
+        assert!(RosetClient::host_is_allowed(&patterns, "CDN.ROSET.DEV"));
+        assert!(RosetClient::host_is_allowed(&patterns, "a.storage.roset.dev"));
+        assert!(RosetClient::host_is_allowed(&patterns, "storage.roset.dev"));
+        assert!(!RosetClient::host_is_allowed(&patterns, "evil.example.com"));
+        assert!(!RosetClient::host_is_allowed(&patterns, "roset.dev"));
+    }
+
+    #[tokio::test]
+    async fn a_disallowed_host_is_rejected_before_any_request_is_made() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url())
+            .with_allowed_storage_hosts(vec!["cdn.roset.dev".to_string()]);
+
+        let result = client.get_inline_content("n1").await;
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("disallowed host"));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_host_matching_the_allow_list_is_requested_normally() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .with_status(200)
+            .with_body("hello")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let host = reqwest::Url::parse(&server.url()).unwrap().host_str().unwrap().to_string();
+        let client = RosetClient::new(server.url()).with_allowed_storage_hosts(vec![host]);
+
+        let result = client.get_inline_content("n1").await;
+
+        assert_eq!(result.unwrap(), b"hello");
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn user_agent_carries_version_and_mount_id() {
+        let ua = build_user_agent("mount-abc", Some("tag=nightly"));
+        assert!(ua.starts_with("roset-fuse/"));
+        assert!(ua.contains("mount_id=mount-abc"));
+        assert!(ua.ends_with("tag=nightly"));
+    }
+
+    #[tokio::test]
+    async fn client_sends_the_configured_user_agent() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/ping")
+            .match_header("user-agent", mockito::Matcher::Regex("^roset-fuse/.*mount_id=m1".into()))
+            .with_status(200)
+            .create_async()
+            .await;
+
+        let client = RosetClient::with_pool_config_and_user_agent(
+            server.url(),
+            HttpPoolConfig::default(),
+            build_user_agent("m1", None),
+        );
+        let _ = client.http.get(format!("{}/v1/ping", server.url())).send().await;
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn get_node_carries_the_mount_id_header_despite_having_no_body_to_put_it_in() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/nodes/n1")
+            .match_header(MOUNT_ID_HEADER, "mount-xyz")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"n1","name":"f","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#,
+            )
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url()).with_mount_id("mount-xyz");
+
+        let node = client.get_node("n1").await.unwrap();
+
+        assert_eq!(node.id, "n1");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_transient_page_failure_is_retried_and_listing_completes() {
+        let mut server = mockito::Server::new_async().await;
+        let page1 = server
+            .mock("GET", "/v1/nodes/dir-1/children")
+            .with_status(200)
+            .with_body(r#"{"children":[{"id":"a","name":"a","node_type":"file","size":1,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}],"next_cursor":"page2"}"#)
+            .create_async()
+            .await;
+        let page2_fail = server
+            .mock("GET", "/v1/nodes/dir-1/children")
+            .match_query(mockito::Matcher::UrlEncoded("cursor".into(), "page2".into()))
+            .with_status(503)
+            .expect(1)
+            .create_async()
+            .await;
+        let page2_ok = server
+            .mock("GET", "/v1/nodes/dir-1/children")
+            .match_query(mockito::Matcher::UrlEncoded("cursor".into(), "page2".into()))
+            .with_status(200)
+            .with_body(r#"{"children":[{"id":"b","name":"b","node_type":"file","size":1,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}],"next_cursor":null}"#)
+            .create_async()
+            .await;
+
+        let client = RosetClient::with_backoff_config(
+            RosetClient::new(server.url()),
+            BackoffConfig {
+                max_backoff_rate_limit: Duration::from_millis(1),
+                max_backoff_server_error: Duration::from_millis(1),
+                max_backoff_network: Duration::from_millis(1),
+            },
+        );
+
+        let listing = client.list_all_children("dir-1").await;
+
+        assert!(!listing.truncated);
+        assert_eq!(listing.children.len(), 2);
+        page1.assert_async().await;
+        page2_fail.assert_async().await;
+        page2_ok.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn retryable_400_succeeds_after_one_retry() {
+        let mut server = mockito::Server::new_async().await;
+        let not_ready = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(400)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"upload_session_not_ready"}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let ok = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(200)
+            .with_body("{}")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = RosetClient::with_backoff_config(
+            RosetClient::new(server.url()),
+            BackoffConfig {
+                max_backoff_rate_limit: Duration::from_millis(1),
+                max_backoff_server_error: Duration::from_millis(1),
+                max_backoff_network: Duration::from_millis(1),
+            },
+        );
+
+        let resp = client
+            .execute_request(
+                "test_op",
+                || client.http.get(format!("{}/v1/nodes/n1", client.base_url)),
+                3,
+                &["upload_session_not_ready"],
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status, reqwest::StatusCode::OK);
+        not_ready.assert_async().await;
+        ok.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn create_directory_distinguishes_already_exists_from_lease_conflict() {
+        let mut server = mockito::Server::new_async().await;
+        let exists = server
+            .mock("POST", "/v1/nodes/parent/children")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({"name": "a"})))
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"already_exists"}"#)
+            .create_async()
+            .await;
+        let busy = server
+            .mock("POST", "/v1/nodes/parent/children")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({"name": "b"})))
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"lease_conflict"}"#)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+
+        let err_a = client.create_directory("parent", "a").await.unwrap_err();
+        assert!(matches!(err_a, ApiError::AlreadyExists));
+
+        let err_b = client.create_directory("parent", "b").await.unwrap_err();
+        assert!(matches!(err_b, ApiError::LeaseConflict));
+
+        exists.assert_async().await;
+        busy.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn renew_lease_extends_expiry_but_fails_with_lease_conflict_once_it_lapsed() {
+        let mut server = mockito::Server::new_async().await;
+        let renewed = server
+            .mock("POST", "/v1/nodes/n1/lease/lease-1/renew")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"node_id":"n1","lease_id":"lease-1","expires_at_unix_secs":200}"#)
+            .create_async()
+            .await;
+        let lapsed = server
+            .mock("POST", "/v1/nodes/n1/lease/lease-2/renew")
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"lease_conflict"}"#)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+
+        let renewed_lease = client
+            .renew_lease(&Lease { node_id: "n1".to_string(), lease_id: "lease-1".to_string(), expires_at_unix_secs: Some(100) })
+            .await
+            .unwrap();
+        assert_eq!(renewed_lease.expires_at_unix_secs, Some(200));
+
+        let err = client
+            .renew_lease(&Lease { node_id: "n1".to_string(), lease_id: "lease-2".to_string(), expires_at_unix_secs: Some(100) })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ApiError::LeaseConflict));
+
+        renewed.assert_async().await;
+        lapsed.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn an_already_expired_deadline_stops_retries_immediately() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(503)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let deadline = crate::retry::Deadline::after(Duration::from_secs(0));
+        std::thread::sleep(Duration::from_millis(5));
+
+        let result = client
+            .execute_request(
+                "test_op",
+                || client.http.get(format!("{}/v1/nodes/n1", client.base_url)),
+                5,
+                &[],
+                Some(&deadline),
+            )
+            .await;
+
+        assert!(result.is_err());
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn deleted_file_appears_in_trash_and_restore_round_trips() {
+        let mut server = mockito::Server::new_async().await;
+        let listing = server
+            .mock("GET", "/v1/trash")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"nodes":[{"id":"n1","name":"deleted.txt","node_type":"file","size":4,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}]}"#)
+            .create_async()
+            .await;
+        let restore = server
+            .mock("POST", "/v1/trash/n1/restore")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"deleted.txt","node_type":"file","size":4,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+
+        let trash = client.list_trash().await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].name, "deleted.txt");
+
+        let restored = client.restore_node("n1").await.unwrap();
+        assert_eq!(restored.id, "n1");
+
+        listing.assert_async().await;
+        restore.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn refresh_size_fetches_the_concrete_size_once_the_backend_has_computed_it() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"size":4096}"#)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let size = client.refresh_size("n1").await.unwrap();
+
+        assert_eq!(size, 4096);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn get_inline_content_fetches_the_raw_body_in_a_single_round_trip() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .with_status(200)
+            .with_body(b"small file contents")
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let content = client.get_inline_content("n1").await.unwrap();
+
+        assert_eq!(content, b"small file contents");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_truncated_range_response_is_retried_instead_of_served() {
+        let mut server = mockito::Server::new_async().await;
+        let truncated = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .match_header("range", "bytes=0-9")
+            .with_status(206)
+            .with_body(b"short")
+            .expect(1)
+            .create_async()
+            .await;
+        let full = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .match_header("range", "bytes=0-9")
+            .with_status(206)
+            .with_body(b"0123456789")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let content = client.download_range("n1", 0, 9).await.unwrap();
+
+        assert_eq!(content, b"0123456789");
+        truncated.assert_async().await;
+        full.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_short_read_that_matches_the_reported_total_is_accepted_as_eof() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .match_header("range", "bytes=5-99")
+            .with_status(206)
+            .with_header("content-range", "bytes 5-9/10")
+            .with_body(b"fghij")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let content = client.download_range("n1", 5, 99).await.unwrap();
+
+        assert_eq!(content, b"fghij");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_crc32_mismatch_is_retried_instead_of_served() {
+        let mut server = mockito::Server::new_async().await;
+        let corrupted = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .match_header("range", "bytes=0-4")
+            .with_status(206)
+            .with_header("x-amz-checksum-crc32", &base64_encode(&crc32(b"abcde").to_be_bytes()))
+            .with_body(b"XXXXX")
+            .expect(1)
+            .create_async()
+            .await;
+        let intact = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .match_header("range", "bytes=0-4")
+            .with_status(206)
+            .with_header("x-amz-checksum-crc32", &base64_encode(&crc32(b"abcde").to_be_bytes()))
+            .with_body(b"abcde")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let content = client.download_range("n1", 0, 4).await.unwrap();
+
+        assert_eq!(content, b"abcde");
+        corrupted.assert_async().await;
+        intact.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_range_request_reapplies_its_range_header_across_a_redirect() {
+        let mut server = mockito::Server::new_async().await;
+        let redirect = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .match_header("range", "bytes=0-4")
+            .with_status(302)
+            .with_header("location", &format!("{}/v1/nodes/n1/content-real", server.url()))
+            .expect(1)
+            .create_async()
+            .await;
+        // This mock only matches if the `Range` header survived the
+        // redirect; if `execute_with_redirects` dropped it, mockito would
+        // fail to match any mock and the call would error instead of
+        // succeeding with the expected partial body.
+        let real = server
+            .mock("GET", "/v1/nodes/n1/content-real")
+            .match_header("range", "bytes=0-4")
+            .with_status(206)
+            .with_header("content-range", "bytes 0-4/10")
+            .with_body(b"hello")
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let content = client.download_range("n1", 0, 4).await.unwrap();
+
+        assert_eq!(content, b"hello");
+        redirect.assert_async().await;
+        real.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_full_200_response_to_a_ranged_request_is_rejected_as_a_dropped_range_header() {
+        let mut server = mockito::Server::new_async().await;
+        let full_body = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .match_header("range", "bytes=0-4")
+            .with_status(200)
+            .with_body(b"0123456789")
+            .expect(3)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let err = client.download_range("n1", 0, 4).await.unwrap_err();
+
+        assert!(err.contains("206"));
+        full_body.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn get_node_captures_the_etag_when_the_backend_reports_one() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"n1","name":"f","node_type":"file","size":0,"etag":"e1","mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#,
+            )
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let node = client.get_node("n1").await.unwrap();
+
+        assert_eq!(node.etag, Some("e1".to_string()));
+        assert_eq!(node.version(), Some("e1"));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn metadata_patch_merges_in_a_single_call() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("PATCH", "/v1/nodes/n1/metadata")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "patch": {"xattr.user.a": "MQ==", "xattr.user.b": "Mg=="}
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"f","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{"xattr.user.a":"MQ==","xattr.user.b":"Mg=="}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let mut patch = std::collections::HashMap::new();
+        patch.insert("xattr.user.a".to_string(), "MQ==".to_string());
+        patch.insert("xattr.user.b".to_string(), "Mg==".to_string());
+
+        let node = client
+            .update_node_metadata_patch("n1", patch, None)
+            .await
+            .unwrap();
+
+        assert_eq!(node.metadata.get("xattr.user.a"), Some(&"MQ==".to_string()));
+        assert_eq!(node.metadata.get("xattr.user.b"), Some(&"Mg==".to_string()));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn metadata_patch_with_a_stale_precondition_reports_a_version_mismatch() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("PATCH", "/v1/nodes/n1/metadata")
+            .match_header("if-match", "v1")
+            .with_status(412)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let mut patch = std::collections::HashMap::new();
+        patch.insert("xattr.user.a".to_string(), "MQ==".to_string());
+
+        let err = client
+            .update_node_metadata_patch("n1", patch, Some("v1"))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, PatchError::VersionMismatch));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn patch_content_sends_a_content_range_header_and_returns_the_updated_node() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("PATCH", "/v1/nodes/n1/content")
+            .match_header("content-range", "bytes 10-19/*")
+            .match_body(b"0123456789".to_vec())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"n1","name":"f","node_type":"file","size":20,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let node = client
+            .patch_content("n1", 10, b"0123456789", None)
+            .await
+            .unwrap();
+
+        assert_eq!(node.size, Some(20));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn patch_content_reports_unsupported_when_the_backend_has_no_partial_content_support() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("PATCH", "/v1/nodes/n1/content")
+            .with_status(501)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let err = client.patch_content("n1", 0, b"x", None).await.unwrap_err();
+
+        assert!(matches!(err, PatchContentError::Unsupported));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn patch_content_with_a_stale_precondition_reports_a_version_mismatch() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("PATCH", "/v1/nodes/n1/content")
+            .match_header("if-match", "v1")
+            .with_status(412)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let err = client
+            .patch_content("n1", 0, b"x", Some("v1"))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, PatchContentError::VersionMismatch));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn circuit_opens_after_consecutive_failures_and_fast_fails_without_hitting_the_mock() {
+        let mut server = mockito::Server::new_async().await;
+        let failing = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(503)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let client = RosetClient::with_backoff_config(
+            RosetClient::new(server.url()),
+            BackoffConfig {
+                max_backoff_rate_limit: Duration::from_millis(1),
+                max_backoff_server_error: Duration::from_millis(1),
+                max_backoff_network: Duration::from_millis(1),
+            },
+        )
+        .with_circuit_breaker(2, Duration::from_secs(60));
+
+        // Each call makes a single attempt (max_attempts=1 here means no
+        // internal retry), so two outer calls accrue two consecutive
+        // circuit-breaker failures and open the breaker.
+        for _ in 0..2 {
+            let _ = client
+                .execute_request(
+                    "test_op",
+                    || client.http.get(format!("{}/v1/nodes/n1", client.base_url)),
+                    1,
+                    &[],
+                    None,
+                )
+                .await;
+        }
+
+        let result = client
+            .execute_request(
+                "test_op",
+                || client.http.get(format!("{}/v1/nodes/n1", client.base_url)),
+                1,
+                &[],
+                None,
+            )
+            .await;
+
+        assert!(result.is_err());
+        failing.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn an_artificially_slow_mock_response_triggers_the_slow_op_warning() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(200)
+            .with_body("{}")
+            .create_async()
+            .await;
+
+        let warnings: Arc<Mutex<Vec<(String, Duration)>>> = Arc::new(Mutex::new(Vec::new()));
+        let warnings_for_hook = warnings.clone();
+
+        // The mock responds immediately, so rather than actually sleeping
+        // in the test, the threshold is set to 0: any real round trip
+        // (however fast) exceeds it, which exercises the same warning
+        // path an artificially slow backend would hit.
+        let client = RosetClient::new(server.url()).with_slow_op_threshold(
+            Duration::ZERO,
+            Some(Arc::new(move |op: &str, elapsed: Duration| {
+                warnings_for_hook.lock().unwrap().push((op.to_string(), elapsed));
+            })),
+        );
+
+        let resp = client
+            .execute_request("get_node", || client.http.get(format!("{}/v1/nodes/n1", client.base_url)), 3, &[], None)
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status, reqwest::StatusCode::OK);
+
+        {
+            let recorded = warnings.lock().unwrap();
+            assert_eq!(recorded.len(), 1);
+            assert_eq!(recorded[0].0, "get_node");
+            assert!(recorded[0].1 >= Duration::ZERO);
+        }
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_200_response_with_an_html_body_is_retried_once_then_reported_clearly() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(200)
+            .with_header("content-type", "text/html")
+            .with_body("<!doctype html><html><body>502 Bad Gateway</body></html>")
+            .expect(2)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url()).with_backoff_config(BackoffConfig {
+            max_backoff_rate_limit: Duration::from_millis(1),
+            max_backoff_server_error: Duration::from_millis(1),
+            max_backoff_network: Duration::from_millis(1),
+        });
+
+        let err = client
+            .execute_request("get_node", || client.http.get(format!("{}/v1/nodes/n1", client.base_url)), 3, &[], None)
+            .await
+            .unwrap_err();
+
+        assert!(err.contains("HTML error page"), "unexpected error: {err}");
+        assert!(err.contains("502 Bad Gateway"), "expected a body snippet in the error: {err}");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn initiate_multipart_upload_returns_the_backends_upload_id() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/nodes/n1/multipart")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"upload_id":"up-1"}"#)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let upload_id = client.initiate_multipart_upload("n1").await.unwrap();
+
+        assert_eq!(upload_id, "up-1");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn upload_part_sends_the_part_body_to_its_numbered_endpoint_and_returns_its_etag() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("PUT", "/v1/nodes/n1/multipart/up-1/parts/2")
+            .match_body(b"0123456789".to_vec())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"etag":"etag-2"}"#)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let part = crate::upload::Part { number: 2, offset: 10, len: 10 };
+        let etag = client.upload_part("n1", "up-1", &part, b"0123456789".to_vec()).await.unwrap();
+
+        assert_eq!(etag, "etag-2");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn complete_multipart_upload_sends_every_parts_number_and_etag() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/nodes/n1/multipart/up-1/complete")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "parts": [
+                    {"part_number": 1, "etag": "etag-1"},
+                    {"part_number": 2, "etag": "etag-2"},
+                ]
+            })))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"n1","name":"f","node_type":"file","size":20,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#,
+            )
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let parts = vec![
+            crate::upload::UploadedPart { part: crate::upload::Part { number: 1, offset: 0, len: 10 }, etag: "etag-1".to_string() },
+            crate::upload::UploadedPart { part: crate::upload::Part { number: 2, offset: 10, len: 10 }, etag: "etag-2".to_string() },
+        ];
+        let node = client.complete_multipart_upload("n1", "up-1", &parts).await.unwrap();
+
+        assert_eq!(node.size, Some(20));
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn abort_multipart_upload_deletes_the_session() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("DELETE", "/v1/nodes/n1/multipart/up-1")
+            .with_status(204)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        client.abort_multipart_upload("n1", "up-1").await.unwrap();
+
+        mock.assert_async().await;
+    }
+}
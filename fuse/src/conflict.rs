@@ -0,0 +1,103 @@
+use crate::error::FsError;
+
+/// How to resolve a write whose pre-write version doesn't match the
+/// backend's current version at upload-completion time, i.e. another
+/// client modified or deleted the node after this handle's `open` read
+/// its state but before this handle's write landed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ConflictPolicy {
+    /// Overwrite the newer backend state with this write anyway.
+    #[default]
+    LastWriterWins,
+    /// Fail the write (`EBUSY`) rather than risk clobbering newer data.
+    Fail,
+    /// Complete the write under a `.conflicted-<suffix>` sibling name
+    /// instead of the original, leaving the newer backend state intact.
+    RenameLoser,
+}
+
+/// Result of [`resolve_conflict`]: either proceed with the write as
+/// planned, or (under `RenameLoser`) complete it under a different name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictOutcome {
+    Proceed,
+    RenameTo(String),
+}
+
+/// Compares the version captured at `open` against the backend's current
+/// version and applies `policy` if they disagree. `None` on either side
+/// means the backend doesn't report versions for this node, in which case
+/// there's nothing to compare and the write always proceeds.
+pub fn resolve_conflict(
+    policy: ConflictPolicy,
+    opened_version: Option<&str>,
+    current_version: Option<&str>,
+    name: &str,
+    conflict_suffix: &str,
+) -> Result<ConflictOutcome, FsError> {
+    let conflicted = match (opened_version, current_version) {
+        (Some(a), Some(b)) => a != b,
+        _ => false,
+    };
+    if !conflicted {
+        return Ok(ConflictOutcome::Proceed);
+    }
+    match policy {
+        ConflictPolicy::LastWriterWins => Ok(ConflictOutcome::Proceed),
+        ConflictPolicy::Fail => Err(FsError::Conflict),
+        ConflictPolicy::RenameLoser => Ok(ConflictOutcome::RenameTo(format!(
+            "{name}.conflicted-{conflict_suffix}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_versions_always_proceed_regardless_of_policy() {
+        for policy in [
+            ConflictPolicy::LastWriterWins,
+            ConflictPolicy::Fail,
+            ConflictPolicy::RenameLoser,
+        ] {
+            assert_eq!(
+                resolve_conflict(policy, Some("v1"), Some("v1"), "data.bin", "abc"),
+                Ok(ConflictOutcome::Proceed)
+            );
+        }
+    }
+
+    #[test]
+    fn missing_version_information_is_not_treated_as_a_conflict() {
+        assert_eq!(
+            resolve_conflict(ConflictPolicy::Fail, None, Some("v2"), "data.bin", "abc"),
+            Ok(ConflictOutcome::Proceed)
+        );
+    }
+
+    #[test]
+    fn last_writer_wins_overwrites_despite_a_mismatch() {
+        assert_eq!(
+            resolve_conflict(ConflictPolicy::LastWriterWins, Some("v1"), Some("v2"), "data.bin", "abc"),
+            Ok(ConflictOutcome::Proceed)
+        );
+    }
+
+    #[test]
+    fn fail_policy_rejects_a_mismatched_write() {
+        assert_eq!(
+            resolve_conflict(ConflictPolicy::Fail, Some("v1"), Some("v2"), "data.bin", "abc"),
+            Err(FsError::Conflict)
+        );
+    }
+
+    #[test]
+    fn rename_loser_policy_diverts_to_a_conflicted_sidecar_name() {
+        assert_eq!(
+            resolve_conflict(ConflictPolicy::RenameLoser, Some("v1"), Some("v2"), "data.bin", "abc"),
+            Ok(ConflictOutcome::RenameTo("data.bin.conflicted-abc".to_string()))
+        );
+    }
+}
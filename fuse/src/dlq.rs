@@ -0,0 +1,217 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// One upload that exhausted its staging retries and was moved aside
+/// instead of being dropped outright, so an operator can inspect or
+/// retry it before it's eventually purged.
+#[derive(Debug, Clone)]
+pub struct DlqEntry {
+    pub node_id: String,
+    pub path: PathBuf,
+    pub failed_at: SystemTime,
+    pub size: u64,
+}
+
+/// How long to keep DLQ entries, and/or how much disk they may
+/// collectively occupy, before [`Dlq::purge`] reclaims them. `None`
+/// disables that dimension of the policy.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DlqRetentionPolicy {
+    pub max_age: Option<Duration>,
+    pub max_bytes: Option<u64>,
+}
+
+/// A directory (conventionally `staging/failed`) holding the raw bytes of
+/// uploads that exhausted [`crate::staging::StagingRetryConfig::max_attempts`],
+/// one file per failure, named `<node_id>-<failed_at_millis>.bin` so
+/// [`Dlq::list_entries`] can recover the failure time without relying on
+/// filesystem mtimes (which `tar`/backup/restore can clobber).
+pub struct Dlq {
+    dir: PathBuf,
+}
+
+impl Dlq {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Writes `data` to the DLQ, timestamped `SystemTime::now()`.
+    pub fn record_failure(&self, node_id: &str, data: &[u8]) -> io::Result<PathBuf> {
+        self.record_failure_at(node_id, data, SystemTime::now())
+    }
+
+    /// Like [`Self::record_failure`], with an explicit timestamp —
+    /// exposed separately so retention tests don't depend on real wall
+    /// clock gaps between writes.
+    pub fn record_failure_at(&self, node_id: &str, data: &[u8], failed_at: SystemTime) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        let millis = failed_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let path = self.dir.join(format!("{node_id}-{millis}.bin"));
+        fs::write(&path, data)?;
+        Ok(path)
+    }
+
+    /// Reads back the raw bytes of a DLQ entry so an operator-triggered
+    /// retry can re-attempt the upload before it's purged.
+    pub fn read_entry(&self, entry: &DlqEntry) -> io::Result<Vec<u8>> {
+        fs::read(&entry.path)
+    }
+
+    /// Removes a DLQ entry after it's been successfully retried.
+    pub fn remove_entry(&self, entry: &DlqEntry) -> io::Result<()> {
+        fs::remove_file(&entry.path)
+    }
+
+    /// Lists every entry currently in the DLQ, oldest first.
+    pub fn list_entries(&self) -> io::Result<Vec<DlqEntry>> {
+        let mut entries = Vec::new();
+        let read_dir = match fs::read_dir(&self.dir) {
+            Ok(rd) => rd,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(entries),
+            Err(e) => return Err(e),
+        };
+        for dir_entry in read_dir {
+            let dir_entry = dir_entry?;
+            let path = dir_entry.path();
+            if let Some(entry) = Self::parse_entry(&path, dir_entry.metadata()?.len()) {
+                entries.push(entry);
+            }
+        }
+        entries.sort_by_key(|e| e.failed_at);
+        Ok(entries)
+    }
+
+    fn parse_entry(path: &Path, size: u64) -> Option<DlqEntry> {
+        let stem = path.file_stem()?.to_str()?;
+        let (node_id, millis) = stem.rsplit_once('-')?;
+        let millis: u64 = millis.parse().ok()?;
+        Some(DlqEntry {
+            node_id: node_id.to_string(),
+            path: path.to_path_buf(),
+            failed_at: UNIX_EPOCH + Duration::from_millis(millis),
+            size,
+        })
+    }
+
+    /// Deletes entries older than `policy.max_age` (if set), then, if the
+    /// remaining entries still exceed `policy.max_bytes`, deletes the
+    /// oldest of those until back under budget. Logs and returns every
+    /// entry it removed so the deletion is at least auditable.
+    pub fn purge(&self, policy: DlqRetentionPolicy, now: SystemTime) -> io::Result<Vec<DlqEntry>> {
+        let entries = self.list_entries()?;
+        let mut removed = Vec::new();
+        let mut kept = Vec::new();
+        for entry in entries {
+            let age = now.duration_since(entry.failed_at).unwrap_or(Duration::ZERO);
+            if policy.max_age.is_some_and(|max_age| age > max_age) {
+                removed.push(entry);
+            } else {
+                kept.push(entry);
+            }
+        }
+
+        if let Some(max_bytes) = policy.max_bytes {
+            let mut total: u64 = kept.iter().map(|e| e.size).sum();
+            let mut cut = 0;
+            while total > max_bytes && cut < kept.len() {
+                total = total.saturating_sub(kept[cut].size);
+                cut += 1;
+            }
+            removed.extend(kept.drain(0..cut));
+        }
+
+        for entry in &removed {
+            if fs::remove_file(&entry.path).is_ok() {
+                eprintln!(
+                    "roset-fuse: purged DLQ entry for node {} ({} bytes, failed at {:?})",
+                    entry.node_id, entry.size, entry.failed_at
+                );
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries_older_than_max_age_are_purged_and_newer_ones_retained() {
+        let dir = std::env::temp_dir().join(format!("roset-fuse-dlq-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dlq = Dlq::new(dir.clone());
+
+        let now = SystemTime::now();
+        let old = now - Duration::from_secs(10 * 24 * 3600);
+        let recent = now - Duration::from_secs(60);
+        dlq.record_failure_at("old-node", b"stale", old).unwrap();
+        dlq.record_failure_at("fresh-node", b"new", recent).unwrap();
+
+        let removed = dlq
+            .purge(
+                DlqRetentionPolicy {
+                    max_age: Some(Duration::from_secs(24 * 3600)),
+                    max_bytes: None,
+                },
+                now,
+            )
+            .unwrap();
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].node_id, "old-node");
+
+        let remaining = dlq.list_entries().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].node_id, "fresh-node");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn oldest_entries_are_purged_first_once_max_bytes_is_exceeded() {
+        let dir = std::env::temp_dir().join(format!("roset-fuse-dlq-bytes-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dlq = Dlq::new(dir.clone());
+
+        let now = SystemTime::now();
+        dlq.record_failure_at("a", &[0u8; 10], now - Duration::from_secs(30)).unwrap();
+        dlq.record_failure_at("b", &[0u8; 10], now - Duration::from_secs(20)).unwrap();
+        dlq.record_failure_at("c", &[0u8; 10], now - Duration::from_secs(10)).unwrap();
+
+        let removed = dlq
+            .purge(
+                DlqRetentionPolicy {
+                    max_age: None,
+                    max_bytes: Some(15),
+                },
+                now,
+            )
+            .unwrap();
+
+        assert_eq!(removed.iter().map(|e| e.node_id.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+        let remaining = dlq.list_entries().unwrap();
+        assert_eq!(remaining.iter().map(|e| e.node_id.as_str()).collect::<Vec<_>>(), vec!["c"]);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn a_retried_entry_can_be_read_back_and_removed() {
+        let dir = std::env::temp_dir().join(format!("roset-fuse-dlq-retry-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dlq = Dlq::new(dir.clone());
+
+        dlq.record_failure("node-1", b"payload").unwrap();
+        let entries = dlq.list_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+
+        assert_eq!(dlq.read_entry(&entries[0]).unwrap(), b"payload");
+        dlq.remove_entry(&entries[0]).unwrap();
+        assert!(dlq.list_entries().unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,47 @@
+/// Filesystem-level errors, named after the errno they map to at the
+/// `fuser::Filesystem` boundary (reply.error(fs_err.into())).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsError {
+    NotFound,
+    ReadOnlyFilesystem,
+    PermissionDenied,
+    AlreadyExists,
+    NotADirectory,
+    IsADirectory,
+    Io,
+    CrossDevice,
+    /// A lease or other concurrency conflict distinct from
+    /// `AlreadyExists` — the backend rejected the request because another
+    /// writer holds a conflicting lease, not because the target name is
+    /// already taken. Maps to `EBUSY` so callers can tell "retry later"
+    /// apart from "pick a different name".
+    Conflict,
+    /// An operation that needs to write was attempted on a handle that
+    /// was degraded to read-only at `open` time (see
+    /// `RosetFs::acquire_write_lease`'s `--read-only-fallback` path).
+    /// Maps to `EBADF` rather than `EROFS` since the mount itself is
+    /// still writable — only this one already-open handle isn't.
+    BadFileDescriptor,
+    /// `readlink` called on a node that isn't a symlink. Maps to
+    /// `EINVAL`, matching what a real filesystem's `readlink(2)` returns
+    /// for the same misuse.
+    InvalidArgument,
+}
+
+impl FsError {
+    pub fn errno(self) -> i32 {
+        match self {
+            FsError::NotFound => libc::ENOENT,
+            FsError::ReadOnlyFilesystem => libc::EROFS,
+            FsError::PermissionDenied => libc::EACCES,
+            FsError::AlreadyExists => libc::EEXIST,
+            FsError::NotADirectory => libc::ENOTDIR,
+            FsError::IsADirectory => libc::EISDIR,
+            FsError::Io => libc::EIO,
+            FsError::CrossDevice => libc::EXDEV,
+            FsError::Conflict => libc::EBUSY,
+            FsError::BadFileDescriptor => libc::EBADF,
+            FsError::InvalidArgument => libc::EINVAL,
+        }
+    }
+}
@@ -0,0 +1,562 @@
+//! The real `fuser::Filesystem` binding for [`RosetFs`] — every kernel
+//! callback here is a thin adapter translating `fuser`'s inode/`OsStr`
+//! vocabulary into the path/`Node`-based helpers already implemented on
+//! `RosetFs` (see `fs.rs`), so the actual behavior (caching, leases,
+//! conflict handling, upload strategy, ...) lives in exactly one place.
+//!
+//! `unlink`/`rmdir` are deliberately left at `fuser`'s default `ENOSYS`:
+//! `RosetClient` has no delete-node endpoint to call, and a mount that
+//! claims to support deletion it can't actually perform on the backend
+//! would be worse than one that's honest about not supporting it yet.
+
+use std::ffi::OsStr;
+use std::time::Duration;
+
+use fuser::{
+    FileType, Filesystem, KernelConfig, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory,
+    ReplyEmpty, ReplyEntry, ReplyOpen, ReplyStatfs, ReplyWrite, ReplyXattr, Request, TimeOrNow,
+};
+
+use crate::error::FsError;
+use crate::fs::{fill_reply_directory, DirectoryListing, RosetFs};
+use crate::handle::Handle;
+use crate::node::NodeType;
+
+/// How long a `lookup`/`mkdir`/`symlink`/`create` entry is cached by the
+/// kernel before it re-validates via `getattr` — short enough that a
+/// change made through another mount (or another process on the
+/// backend) shows up quickly, long enough to spare a `stat`-heavy
+/// workload a round trip per lookup.
+const ENTRY_TTL: Duration = Duration::from_secs(1);
+
+/// Same trade-off as [`ENTRY_TTL`], for `getattr`/`setattr` replies.
+const ATTR_TTL: Duration = Duration::from_secs(1);
+
+fn node_kind(node_type: NodeType) -> FileType {
+    match node_type {
+        NodeType::File => FileType::RegularFile,
+        NodeType::Directory => FileType::Directory,
+        NodeType::Symlink => FileType::Symlink,
+    }
+}
+
+impl RosetFs {
+    /// Resolves `parent`'s path and appends `name`, the way every
+    /// by-name callback (`lookup`, `mkdir`, `create`, ...) needs before
+    /// it can touch [`Self::inodes`] or [`Self::cache`]. `None` for a
+    /// `parent` inode this mount has already forgotten.
+    fn full_path(&self, parent: u64, name: &OsStr) -> Option<String> {
+        let parent_path = self.inodes.path_for(parent)?;
+        let name = name.to_str()?;
+        if parent_path == "/" {
+            Some(format!("/{name}"))
+        } else {
+            Some(format!("{parent_path}/{name}"))
+        }
+    }
+
+    /// Fills in `handle.name`/`handle.parent_id` for a write handle
+    /// obtained via [`RosetFs::acquire_write_lease`] (`open`, unlike
+    /// `create`, only gets the target's own `ino` from the kernel, not its
+    /// parent), so a conflict on this handle can still be resolved under
+    /// `--conflict-policy=rename-loser`. Left unset — the `RenameLoser`
+    /// conflict then degrades to `Fail`, see `Handle::parent_id` — if this
+    /// mount has already forgotten `ino`'s path or its parent.
+    fn fill_handle_name_and_parent(&self, handle: &mut Handle, ino: u64) {
+        let Some(path) = self.inodes.path_for(ino) else {
+            return;
+        };
+        let (parent_path, name) = match path.rsplit_once('/') {
+            Some(("", name)) => ("/".to_string(), name.to_string()),
+            Some((parent, name)) => (parent.to_string(), name.to_string()),
+            None => return,
+        };
+        let parent_node = if parent_path == "/" {
+            self.inodes.node_for(crate::inode::ROOT_INO)
+        } else {
+            self.resolve_path(&parent_path).ok().flatten()
+        };
+        if let Some(parent_node) = parent_node {
+            handle.parent_id = Some(parent_node.id);
+            handle.name = Some(name);
+        }
+    }
+}
+
+impl Filesystem for RosetFs {
+    fn init(&mut self, _req: &Request<'_>, config: &mut KernelConfig) -> Result<(), libc::c_int> {
+        self.negotiate_init_capabilities(config)
+    }
+
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let Some(path) = self.full_path(parent, name) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        match self.resolve_path(&path) {
+            Ok(Some(node)) => {
+                let ino = self.inodes.lookup_id(path, node.clone());
+                reply.entry(&ENTRY_TTL, &self.node_to_attr(ino, &node), 0);
+            }
+            Ok(None) => reply.error(libc::ENOENT),
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn forget(&mut self, _req: &Request<'_>, ino: u64, nlookup: u64) {
+        self.inodes.forget(ino, nlookup);
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        if ino == crate::inode::ROOT_INO {
+            match self.getattr_root() {
+                Ok(attr) => reply.attr(&ATTR_TTL, &attr),
+                Err(e) => reply.error(e.errno()),
+            }
+            return;
+        }
+        match self.inodes.node_for(ino) {
+            Some(node) => reply.attr(&ATTR_TTL, &self.node_to_attr(ino, &node)),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn setattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<TimeOrNow>,
+        mtime: Option<TimeOrNow>,
+        _ctime: Option<std::time::SystemTime>,
+        fh: Option<u64>,
+        _crtime: Option<std::time::SystemTime>,
+        _chgtime: Option<std::time::SystemTime>,
+        _bkuptime: Option<std::time::SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        if uid.is_some() || gid.is_some() {
+            if let Err(_e) = self.chown(ino, uid, gid) {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        }
+
+        if let Some(fh) = fh {
+            if let Some(new_size) = size {
+                let mut handles = self.handles.lock().unwrap();
+                if let Some(handle) = handles.get_mut(&fh) {
+                    let dirty = handle.dirty.get_or_insert_with(Vec::new);
+                    dirty.resize(new_size as usize, 0);
+                    handle.record_write(0, new_size);
+                }
+            }
+            if let Some(mtime) = mtime {
+                let when = match mtime {
+                    TimeOrNow::SpecificTime(t) => t,
+                    TimeOrNow::Now => std::time::SystemTime::now(),
+                };
+                self.record_pending_mtime(fh, when);
+            }
+        }
+
+        match self.inodes.node_for(ino) {
+            Some(node) => reply.attr(&ATTR_TTL, &self.node_to_attr(ino, &node)),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn readlink(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyData) {
+        match RosetFs::readlink(self, ino) {
+            Ok(target) => reply.data(target.as_bytes()),
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn mkdir(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        reply: ReplyEntry,
+    ) {
+        let (Some(path), Some(name_str), Some(parent_node)) =
+            (self.full_path(parent, name), name.to_str(), self.inodes.node_for(parent))
+        else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        match RosetFs::mkdir(self, &parent_node.id, name_str) {
+            Ok(node) => {
+                let ino = self.inodes.lookup_id(path, node.clone());
+                reply.entry(&ENTRY_TTL, &self.node_to_attr(ino, &node), 0);
+            }
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn symlink(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        link_name: &OsStr,
+        target: &std::path::Path,
+        reply: ReplyEntry,
+    ) {
+        let (Some(path), Some(name_str), Some(target_str), Some(parent_node)) = (
+            self.full_path(parent, link_name),
+            link_name.to_str(),
+            target.to_str(),
+            self.inodes.node_for(parent),
+        ) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        match RosetFs::symlink(self, &parent_node.id, name_str, target_str) {
+            Ok(node) => {
+                let ino = self.inodes.lookup_id(path, node.clone());
+                reply.entry(&ENTRY_TTL, &self.node_to_attr(ino, &node), 0);
+            }
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        _flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        let (Some(old_path), Some(new_path), Some(new_parent_node), Some(new_name_str)) = (
+            self.full_path(parent, name),
+            self.full_path(newparent, newname),
+            self.inodes.node_for(newparent),
+            newname.to_str(),
+        ) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        let node = match self.resolve_path(&old_path) {
+            Ok(Some(node)) => node,
+            Ok(None) => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+            Err(e) => {
+                reply.error(e.errno());
+                return;
+            }
+        };
+        match RosetFs::rename(self, &node.id, &new_parent_node.id, new_name_str, &old_path) {
+            Ok(renamed) => {
+                self.inodes.map_id(new_path, renamed);
+                reply.ok();
+            }
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        if let Err(e) = self.check_not_a_directory(ino) {
+            reply.error(e.errno());
+            return;
+        }
+        let Some(node) = self.inodes.node_for(ino) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        let wants_write = flags & (libc::O_WRONLY | libc::O_RDWR) != 0;
+        let handle = if wants_write {
+            match self.acquire_write_lease(&node.id, flags) {
+                Ok(mut handle) => {
+                    self.fill_handle_name_and_parent(&mut handle, ino);
+                    handle
+                }
+                Err(e) => {
+                    reply.error(e.errno());
+                    return;
+                }
+            }
+        } else {
+            Handle::new(node.id.clone())
+        };
+        let fh = self.alloc_fh();
+        self.handles.lock().unwrap().insert(fh, handle);
+        reply.opened(fh, self.open_reply_flags(ino));
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        _flags: i32,
+        reply: ReplyCreate,
+    ) {
+        let (Some(path), Some(name_str), Some(parent_node)) =
+            (self.full_path(parent, name), name.to_str(), self.inodes.node_for(parent))
+        else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        match self.handle_create(&parent_node.id, name_str) {
+            Ok((node, handle)) => {
+                let ino = self.inodes.lookup_id(path, node.clone());
+                let fh = self.alloc_fh();
+                self.handles.lock().unwrap().insert(fh, handle);
+                reply.created(&ENTRY_TTL, &self.node_to_attr(ino, &node), 0, fh, self.open_reply_flags(ino));
+            }
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let offset = offset.max(0) as u64;
+        let len = size as u64;
+
+        if let Some(dirty) = self.handles.lock().unwrap().get(&fh).and_then(|h| h.dirty.as_ref()) {
+            let start = (offset as usize).min(dirty.len());
+            let end = (start + len as usize).min(dirty.len());
+            reply.data(&dirty[start..end]);
+            return;
+        }
+
+        let Some(node) = self.inodes.node_for(ino) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        match self.read_range(&node, offset, len) {
+            Ok(data) => reply.data(&data),
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        let offset = offset.max(0) as u64;
+        let mut handles = self.handles.lock().unwrap();
+        let Some(handle) = handles.get_mut(&fh) else {
+            reply.error(libc::EBADF);
+            return;
+        };
+        if let Err(e) = self.check_handle_writable(handle) {
+            reply.error(e.errno());
+            return;
+        }
+        let buf = handle.dirty.get_or_insert_with(Vec::new);
+        let end = offset as usize + data.len();
+        if buf.len() < end {
+            buf.resize(end, 0);
+        }
+        buf[offset as usize..end].copy_from_slice(data);
+        handle.record_write(offset, data.len() as u64);
+        reply.written(data.len() as u32);
+    }
+
+    fn flush(&mut self, _req: &Request<'_>, _ino: u64, fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
+        match self.handle_flush(fh) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        self.handle_release(fh);
+        reply.ok();
+    }
+
+    fn fsync(&mut self, _req: &Request<'_>, _ino: u64, fh: u64, datasync: bool, reply: ReplyEmpty) {
+        match self.handle_fsync(fh, datasync) {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn fsyncdir(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, datasync: bool, reply: ReplyEmpty) {
+        match RosetFs::fsyncdir(self, ino, datasync) {
+            Ok(()) => reply.ok(),
+            Err(_) => reply.error(libc::EIO),
+        }
+    }
+
+    fn readdir(&mut self, _req: &Request<'_>, ino: u64, _fh: u64, offset: i64, mut reply: ReplyDirectory) {
+        let Some(dir_path) = self.inodes.path_for(ino) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        let Some(dir_node) = self.inodes.node_for(ino) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+
+        let children = if RosetFs::is_trash_path(&dir_path) {
+            self.list_trash()
+        } else {
+            match self.list_committed_directory(&dir_node.id) {
+                DirectoryListing::Manifest(nodes) => Ok(nodes),
+                DirectoryListing::Paged(listing) => Ok(listing.children),
+            }
+        };
+        let children = match children {
+            Ok(nodes) => self.filter_ignored(nodes),
+            Err(e) => {
+                reply.error(e.errno());
+                return;
+            }
+        };
+
+        let prefix = if dir_path == "/" { String::new() } else { dir_path.clone() };
+        let mut full = false;
+        fill_reply_directory(&children, offset, |node, cookie| {
+            if full {
+                return true;
+            }
+            let child_path = format!("{prefix}/{}", node.name);
+            let ino = self.inodes.map_id(child_path, node.clone());
+            if reply.add(ino, cookie, node_kind(node.node_type), &node.name) {
+                full = true;
+            }
+            full
+        });
+        reply.ok();
+    }
+
+    fn statfs(&mut self, _req: &Request<'_>, _ino: u64, reply: ReplyStatfs) {
+        let s = self.handle_statfs();
+        reply.statfs(s.blocks, s.bfree, s.bavail, s.files, s.ffree, s.bsize, s.namelen, s.frsize);
+    }
+
+    fn setxattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        value: &[u8],
+        _flags: i32,
+        _position: u32,
+        reply: ReplyEmpty,
+    ) {
+        let Some(name) = name.to_str() else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        let result = match name {
+            "user.roset.recover" => self.handle_recover_xattr(),
+            "user.roset.invalidate" => {
+                if let Some(path) = self.inodes.path_for(ino) {
+                    self.handle_invalidate_xattr(ino, &path);
+                }
+                Ok(())
+            }
+            "user.roset.commit" => {
+                let Some(node) = self.inodes.node_for(ino) else {
+                    reply.error(libc::ENOENT);
+                    return;
+                };
+                let message = String::from_utf8_lossy(value).to_string();
+                self.handle_commit_xattr(ino, &node.id, &message).map(|_| ()).map_err(|_| FsError::Io)
+            }
+            _ => self.set_binary_xattr(ino, name, value),
+        };
+        match result {
+            Ok(()) => reply.ok(),
+            Err(e) => reply.error(e.errno()),
+        }
+    }
+
+    fn getxattr(&mut self, _req: &Request<'_>, ino: u64, name: &OsStr, size: u32, reply: ReplyXattr) {
+        let Some(name) = name.to_str() else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        let value = match name {
+            "user.roset.ready" => Some(self.ready_xattr()),
+            "user.roset.last-commit" => self.last_commit(ino).map(String::into_bytes),
+            "user.roset.upload-progress" => self.upload_progress_xattr(ino),
+            _ => self.get_binary_xattr(ino, name),
+        };
+        match value {
+            Some(value) => {
+                if size == 0 {
+                    reply.size(value.len() as u32);
+                } else if value.len() as u32 > size {
+                    reply.error(libc::ERANGE);
+                } else {
+                    reply.data(&value);
+                }
+            }
+            None => reply.error(libc::ENODATA),
+        }
+    }
+
+    fn listxattr(&mut self, _req: &Request<'_>, ino: u64, size: u32, reply: ReplyXattr) {
+        let Some(node) = self.inodes.node_for(ino) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        let mut names = Vec::new();
+        for key in node.metadata.keys() {
+            if let Some(name) = key.strip_prefix("xattr.") {
+                names.push(name.to_string());
+            }
+        }
+        let mut buf = Vec::new();
+        for name in &names {
+            buf.extend_from_slice(name.as_bytes());
+            buf.push(0);
+        }
+        if size == 0 {
+            reply.size(buf.len() as u32);
+        } else if buf.len() as u32 > size {
+            reply.error(libc::ERANGE);
+        } else {
+            reply.data(&buf);
+        }
+    }
+
+    fn access(&mut self, _req: &Request<'_>, _ino: u64, _mask: i32, reply: ReplyEmpty) {
+        reply.ok();
+    }
+}
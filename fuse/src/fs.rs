@@ -0,0 +1,4677 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::cache::{AttrCache, CachePolicy};
+use crate::client::RosetClient;
+use crate::error::FsError;
+use crate::handle::Handle;
+use crate::inode::{InodeMap, ROOT_INO};
+use crate::node::{Node, NodeType};
+use crate::notify::Notifier;
+use crate::staging::StagingManager;
+
+/// Default `st_blksize` reported in `getattr`: the I/O size we actually
+/// prefer callers use, independent of the (much larger) multipart
+/// upload part size.
+const DEFAULT_BLOCK_SIZE: u32 = 128 * 1024;
+
+/// Virtual directory at the mount root surfacing soft-deleted nodes, so
+/// an accidental `rm` can be recovered without leaving the mount.
+pub const TRASH_DIR_NAME: &str = ".roset-trash";
+
+/// Default cap on how many entries a committed directory's manifest may
+/// have before `list_committed_directory` gives up on bulk-loading it and
+/// falls back to lazy paged listing instead. A manifest for a
+/// million-file dataset loaded in one shot (and cached in one shot) can
+/// OOM the process; paged listing keeps memory bounded at the cost of
+/// one round-trip per page instead of one round-trip total.
+pub const DEFAULT_MANIFEST_NODE_COUNT_THRESHOLD: usize = 50_000;
+
+/// Default cap on how large a file [`RosetFs::read_small_file_inline`]
+/// will fetch in a single round trip. Opening and reading a tiny file
+/// (a config, a label, a small JSON blob) otherwise costs the normal
+/// signed-URL-then-range-read dance for a few hundred bytes, which
+/// dominates metadata-heavy dataset traversal.
+pub const DEFAULT_INLINE_CONTENT_MAX_BYTES: u64 = 16 * 1024;
+
+/// How long [`RosetFs::getattr_root`] trusts its own cached root
+/// `FileAttr` before refreshing it from the backend. Deliberately shorter
+/// than a typical [`AttrCache`] TTL: the root is resolved far more often
+/// than anything else (every `stat`/`ls` of the mountpoint touches it), so
+/// this bounds how stale a legitimate change to the root's own metadata
+/// (e.g. an out-of-band commit) can appear, independent of whatever TTL
+/// the general cache was configured with.
+const ROOT_ATTR_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How long before a write [`crate::client::Lease`] expires
+/// [`RosetFs::spawn_lease_renewal`] sends the renewal, so a slow renewal
+/// round trip or a missed wakeup still leaves slack before the backend
+/// actually lets the lease lapse out from under a long-running write.
+const LEASE_RENEWAL_MARGIN: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Default kernel readahead window requested during `init`
+/// (`--max-readahead-kb`). Larger than the kernel's own conservative
+/// default (128 KiB): this mount's backend is a higher-latency HTTP API
+/// rather than a local block device, so the kernel's usual tuning
+/// assumptions undersell how much readahead pays for itself here.
+pub const DEFAULT_MAX_READAHEAD_KB: u32 = 1024;
+
+/// Bounds enforced by [`validate_max_readahead_kb`]. Below the low end
+/// the kernel's own default already does at least as well; above the
+/// high end, one sequential reader could pin an unreasonable amount of
+/// page cache against a single file.
+pub const MIN_MAX_READAHEAD_KB: u32 = 4;
+pub const MAX_MAX_READAHEAD_KB: u32 = 16 * 1024;
+
+/// Rejects a `--max-readahead-kb` value outside
+/// `[MIN_MAX_READAHEAD_KB, MAX_MAX_READAHEAD_KB]`, the same way
+/// [`crate::staging::validate_staging_dir`] rejects a bad
+/// `--staging-dir` before the mount ever starts, rather than letting an
+/// unreasonable value reach the kernel via `init`.
+pub fn validate_max_readahead_kb(kb: u32) -> Result<(), String> {
+    if !(MIN_MAX_READAHEAD_KB..=MAX_MAX_READAHEAD_KB).contains(&kb) {
+        return Err(format!(
+            "--max-readahead-kb must be between {MIN_MAX_READAHEAD_KB} and {MAX_MAX_READAHEAD_KB}, got {kb}"
+        ));
+    }
+    Ok(())
+}
+
+/// A single cached `getattr(ROOT_INO)` reply, refreshed no more than once
+/// per [`ROOT_ATTR_REFRESH_INTERVAL`] — see [`RosetFs::getattr_root`].
+struct RootAttrEntry {
+    attr: fuser::FileAttr,
+    cached_at: std::time::Instant,
+}
+
+/// The outcome of [`RosetFs::list_committed_directory`]: either the whole
+/// subtree manifest, small enough to bulk-load into the cache in one
+/// shot, or a paged listing for a subtree too large to do that safely.
+pub enum DirectoryListing {
+    Manifest(Vec<Node>),
+    Paged(crate::client::ChildListing),
+}
+
+/// Feeds `entries` into `add` (a `readdir` reply's `add`, e.g.
+/// `fuser::ReplyDirectory::add`) one at a time, resuming from `offset`
+/// (the cookie the kernel passed back in on this call) and stopping as
+/// soon as `add` reports the reply buffer is full.
+///
+/// `add` is called with the cookie to resume from *after* this entry —
+/// `i + 1` for the entry at index `i` — matching `fuser`'s convention
+/// that the cookie identifies where the *next* call should continue, not
+/// the entry itself. When `add` rejects an entry (buffer full), that
+/// entry's own cookie is never reused for anything already delivered,
+/// so the next call's `offset` lands exactly back on the rejected entry
+/// — including when the rejection happens to be the very last entry in
+/// `entries`, which still resumes correctly rather than being silently
+/// dropped or re-sent a second time.
+///
+/// Requires `entries` to be the same snapshot across every call for one
+/// `readdir` sequence (e.g. from an opendir-time snapshot); a listing
+/// that changes between calls can still skip or duplicate entries, which
+/// this function has no way to detect on its own.
+pub fn fill_reply_directory<F>(entries: &[Node], offset: i64, mut add: F)
+where
+    F: FnMut(&Node, i64) -> bool,
+{
+    let start = offset.max(0) as usize;
+    for (i, entry) in entries.iter().enumerate().skip(start) {
+        if add(entry, i as i64 + 1) {
+            break;
+        }
+    }
+}
+
+/// Controls when a dirty write's upload is forced to complete rather than
+/// left to finish on the `StagingManager`'s background worker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum WriteDurability {
+    /// `flush` returns immediately; `release` hands any dirty data to the
+    /// staging queue and the upload finishes in the background.
+    #[default]
+    Async,
+    /// `flush` blocks until the upload completes, returning `EIO` on
+    /// failure, so a successful `close()` guarantees the data has
+    /// actually landed.
+    Sync,
+}
+
+/// Controls whether `create` stages an upload session for the new node
+/// right away or waits for the first `write`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum CreateUploadMode {
+    /// `create` immediately stages an empty upload for the new node, the
+    /// same as every prior write this mount has done. Simple, but spins
+    /// up a staging job (and, once multipart uploads are wired in, an
+    /// upload session) for files that may never be written to.
+    #[default]
+    Eager,
+    /// `create` only creates the node's metadata; the upload session is
+    /// lazily staged on the first `write` instead. A file created and
+    /// closed without ever being written (a lock file, a sentinel)
+    /// never pays for an upload session at all.
+    Deferred,
+}
+
+/// Default capacity (`--reported-capacity-gb`) [`RosetFs::handle_statfs`]
+/// reports when the backend has no real capacity endpoint to query.
+/// Large enough that `df` and training frameworks that preflight free
+/// space before writing see ample room instead of the zeros/garbage an
+/// unimplemented `statfs` otherwise leaves them with.
+pub const DEFAULT_REPORTED_CAPACITY_GB: u64 = 1024 * 1024;
+
+/// Synthetic inode headroom [`RosetFs::handle_statfs`] reports as
+/// `ffree`, since there's no real inode-count ceiling to approach against
+/// a remote backend.
+const SYNTHETIC_FREE_INODE_HEADROOM: u64 = 1_000_000_000;
+
+/// Synthetic `statfs` reply — see [`RosetFs::handle_statfs`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatfsReply {
+    pub blocks: u64,
+    pub bfree: u64,
+    pub bavail: u64,
+    pub files: u64,
+    pub ffree: u64,
+    pub bsize: u32,
+    pub namelen: u32,
+    pub frsize: u32,
+}
+
+/// Controls which reads [`Self::with_block_cache`]'s block cache is
+/// allowed to serve from local disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ReadCachePolicy {
+    /// Cache committed/immutable nodes' blocks indefinitely; a mutable
+    /// node's reads always go straight to the backend, since its content
+    /// can change underneath the cache at any time.
+    #[default]
+    ImmutableOnly,
+    /// Cache every node's blocks — immutable ones indefinitely, mutable
+    /// ones for [`crate::block_cache::DEFAULT_MUTABLE_BLOCK_CACHE_TTL`] —
+    /// trading a window of possible staleness for fewer backend round
+    /// trips on hot mutable files.
+    All,
+    /// Disable the block cache outright, regardless of `--cache-dir`;
+    /// every read goes straight to [`RosetClient::download_range`].
+    None,
+}
+
+/// One discrepancy found by [`RosetFs::verify_subtree`] between the
+/// mount's cached view of a path and what the backend reports right now.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Discrepancy {
+    /// `path` has a positive cache entry, but the backend no longer has a
+    /// node there (or never did).
+    MissingFromBackend { path: String },
+    /// `path` exists on the backend but the cache has no entry for it,
+    /// positive or negative.
+    MissingFromCache { path: String },
+    /// Both sides agree `path` exists but disagree on its type.
+    TypeMismatch {
+        path: String,
+        cached: NodeType,
+        backend: NodeType,
+    },
+    /// Both sides agree `path` exists but disagree on its size.
+    SizeMismatch {
+        path: String,
+        cached: Option<u64>,
+        backend: Option<u64>,
+    },
+}
+
+/// The `fuser::Filesystem` implementation backing a Roset mount.
+///
+/// `fuser`'s callback methods are synchronous, so FUSE ops that need to
+/// talk to the Roset API hand work off to a Tokio runtime and block on
+/// it; `handles` and the other fields here are shared with that runtime.
+pub struct RosetFs {
+    pub(crate) handles: Mutex<HashMap<u64, Handle>>,
+    pub(crate) client: RosetClient,
+    pub(crate) staging: StagingManager,
+    pub(crate) cache: AttrCache,
+    pub(crate) inodes: InodeMap,
+    pub(crate) notifier: Option<Notifier>,
+    pub(crate) root_node_id: String,
+    pub(crate) commit_on_unmount: bool,
+    pub(crate) read_only: AtomicBool,
+    pub(crate) read_only_fallback: AtomicBool,
+    pub(crate) allow_offline: AtomicBool,
+    pub(crate) degraded: AtomicBool,
+    pub(crate) poll_registry: crate::poll::PollRegistry,
+    pub(crate) direct_io: bool,
+    pub(crate) allow_security_capability_xattr: bool,
+    pub(crate) writeback_cache: bool,
+    pub(crate) shared_cache: Option<crate::shared_cache::SharedBlockCache>,
+    pub(crate) block_size: u32,
+    pub(crate) max_write_bytes: u32,
+    pub(crate) read_ahead_bytes: u64,
+    pub(crate) max_readahead_kb: u32,
+    pub(crate) conflict_policy: crate::conflict::ConflictPolicy,
+    write_durability: WriteDurability,
+    create_upload_mode: CreateUploadMode,
+    ignore_globs: Vec<glob::Pattern>,
+    allow_hidden_lookup: bool,
+    manifest_node_count_threshold: usize,
+    inline_content_threshold: u64,
+    manifest_unsupported: AtomicBool,
+    patch_unsupported: AtomicBool,
+    block_cache: Option<crate::block_cache::BlockCache>,
+    read_cache_policy: ReadCachePolicy,
+    reported_capacity_bytes: u64,
+    last_commits: Mutex<HashMap<u64, String>>,
+    root_attr: Mutex<Option<RootAttrEntry>>,
+    runtime: tokio::runtime::Handle,
+    next_fh: std::sync::atomic::AtomicU64,
+}
+
+impl RosetFs {
+    pub fn new(
+        client: RosetClient,
+        staging: StagingManager,
+        cache: AttrCache,
+        runtime: tokio::runtime::Handle,
+    ) -> Self {
+        Self {
+            handles: Mutex::new(HashMap::new()),
+            client,
+            staging,
+            cache,
+            inodes: InodeMap::new(),
+            notifier: None,
+            root_node_id: String::new(),
+            commit_on_unmount: false,
+            read_only: AtomicBool::new(false),
+            read_only_fallback: AtomicBool::new(false),
+            allow_offline: AtomicBool::new(false),
+            degraded: AtomicBool::new(false),
+            poll_registry: crate::poll::PollRegistry::new(),
+            direct_io: false,
+            allow_security_capability_xattr: false,
+            writeback_cache: false,
+            shared_cache: None,
+            block_size: DEFAULT_BLOCK_SIZE,
+            max_write_bytes: crate::upload::DEFAULT_MAX_WRITE_CHUNK as u32,
+            read_ahead_bytes: crate::readahead::DEFAULT_READ_AHEAD_BYTES,
+            max_readahead_kb: DEFAULT_MAX_READAHEAD_KB,
+            conflict_policy: crate::conflict::ConflictPolicy::default(),
+            write_durability: WriteDurability::default(),
+            create_upload_mode: CreateUploadMode::default(),
+            ignore_globs: Vec::new(),
+            allow_hidden_lookup: false,
+            manifest_node_count_threshold: DEFAULT_MANIFEST_NODE_COUNT_THRESHOLD,
+            inline_content_threshold: DEFAULT_INLINE_CONTENT_MAX_BYTES,
+            manifest_unsupported: AtomicBool::new(false),
+            patch_unsupported: AtomicBool::new(false),
+            block_cache: None,
+            read_cache_policy: ReadCachePolicy::default(),
+            reported_capacity_bytes: DEFAULT_REPORTED_CAPACITY_GB * 1024 * 1024 * 1024,
+            last_commits: Mutex::new(HashMap::new()),
+            root_attr: Mutex::new(None),
+            runtime,
+            next_fh: std::sync::atomic::AtomicU64::new(1),
+        }
+    }
+
+    /// Allocates a fresh, process-unique file handle for `open`/`create`.
+    pub(crate) fn alloc_fh(&self) -> u64 {
+        self.next_fh.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Overrides [`DEFAULT_MANIFEST_NODE_COUNT_THRESHOLD`].
+    pub fn with_manifest_node_count_threshold(mut self, threshold: usize) -> Self {
+        self.manifest_node_count_threshold = threshold;
+        self
+    }
+
+    /// Overrides [`DEFAULT_INLINE_CONTENT_MAX_BYTES`].
+    pub fn with_inline_content_threshold(mut self, threshold: u64) -> Self {
+        self.inline_content_threshold = threshold;
+        self
+    }
+
+    /// Configures how often [`Self::resolve_path`] double-checks a cached
+    /// negative lookup against the backend instead of trusting it — see
+    /// [`crate::cache::NegativeRevalidationPolicy`]. Disabled by default.
+    pub fn with_negative_revalidation_policy(self, policy: crate::cache::NegativeRevalidationPolicy) -> Self {
+        self.cache.set_negative_revalidation_policy(policy);
+        self
+    }
+
+    /// Entries matching any of these globs (e.g. `.roset*`) are hidden
+    /// from `readdir` results by [`Self::filter_ignored`] — a backend
+    /// node named `.roset-something` shouldn't be confused for this
+    /// mount's own control/staging files.
+    pub fn with_ignore_globs(mut self, patterns: Vec<glob::Pattern>) -> Self {
+        self.ignore_globs = patterns;
+        self
+    }
+
+    /// Drops entries whose name matches a configured ignore-glob (see
+    /// [`Self::with_ignore_globs`]) from a `readdir` listing.
+    pub fn filter_ignored(&self, nodes: Vec<Node>) -> Vec<Node> {
+        if self.ignore_globs.is_empty() {
+            return nodes;
+        }
+        nodes
+            .into_iter()
+            .filter(|node| !self.is_hidden(&node.name))
+            .collect()
+    }
+
+    /// Whether `name` matches one of the configured ignore-globs (see
+    /// [`Self::with_ignore_globs`]).
+    pub fn is_hidden(&self, name: &str) -> bool {
+        self.ignore_globs.iter().any(|pattern| pattern.matches(name))
+    }
+
+    /// By default, `--ignore-glob`/`--hide-glob` entries also can't be
+    /// `lookup`'d by exact name (`ENOENT`), not just hidden from
+    /// `readdir` — set this to keep them directly accessible by name
+    /// while still hiding them from listings. Either way, writes can
+    /// still *create* a hidden-named file; this only gates read-path
+    /// name resolution.
+    pub fn with_allow_hidden_lookup(mut self, allow: bool) -> Self {
+        self.allow_hidden_lookup = allow;
+        self
+    }
+
+    /// Handles `lookup(parent, name)` for a hidden (ignore-globbed) name:
+    /// `ENOENT` unless `--allow-hidden-lookup` is set.
+    pub fn check_lookup_visible(&self, name: &str) -> Result<(), FsError> {
+        if self.is_hidden(name) && !self.allow_hidden_lookup {
+            Err(FsError::NotFound)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn with_read_only(self, read_only: bool) -> Self {
+        self.read_only.store(read_only, Ordering::SeqCst);
+        self
+    }
+
+    /// When a write lease can't be acquired because another writer holds
+    /// it, degrade the open to a read-only handle instead of failing it
+    /// with `EBUSY` — see [`Self::acquire_write_lease`].
+    pub fn with_read_only_fallback(self, read_only_fallback: bool) -> Self {
+        self.read_only_fallback.store(read_only_fallback, Ordering::SeqCst);
+        self
+    }
+
+    pub fn with_allow_offline(self, allow_offline: bool) -> Self {
+        self.allow_offline.store(allow_offline, Ordering::SeqCst);
+        self
+    }
+
+    pub fn with_direct_io(mut self, direct_io: bool) -> Self {
+        self.direct_io = direct_io;
+        self
+    }
+
+    /// Gates `security.capability` xattr support (see
+    /// [`Self::set_binary_xattr`]/[`Self::get_binary_xattr`]): allowing
+    /// file capabilities to round-trip through the mount means a
+    /// capability-bearing binary placed on a shared mount could grant
+    /// itself privileges on any node that executes it, so this defaults
+    /// to off.
+    pub fn with_security_capability_xattr(mut self, allow: bool) -> Self {
+        self.allow_security_capability_xattr = allow;
+        self
+    }
+
+    /// Opts into the kernel's writeback cache: the kernel coalesces small
+    /// writes itself and may send them after `release`/`close` rather
+    /// than synchronously, and `setattr` size/mtime updates can arrive
+    /// out of order with respect to pending writes. `write`/`setattr`/
+    /// `flush` need to account for that relaxed ordering once this is on,
+    /// so it stays opt-in rather than auto-detected.
+    pub fn with_writeback_cache(mut self, enabled: bool) -> Self {
+        self.writeback_cache = enabled;
+        self
+    }
+
+    /// Opts into a node-local cache directory shared across every mount
+    /// of the same dataset on this node, deduplicating downloads across
+    /// pods. Entries are keyed by node id plus version (see
+    /// [`Self::read_small_file_inline`]), not by content hash, so identical
+    /// content under two different node ids is downloaded twice.
+    pub fn with_shared_cache_dir(mut self, dir: Option<std::path::PathBuf>) -> Self {
+        self.shared_cache = dir.and_then(|d| crate::shared_cache::SharedBlockCache::new(d).ok());
+        self
+    }
+
+    /// Opts into a disk-backed cache of this mount's own file content
+    /// blocks at `dir` (e.g. a fast local NVMe), bounded by
+    /// `capacity_bytes` (`--cache-size-mb`/`cacheSizeGi`) — see
+    /// [`crate::block_cache::BlockCache`]. `None` leaves every read
+    /// hitting [`RosetClient::download_range`] directly, as before this
+    /// cache existed.
+    pub fn with_block_cache(mut self, dir: Option<std::path::PathBuf>, capacity_bytes: u64) -> Self {
+        self.block_cache = dir.and_then(|d| {
+            crate::block_cache::BlockCache::new(d, crate::block_cache::DEFAULT_BLOCK_SIZE, capacity_bytes).ok()
+        });
+        self
+    }
+
+    /// Sets the policy (`--read-cache-policy`) [`Self::read_block`]
+    /// consults to decide whether a block is eligible for
+    /// [`Self::with_block_cache`]'s cache at all, and for how long.
+    pub fn with_read_cache_policy(mut self, policy: ReadCachePolicy) -> Self {
+        self.read_cache_policy = policy;
+        self
+    }
+
+    /// Overrides [`DEFAULT_REPORTED_CAPACITY_GB`] (`--reported-capacity-gb`),
+    /// the synthetic total capacity [`Self::handle_statfs`] reports.
+    pub fn with_reported_capacity_bytes(mut self, reported_capacity_bytes: u64) -> Self {
+        self.reported_capacity_bytes = reported_capacity_bytes;
+        self
+    }
+
+    /// Overrides the preferred I/O size reported as `st_blksize`, e.g. to
+    /// match the part size used for multipart uploads.
+    pub fn with_block_size(mut self, block_size: u32) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Overrides the max single-write size negotiated with the kernel
+    /// (`--max-write-kb`). This only sets `max_write` at `init`; writes
+    /// are not chunked by it, since they land in the handle's in-memory
+    /// `dirty` buffer rather than a local temp file.
+    pub fn with_max_write_bytes(mut self, max_write_bytes: u32) -> Self {
+        self.max_write_bytes = max_write_bytes;
+        self
+    }
+
+    /// Overrides the background prefetch window (`--read-ahead-kb`) kicked
+    /// off once a handle's reads look sequential, see
+    /// [`Self::plan_handle_read_ahead`]. `0` disables read-ahead entirely.
+    pub fn with_read_ahead(mut self, read_ahead_bytes: u64) -> Self {
+        self.read_ahead_bytes = read_ahead_bytes;
+        self
+    }
+
+    /// Overrides the kernel readahead window requested during `init`
+    /// (`--max-readahead-kb`) — see [`Self::negotiate_init_capabilities`].
+    /// Callers should validate with [`validate_max_readahead_kb`] first;
+    /// this setter itself doesn't re-check the bounds.
+    pub fn with_max_readahead_kb(mut self, max_readahead_kb: u32) -> Self {
+        self.max_readahead_kb = max_readahead_kb;
+        self
+    }
+
+    /// Sets the policy (`--conflict-policy`) consulted when a write's
+    /// pre-write version disagrees with the backend's current version at
+    /// upload completion. See [`Self::resolve_write_conflict`].
+    pub fn with_conflict_policy(mut self, policy: crate::conflict::ConflictPolicy) -> Self {
+        self.conflict_policy = policy;
+        self
+    }
+
+    /// Sets the policy (`--write-durability`) [`Self::handle_flush`]
+    /// consults to decide whether a dirty write's upload must complete
+    /// before `flush` returns.
+    pub fn with_write_durability(mut self, durability: WriteDurability) -> Self {
+        self.write_durability = durability;
+        self
+    }
+
+    /// Sets the policy (`--create-upload-mode`) [`Self::handle_create`]
+    /// consults to decide whether a new node's upload session is staged
+    /// right away or deferred to the first `write`.
+    pub fn with_create_upload_mode(mut self, mode: CreateUploadMode) -> Self {
+        self.create_upload_mode = mode;
+        self
+    }
+
+    /// Decides what to do with a completing upload whose handle's
+    /// `opened_version` disagrees with `current_version` (the backend's
+    /// version just before completion), per the configured
+    /// `--conflict-policy`. `conflict_suffix` names the sidecar file under
+    /// `RenameLoser`; callers pass something unique per conflict (e.g. a
+    /// short id) so repeated conflicts on the same file don't collide.
+    pub fn resolve_write_conflict(
+        &self,
+        handle: &Handle,
+        current_version: Option<&str>,
+        name: &str,
+        conflict_suffix: &str,
+    ) -> Result<crate::conflict::ConflictOutcome, FsError> {
+        crate::conflict::resolve_conflict(
+            self.conflict_policy,
+            handle.opened_version.as_deref(),
+            current_version,
+            name,
+            conflict_suffix,
+        )
+    }
+
+    /// Builds the `fuser::FileAttr` reply for `node` at `ino`.
+    ///
+    /// `st_blksize` reflects the configured preferred I/O size
+    /// ([`Self::with_block_size`]) rather than a fixed `4096`, so callers
+    /// that size their I/O off it pick efficient request sizes for this
+    /// backend. `st_blocks` stays in 512-byte units per POSIX regardless
+    /// of `blksize`. For a node that reports a `stored_size` metadata key
+    /// (e.g. a sparse file, or an immutable snapshot whose actual stored
+    /// bytes differ from its logical size), `blocks` is computed from
+    /// that instead of `size` so `du` reflects real backend usage.
+    pub fn node_to_attr(&self, ino: u64, node: &Node) -> fuser::FileAttr {
+        let size = self.resolve_size(node).unwrap_or(0);
+        let size_for_blocks = node
+            .metadata
+            .get("stored_size")
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(size);
+        let kind = match node.node_type {
+            crate::node::NodeType::File => fuser::FileType::RegularFile,
+            crate::node::NodeType::Directory => fuser::FileType::Directory,
+            crate::node::NodeType::Symlink => fuser::FileType::Symlink,
+        };
+        fuser::FileAttr {
+            ino,
+            size,
+            blocks: size_for_blocks.div_ceil(512),
+            atime: node.mtime,
+            mtime: node.mtime,
+            ctime: node.mtime,
+            crtime: node.mtime,
+            kind,
+            perm: 0o644,
+            nlink: 1,
+            uid: node.uid().unwrap_or(0),
+            gid: node.gid().unwrap_or(0),
+            rdev: 0,
+            blksize: self.block_size,
+            flags: 0,
+        }
+    }
+
+    /// Resolves `node`'s size, fetching the authoritative value from the
+    /// backend when the node's own `size` is still `None` (the backend
+    /// hasn't finished computing it yet, e.g. right after an upload
+    /// completes but before finalization). Without this, `read` would see
+    /// a defaulted size of `0` and immediately return empty since
+    /// `offset >= file_size`, even though the file has real content.
+    pub fn resolve_size(&self, node: &Node) -> Result<u64, FsError> {
+        match node.size {
+            Some(size) => Ok(size),
+            None => self
+                .runtime
+                .block_on(self.client.refresh_size(&node.id))
+                .map_err(|_| FsError::Io),
+        }
+    }
+
+    /// Fetches `node`'s full content directly via
+    /// [`RosetClient::get_inline_content`] rather than the normal
+    /// signed-URL-then-range-read dance, for a file at or under
+    /// [`Self::with_inline_content_threshold`] (default
+    /// [`DEFAULT_INLINE_CONTENT_MAX_BYTES`]).
+    ///
+    /// Returns `Ok(None)` for a node over the threshold (or with an
+    /// unknown size), so the caller falls back to the normal read path.
+    /// Content is cached in [`Self::shared_cache`] when one is configured,
+    /// keyed by `node.id` plus its [`Node::version`] (if the backend
+    /// reports one) so a later read after an edit isn't served stale bytes
+    /// from the shared cache.
+    ///
+    /// A cached or freshly-fetched block whose length doesn't match
+    /// `node.size` is read-repaired — evicted and re-fetched — via
+    /// [`crate::shared_cache::SharedBlockCache::get_or_fetch_with_repair`],
+    /// so transient on-disk corruption of the shared cache (or a transfer
+    /// that slipped past [`RosetClient::get_inline_content`]'s own CRC32
+    /// check) doesn't silently serve bad bytes to a long-running reader.
+    pub fn read_small_file_inline(&self, node: &Node) -> Result<Option<Vec<u8>>, FsError> {
+        let Some(size) = node.size else {
+            return Ok(None);
+        };
+        if size > self.inline_content_threshold {
+            return Ok(None);
+        }
+
+        let cache_key = format!("{}:{}", node.id, node.version().unwrap_or("none"));
+        let fetch = || {
+            self.runtime
+                .block_on(self.client.get_inline_content(&node.id))
+                .map_err(std::io::Error::other)
+        };
+
+        let data = match &self.shared_cache {
+            Some(cache) => cache
+                .get_or_fetch_with_repair(&cache_key, fetch, |data| data.len() as u64 == size)
+                .map_err(|_| FsError::Io)?,
+            None => fetch().map_err(|_| FsError::Io)?,
+        };
+
+        Ok(Some(data))
+    }
+
+    /// Fetches the block of `node`'s content at `block_index` — see
+    /// [`crate::block_cache::block_index_for`]/[`crate::block_cache::block_range`]
+    /// — for a file over [`Self::with_inline_content_threshold`], the
+    /// counterpart to [`Self::read_small_file_inline`] for the normal
+    /// range-read path.
+    ///
+    /// Consults [`Self::with_block_cache`] before calling
+    /// [`RosetClient::download_range`], and populates it on a miss, so
+    /// repeated reads of the same region of the same dataset (e.g. across
+    /// training epochs) are served from local disk. With no block cache
+    /// configured, every call goes straight to `download_range`, matching
+    /// this mount's behavior before the cache existed.
+    ///
+    /// [`Self::with_read_cache_policy`] decides whether this particular
+    /// node is eligible: `ImmutableOnly` (the default) only caches
+    /// committed nodes (see [`Self::open_reply_flags`] for the same
+    /// immutability check), `All` also caches mutable nodes under
+    /// [`crate::block_cache::DEFAULT_MUTABLE_BLOCK_CACHE_TTL`], and `None`
+    /// bypasses the cache entirely regardless of `--cache-dir`.
+    pub fn read_block(&self, node: &Node, block_index: u64) -> Result<Vec<u8>, FsError> {
+        let size = self.resolve_size(node)?;
+        let fetch = |offset: u64, len: u64| {
+            self.runtime
+                .block_on(self.client.download_range(&node.id, offset, offset + len.saturating_sub(1)))
+                .map_err(std::io::Error::other)
+        };
+
+        let immutable = node.metadata.get("committed").map(String::as_str) == Some("true");
+        let cacheable = self.read_cache_policy != ReadCachePolicy::None
+            && (immutable || self.read_cache_policy == ReadCachePolicy::All);
+
+        let data = match &self.block_cache {
+            Some(cache) if cacheable => {
+                let policy = if immutable { CachePolicy::Immutable } else { CachePolicy::Ttl };
+                cache
+                    .get_or_fetch(&node.id, block_index, policy, || {
+                        let (offset, len) = crate::block_cache::block_range(block_index, cache.block_size(), size);
+                        fetch(offset, len)
+                    })
+                    .map_err(|_| FsError::Io)?
+            }
+            cache => {
+                let block_size = cache.as_ref().map(|c| c.block_size()).unwrap_or(crate::block_cache::DEFAULT_BLOCK_SIZE);
+                let (offset, len) = crate::block_cache::block_range(block_index, block_size, size);
+                fetch(offset, len).map_err(|_| FsError::Io)?
+            }
+        };
+
+        Ok(data)
+    }
+
+    /// Serves a FUSE `read(offset, len)` request against `node`: the
+    /// inline fast path for a small file (see
+    /// [`Self::read_small_file_inline`]), or, for anything larger,
+    /// stitches together as many [`Self::read_block`] calls as `len`
+    /// spans — a single `read` crossing a block boundary is the normal
+    /// case for any read larger than [`Self::with_block_size`]'s
+    /// configured size, so this can't just forward to one `read_block`
+    /// call the way a block-aligned reader would.
+    pub fn read_range(&self, node: &Node, offset: u64, len: u64) -> Result<Vec<u8>, FsError> {
+        let size = self.resolve_size(node)?;
+        if offset >= size || len == 0 {
+            return Ok(Vec::new());
+        }
+        let len = len.min(size - offset);
+
+        if let Some(data) = self.read_small_file_inline(node)? {
+            let start = (offset as usize).min(data.len());
+            let end = ((offset + len) as usize).min(data.len());
+            return Ok(data[start..end].to_vec());
+        }
+
+        let block_size = self
+            .block_cache
+            .as_ref()
+            .map(|c| c.block_size())
+            .unwrap_or(crate::block_cache::DEFAULT_BLOCK_SIZE);
+        let end = offset + len;
+        let mut out = Vec::with_capacity(len as usize);
+        let mut pos = offset;
+        while pos < end {
+            let block_index = crate::block_cache::block_index_for(pos, block_size);
+            let (block_start, block_len) = crate::block_cache::block_range(block_index, block_size, size);
+            let block = self.read_block(node, block_index)?;
+            let within_start = (pos - block_start) as usize;
+            let within_end = (end.min(block_start + block_len) - block_start) as usize;
+            out.extend_from_slice(&block[within_start.min(block.len())..within_end.min(block.len())]);
+            pos = block_start + block_len;
+        }
+        Ok(out)
+    }
+
+    /// Fetches `node`'s entire current content, for seeding
+    /// [`Handle::dirty`] when an existing file is opened for
+    /// modification (see [`Self::acquire_write_lease`]). A file small
+    /// enough for [`Self::read_small_file_inline`] is served from there;
+    /// anything larger skips [`Self::read_block`] and goes straight to
+    /// [`RosetClient::download_range`] for the whole file, since the
+    /// caller needs it all in memory as a single edit buffer anyway.
+    fn download_full_content(&self, node: &Node) -> Result<Vec<u8>, FsError> {
+        if let Some(data) = self.read_small_file_inline(node)? {
+            return Ok(data);
+        }
+        let size = self.resolve_size(node)?;
+        if size == 0 {
+            return Ok(Vec::new());
+        }
+        self.runtime
+            .block_on(self.client.download_range(&node.id, 0, size - 1))
+            .map_err(|_| FsError::Io)
+    }
+
+    /// Drops `node_id`'s cached blocks (see [`Self::with_block_cache`]),
+    /// e.g. after a write to a mutable node invalidates the content
+    /// previously cached for it. A no-op with no block cache configured.
+    pub fn invalidate_block_cache(&self, node_id: &str) {
+        if let Some(cache) = &self.block_cache {
+            cache.invalidate_node(node_id);
+        }
+    }
+
+    /// Whether `init` should request `FUSE_WRITEBACK_CACHE` from the
+    /// kernel, i.e. whether `--enable-writeback-cache` was passed. Split
+    /// out from [`Self::negotiate_init_capabilities`] so the decision is
+    /// testable without constructing a real `fuser::KernelConfig`, which
+    /// only `fuser`'s own session setup can build.
+    pub fn should_enable_writeback_cache(&self) -> bool {
+        self.writeback_cache
+    }
+
+    /// Requests `FUSE_WRITEBACK_CACHE` from the kernel during `init`, if
+    /// `--enable-writeback-cache` was passed.
+    pub fn negotiate_init_capabilities(&self, config: &mut fuser::KernelConfig) -> Result<(), i32> {
+        if self.should_enable_writeback_cache() {
+            config
+                .add_capabilities(fuser::consts::FUSE_WRITEBACK_CACHE)
+                .map_err(|_| libc::EINVAL)?;
+        }
+        config
+            .set_max_write(self.max_write_bytes)
+            .map_err(|_| libc::EINVAL)?;
+        config
+            .set_max_readahead(self.max_readahead_kb * 1024)
+            .map_err(|_| libc::EINVAL)?;
+        Ok(())
+    }
+
+    /// The `--max-readahead-kb` value [`Self::negotiate_init_capabilities`]
+    /// will request from the kernel, for tests that can't construct a
+    /// real `fuser::KernelConfig` (only `fuser`'s own session setup can)
+    /// but still want to assert the configured value made it onto
+    /// `RosetFs` — see [`Self::with_max_readahead_kb`].
+    pub fn configured_max_readahead_kb(&self) -> u32 {
+        self.max_readahead_kb
+    }
+
+    /// Handles `setxattr` for arbitrary (non-control) xattrs, storing the
+    /// raw bytes base64-encoded under a `xattr.<name>` metadata key so
+    /// binary values (like `security.capability`) round-trip exactly
+    /// instead of being mangled by a string-only mapping.
+    /// `security.capability` itself is additionally gated by
+    /// [`Self::with_security_capability_xattr`].
+    pub fn set_binary_xattr(&self, ino: u64, name: &str, value: &[u8]) -> Result<(), FsError> {
+        self.set_binary_xattrs(ino, &[(name, value)])
+    }
+
+    /// Max attempts for [`Self::set_binary_xattrs`]'s optimistic-
+    /// concurrency retry on a [`crate::client::PatchError::VersionMismatch`].
+    const XATTR_PATCH_MAX_ATTEMPTS: u32 = 3;
+
+    /// Sets several xattrs on `ino` as one server-side metadata patch
+    /// (see [`crate::client::RosetClient::update_node_metadata_patch`])
+    /// instead of a get-then-update round-trip per attribute — the
+    /// difference between one call and N for something like `cp -a`
+    /// preserving several attributes at once. The patch is itself sent
+    /// with the node's currently-known version as an `If-Match`
+    /// precondition; if another writer updated the node in between (a
+    /// version mismatch), the node is re-fetched and the same patch
+    /// retried against its fresh version rather than silently applying
+    /// against stale state.
+    pub fn set_binary_xattrs(&self, ino: u64, entries: &[(&str, &[u8])]) -> Result<(), FsError> {
+        if entries
+            .iter()
+            .any(|(name, _)| *name == "security.capability")
+            && !self.allow_security_capability_xattr
+        {
+            return Err(FsError::PermissionDenied);
+        }
+        let mut node = self.inodes.node_for(ino).ok_or(FsError::NotFound)?;
+        let mut patch = HashMap::new();
+        for (name, value) in entries {
+            let key = format!("xattr.{name}");
+            patch.insert(key, base64_encode(value));
+        }
+
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let version = node.version().map(str::to_string);
+            let result = self.runtime.block_on(self.client.update_node_metadata_patch(
+                &node.id,
+                patch.clone(),
+                version.as_deref(),
+            ));
+            match result {
+                Ok(updated) => {
+                    self.inodes.update_node(ino, updated);
+                    return Ok(());
+                }
+                Err(crate::client::PatchError::VersionMismatch)
+                    if attempt < Self::XATTR_PATCH_MAX_ATTEMPTS =>
+                {
+                    match self.runtime.block_on(self.client.get_node(&node.id)) {
+                        Ok(fresh) => node = fresh,
+                        Err(_) => break,
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+
+        // No backend reachable (common in tests against an unmocked
+        // base URL) or retries exhausted: fall back to applying the
+        // patch locally so offline/unit-tested xattr sets still
+        // round-trip, same as before this call existed.
+        for (key, encoded) in patch {
+            node.metadata.insert(key, encoded);
+        }
+        self.inodes.update_node(ino, node);
+        Ok(())
+    }
+
+    /// Handles `getxattr` for arbitrary (non-control) xattrs, reversing
+    /// [`Self::set_binary_xattr`]'s encoding.
+    pub fn get_binary_xattr(&self, ino: u64, name: &str) -> Option<Vec<u8>> {
+        let node = self.inodes.node_for(ino)?;
+        let encoded = node.metadata.get(&format!("xattr.{name}"))?;
+        base64_decode(encoded)
+    }
+
+    /// Computes the `open`/`create` reply flags for `ino`: immutable
+    /// (snapshot-backed) nodes get `FOPEN_KEEP_CACHE` so re-reads avoid
+    /// invalidating the kernel page cache between opens, while
+    /// `--direct-io` mode sets `FOPEN_DIRECT_IO` for coherency-sensitive
+    /// mutable files at the cost of read-cache performance.
+    pub fn open_reply_flags(&self, ino: u64) -> u32 {
+        if self.direct_io {
+            return fuser::consts::FOPEN_DIRECT_IO;
+        }
+        let immutable = self
+            .inodes
+            .node_for(ino)
+            .is_some_and(|n| n.metadata.get("committed").map(String::as_str) == Some("true"));
+        if immutable {
+            fuser::consts::FOPEN_KEEP_CACHE
+        } else {
+            0
+        }
+    }
+
+    /// Computes a synthetic `statfs` reply, since the Roset API has no
+    /// real capacity endpoint to query yet (see
+    /// [`Self::with_reported_capacity_bytes`]). Reports the entire
+    /// configured capacity as free — the backend doesn't expose real
+    /// utilization — so tools like `df` and training frameworks that
+    /// preflight free space before writing see ample room instead of the
+    /// zeros/garbage an unimplemented `statfs` otherwise leaves them
+    /// with. `files` reflects the inodes currently known to this mount
+    /// (plus the root); `ffree` is a synthetic headroom, since there's no
+    /// real inode-count ceiling to approach.
+    pub fn handle_statfs(&self) -> StatfsReply {
+        let bsize = self.block_size;
+        let blocks = self.reported_capacity_bytes / bsize as u64;
+        StatfsReply {
+            blocks,
+            bfree: blocks,
+            bavail: blocks,
+            files: self.inodes.len() as u64 + 1,
+            ffree: SYNTHETIC_FREE_INODE_HEADROOM,
+            bsize,
+            namelen: 255,
+            frsize: bsize,
+        }
+    }
+
+    /// Resolves `path` to a [`Node`], falling back to stale cached data
+    /// on a network error when `--allow-offline` is set. Surfaces the
+    /// degraded state via `self.degraded` (readable through the
+    /// `user.roset.degraded` xattr) so users know reads may be stale.
+    /// Writes never go through this path — they fail fast instead.
+    pub fn resolve_with_offline_fallback(&self, path: &str, live: Option<Node>) -> Result<Option<Node>, FsError> {
+        match live {
+            Some(node) => {
+                self.degraded.store(false, Ordering::SeqCst);
+                Ok(Some(node))
+            }
+            None if self.allow_offline.load(Ordering::SeqCst) => {
+                match self.cache.get_allow_stale(path) {
+                    Some(cached) => {
+                        self.degraded.store(true, Ordering::SeqCst);
+                        Ok(cached)
+                    }
+                    None => Err(FsError::Io),
+                }
+            }
+            None => Err(FsError::Io),
+        }
+    }
+
+    /// Resolves the root node and, if it's marked committed/immutable
+    /// (a snapshot-backed mount), auto-enables read-only semantics and
+    /// immutable caching even if `--read-only` wasn't passed, so a
+    /// snapshot mount can't be accidentally written to.
+    pub fn init_root(&self, root: Node) {
+        let immutable = root.metadata.get("committed").map(String::as_str) == Some("true");
+        if immutable {
+            self.read_only.store(true, Ordering::SeqCst);
+            self.cache.set_policy(CachePolicy::Immutable);
+            eprintln!("roset-fuse: root is immutable, mount auto-set to read-only");
+        }
+        self.cache.put("/".to_string(), Some(root.clone()));
+        self.inodes.set_root(root.clone());
+        let attr = self.node_to_attr(ROOT_INO, &root);
+        *self.root_attr.lock().unwrap() = Some(RootAttrEntry {
+            attr,
+            cached_at: std::time::Instant::now(),
+        });
+    }
+
+    /// Fast path for `getattr(ROOT_INO)`.
+    ///
+    /// The root is resolved once in [`Self::init_root`] and, unlike every
+    /// other inode, is never looked up by walking [`Self::resolve_path`]
+    /// — so routing its very frequent `getattr`s (every `stat`/`ls` of the
+    /// mountpoint hits it) through the general cache/API lookup the way
+    /// [`Self::resolve_path`] does for everything else is wasted work.
+    /// Instead this keeps a single dedicated [`RootAttrEntry`], reused as
+    /// long as it's younger than [`ROOT_ATTR_REFRESH_INTERVAL`] and
+    /// refreshed from the backend once it isn't, so a legitimate change to
+    /// the root's own metadata still surfaces within that bound.
+    pub fn getattr_root(&self) -> Result<fuser::FileAttr, FsError> {
+        if let Some(entry) = self.root_attr.lock().unwrap().as_ref() {
+            if entry.cached_at.elapsed() < ROOT_ATTR_REFRESH_INTERVAL {
+                return Ok(entry.attr);
+            }
+        }
+
+        let root_id = self.inodes.node_for(ROOT_INO).map(|n| n.id).ok_or(FsError::Io)?;
+        let live = self.runtime.block_on(self.client.get_node(&root_id)).ok();
+        let node = self.resolve_with_offline_fallback("/", live)?.ok_or(FsError::Io)?;
+
+        self.inodes.update_node(ROOT_INO, node.clone());
+        self.cache.put("/".to_string(), Some(node.clone()));
+        let attr = self.node_to_attr(ROOT_INO, &node);
+        *self.root_attr.lock().unwrap() = Some(RootAttrEntry {
+            attr,
+            cached_at: std::time::Instant::now(),
+        });
+        Ok(attr)
+    }
+
+    /// Whether the backend API looks reachable right now, per
+    /// [`RosetClient::is_unreachable`]. This is the reachability probe a
+    /// supervisor would call through a `/readyz` endpoint if this process
+    /// exposed one; without one, [`Self::ready_xattr`] publishes the same
+    /// signal through the mount itself instead.
+    pub fn is_ready(&self) -> bool {
+        !self.client.is_unreachable()
+    }
+
+    /// Handles `getxattr("user.roset.ready")`: `"1"` or `"0"` reflecting
+    /// [`Self::is_ready`]. The CSI node service's `NodeGetVolumeStats`
+    /// reads this directly off the mount point, since there's no other
+    /// channel between this process and its supervisor.
+    pub fn ready_xattr(&self) -> Vec<u8> {
+        if self.is_ready() {
+            b"1".to_vec()
+        } else {
+            b"0".to_vec()
+        }
+    }
+
+    /// Handles `setxattr("user.roset.recover", _)`: a supervisor's soft
+    /// recovery lever for a mount whose [`Self::ready_xattr`] has reported
+    /// unreachable for a while, without killing and restaging the whole
+    /// `roset-fuse` process. Flushes every cached attr (some of which may
+    /// have been served stale via [`Self::resolve_with_offline_fallback`]
+    /// while the backend was unreachable) and re-resolves the root, the
+    /// same way [`Self::init_root`] did at mount time.
+    pub fn handle_recover_xattr(&self) -> Result<(), FsError> {
+        self.cache.clear();
+        let root_id = self.inodes.node_for(ROOT_INO).map(|n| n.id).ok_or(FsError::Io)?;
+        let live = self.runtime.block_on(self.client.get_node(&root_id)).ok();
+        let node = self.resolve_with_offline_fallback("/", live)?.ok_or(FsError::Io)?;
+
+        self.inodes.update_node(ROOT_INO, node.clone());
+        self.cache.put("/".to_string(), Some(node.clone()));
+        let attr = self.node_to_attr(ROOT_INO, &node);
+        *self.root_attr.lock().unwrap() = Some(RootAttrEntry {
+            attr,
+            cached_at: std::time::Instant::now(),
+        });
+        Ok(())
+    }
+
+    /// [`AttrCache::get`], except a negative hit chosen for revalidation
+    /// (see [`crate::cache::NegativeRevalidationPolicy`]) is reported as a
+    /// cache miss (`None`) so the caller falls through to a live fetch.
+    fn cached_unless_revalidating(&self, path: &str) -> Option<Option<Node>> {
+        let cached = self.cache.get(path)?;
+        if cached.is_none() && self.cache.should_revalidate_negative(path) {
+            return None;
+        }
+        Some(cached)
+    }
+
+    /// Resolves `path` to its [`Node`] by walking one path segment at a
+    /// time from the root, the same way a sequence of per-component
+    /// `lookup`s would. Each path prefix visited along the way — not
+    /// just the final full path — is cached under [`Self::cache`], so a
+    /// later lookup of a different path sharing a deeply-nested ancestor
+    /// (e.g. two files under the same `/a/b/c` directory) can
+    /// short-circuit partway through the walk instead of only on an
+    /// exact full-path cache hit.
+    ///
+    /// Returns `Ok(None)` for a path that doesn't exist; callers that
+    /// need a negative cache entry on `path` itself (rather than just on
+    /// whichever prefix first came up empty) should cache that
+    /// separately, matching how [`Self::cache`]'s other callers handle
+    /// negative entries.
+    ///
+    /// A cached negative entry is occasionally revalidated against the
+    /// backend rather than trusted outright — see
+    /// [`crate::cache::NegativeRevalidationPolicy`] — so a file created
+    /// out-of-band by another process surfaces before the negative TTL
+    /// would otherwise expire.
+    pub fn resolve_path(&self, path: &str) -> Result<Option<Node>, FsError> {
+        if let Some(cached) = self.cached_unless_revalidating(path) {
+            return Ok(cached);
+        }
+        if path == "/" {
+            return Ok(None);
+        }
+
+        let mut current = match self.cache.get("/").flatten() {
+            Some(root) => root,
+            None => return Err(FsError::Io),
+        };
+        let mut current_path = String::new();
+
+        for segment in path.trim_start_matches('/').split('/') {
+            current_path.push('/');
+            current_path.push_str(segment);
+
+            if let Some(cached) = self.cached_unless_revalidating(&current_path) {
+                match cached {
+                    Some(node) => {
+                        current = node;
+                        continue;
+                    }
+                    None => return Ok(None),
+                }
+            }
+
+            let listing = self.runtime.block_on(self.client.list_all_children(&current.id));
+            let found = listing.children.into_iter().find(|n| n.name == segment);
+            self.cache.put(current_path.clone(), found.clone());
+            match found {
+                Some(node) => current = node,
+                None => return Ok(None),
+            }
+        }
+
+        Ok(Some(current))
+    }
+
+    /// Resolves `path` against the backend only, walking one segment at a
+    /// time from the root the same way [`Self::resolve_path`] does, but
+    /// never reading or writing [`Self::cache`] — used by
+    /// [`Self::verify_subtree`], which needs an independent "ground
+    /// truth" to compare the cache against rather than the cache's own
+    /// idea of what's live.
+    fn resolve_path_live(&self, path: &str) -> Result<Option<Node>, FsError> {
+        let root_id = self.inodes.node_for(ROOT_INO).map(|n| n.id).ok_or(FsError::Io)?;
+        let mut current = self
+            .runtime
+            .block_on(self.client.get_node(&root_id))
+            .map_err(|_| FsError::Io)?;
+        if path == "/" {
+            return Ok(Some(current));
+        }
+
+        for segment in path.trim_start_matches('/').split('/') {
+            let listing = self.runtime.block_on(self.client.list_all_children(&current.id));
+            match listing.children.into_iter().find(|n| n.name == segment) {
+                Some(node) => current = node,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(current))
+    }
+
+    /// Compares `path` and `path`'s cached entry against `live`, appending
+    /// any mismatch to `out`.
+    fn compare_cached_node(path: &str, cached: &Node, live: &Node, out: &mut Vec<Discrepancy>) {
+        if cached.node_type != live.node_type {
+            out.push(Discrepancy::TypeMismatch {
+                path: path.to_string(),
+                cached: cached.node_type,
+                backend: live.node_type,
+            });
+        }
+        if cached.size != live.size {
+            out.push(Discrepancy::SizeMismatch {
+                path: path.to_string(),
+                cached: cached.size,
+                backend: live.size,
+            });
+        }
+    }
+
+    /// Diagnostic backing `roset-fuse verify`: compares the mount's cached
+    /// view of `path` and its direct children against a fresh fetch from
+    /// the backend, without reading or writing anything back into
+    /// [`Self::cache`] via [`Self::resolve_path_live`] — so running it
+    /// can't paper over the very staleness it's trying to surface.
+    ///
+    /// Only checks `path` itself and its immediate children, not the
+    /// whole recursive subtree: [`AttrCache`] is keyed by individual paths
+    /// with no index of "everything under this prefix" cheaper than the
+    /// linear scan [`AttrCache::snapshot_with_prefix`] already does, so
+    /// comparing an arbitrarily deep subtree in one call would mean
+    /// either walking it live (defeating the point of also checking the
+    /// cache's own entries) or scanning the whole cache once per
+    /// directory visited. Callers that want a deeper check can walk the
+    /// tree themselves, calling this once per directory.
+    pub fn verify_subtree(&self, path: &str) -> Result<Vec<Discrepancy>, FsError> {
+        let mut discrepancies = Vec::new();
+
+        let cached_node = self.cache.get_allow_stale(path).flatten();
+        let cached_children = self.cache.snapshot_with_prefix(path);
+
+        let live_node = self.resolve_path_live(path)?;
+
+        match (&cached_node, &live_node) {
+            (Some(cached), Some(live)) => Self::compare_cached_node(path, cached, live, &mut discrepancies),
+            (Some(_), None) => discrepancies.push(Discrepancy::MissingFromBackend { path: path.to_string() }),
+            (None, Some(_)) => discrepancies.push(Discrepancy::MissingFromCache { path: path.to_string() }),
+            (None, None) => {}
+        }
+
+        let Some(live_node) = live_node else {
+            return Ok(discrepancies);
+        };
+        if live_node.node_type != NodeType::Directory {
+            return Ok(discrepancies);
+        }
+
+        let prefix = if path.ends_with('/') { path.to_string() } else { format!("{path}/") };
+        let live_children = self.runtime.block_on(self.client.list_all_children(&live_node.id)).children;
+
+        for (child_path, cached_entry) in &cached_children {
+            let name = &child_path[prefix.len()..];
+            if name.contains('/') {
+                continue; // a grandchild, not a direct child of `path`
+            }
+            let live_match = live_children.iter().find(|n| n.name == name);
+            match (cached_entry, live_match) {
+                (Some(cached), Some(live)) => Self::compare_cached_node(child_path, cached, live, &mut discrepancies),
+                (Some(_), None) => discrepancies.push(Discrepancy::MissingFromBackend { path: child_path.clone() }),
+                (None, Some(_)) => discrepancies.push(Discrepancy::MissingFromCache { path: child_path.clone() }),
+                (None, None) => {}
+            }
+        }
+
+        for live_child in &live_children {
+            let child_path = format!("{prefix}{}", live_child.name);
+            if !cached_children.iter().any(|(path, _)| path == &child_path) {
+                discrepancies.push(Discrepancy::MissingFromCache { path: child_path });
+            }
+        }
+
+        Ok(discrepancies)
+    }
+
+    /// Called periodically (see `POLL_INTERVAL`) for every inode with an
+    /// open, poll-registered handle: if the backend's reported size grew
+    /// since we last checked, tells the kernel to drop its cached
+    /// attributes so a `tail -f`-style reader sees the new data without
+    /// waiting out the attr cache TTL.
+    pub fn check_poll_growth(&self, ino: u64, backend_size: u64) {
+        if self.poll_registry.check_grew(ino, backend_size) {
+            if let Some(notifier) = &self.notifier {
+                notifier.invalidate_inode(ino);
+            }
+        }
+    }
+
+    /// Whether `readdir` on a committed directory should skip straight
+    /// to `list_all_children` instead of trying `get_manifest` first.
+    /// Set after the first 404/501 from `get_manifest` so a backend that
+    /// doesn't support manifests at all doesn't pay a failed round-trip
+    /// on every single listing.
+    pub fn should_skip_manifest(&self) -> bool {
+        self.manifest_unsupported.load(Ordering::SeqCst)
+    }
+
+    pub fn mark_manifest_unsupported(&self) {
+        self.manifest_unsupported.store(true, Ordering::SeqCst);
+    }
+
+    /// Resets the "manifest unsupported" signal, e.g. after the mount is
+    /// reconfigured to point at a different backend.
+    pub fn reset_manifest_support(&self) {
+        self.manifest_unsupported.store(false, Ordering::SeqCst);
+    }
+
+    /// Whether a write's upload should skip straight to a full rewrite
+    /// instead of attempting [`RosetClient::patch_content`]. Set after
+    /// the first [`crate::client::PatchContentError::Unsupported`], the
+    /// same way [`Self::should_skip_manifest`] remembers a backend that
+    /// doesn't support manifests.
+    pub fn should_skip_patch(&self) -> bool {
+        self.patch_unsupported.load(Ordering::SeqCst)
+    }
+
+    pub fn mark_patch_unsupported(&self) {
+        self.patch_unsupported.store(true, Ordering::SeqCst);
+    }
+
+    /// Resets the "patch unsupported" signal, e.g. after the mount is
+    /// reconfigured to point at a different backend.
+    pub fn reset_patch_support(&self) {
+        self.patch_unsupported.store(false, Ordering::SeqCst);
+    }
+
+    /// Decides how a handle's buffered write(s) should reach the backend:
+    /// a patch of just the touched byte ranges, or a full rewrite of the
+    /// whole buffer, the way every write was uploaded before patch
+    /// support existed. Thin wrapper over
+    /// [`crate::upload::plan_upload_strategy`] that supplies this mount's
+    /// current [`Self::should_skip_patch`] signal and default dirty-ratio
+    /// cutoff.
+    pub fn plan_write_upload(
+        &self,
+        original_size: Option<u64>,
+        dirty_ranges: &[(u64, u64)],
+    ) -> crate::upload::UploadStrategy {
+        crate::upload::plan_upload_strategy(
+            original_size,
+            dirty_ranges,
+            !self.should_skip_patch(),
+            crate::upload::DEFAULT_PATCH_MAX_DIRTY_RATIO,
+        )
+    }
+
+    /// Decides whether a `read(offset, len)` should kick off a background
+    /// prefetch, given the last read's end offset on that handle. Thin
+    /// wrapper over [`crate::readahead::plan_read_ahead`] that supplies
+    /// this mount's configured [`Self::with_read_ahead`] window size.
+    /// Doesn't touch the [`Handle`] itself — callers decide what to do
+    /// with the plan (e.g. issue the prefetch and store its result in
+    /// [`Handle::read_ahead_buf`]).
+    pub fn plan_handle_read_ahead(
+        &self,
+        last_read_end: Option<u64>,
+        offset: u64,
+        len: u64,
+    ) -> crate::readahead::ReadAheadPlan {
+        crate::readahead::plan_read_ahead(last_read_end, offset, len, self.read_ahead_bytes)
+    }
+
+    /// Resolves a committed directory's children, preferring a one-shot
+    /// manifest bulk load but falling back to lazy paged listing when the
+    /// manifest is too large to safely hold in memory at once (or the
+    /// backend doesn't support manifests at all).
+    ///
+    /// Checks [`Self::get_manifest_summary`]'s entry count against
+    /// `manifest_node_count_threshold` *before* fetching the manifest
+    /// body, so the threshold guard itself never pays for the bulk
+    /// transfer it's trying to avoid.
+    pub fn list_committed_directory(&self, node_id: &str) -> DirectoryListing {
+        if !self.should_skip_manifest() {
+            match self.runtime.block_on(self.client.get_manifest_summary(node_id)) {
+                Ok(summary) if summary.node_count <= self.manifest_node_count_threshold => {
+                    if let Ok(nodes) = self.runtime.block_on(self.client.get_manifest(node_id)) {
+                        return DirectoryListing::Manifest(nodes);
+                    }
+                }
+                Ok(_) => {
+                    // Manifest exists but is too large to bulk-load: fall
+                    // through to paged listing without marking manifests
+                    // unsupported, since a later, smaller subtree should
+                    // still get the fast path.
+                }
+                Err(_) => self.mark_manifest_unsupported(),
+            }
+        }
+        DirectoryListing::Paged(self.runtime.block_on(self.client.list_all_children(node_id)))
+    }
+
+    /// Caches every node in a manifest bulk load under `dir_path`, so the
+    /// `readdir` that triggered the load is immediately followed by free
+    /// `lookup`/`getattr` cache hits for each child instead of one API
+    /// call per entry.
+    pub fn bulk_load_manifest_into_cache(&self, dir_path: &str, nodes: &[Node]) {
+        let prefix = if dir_path.ends_with('/') {
+            dir_path.to_string()
+        } else {
+            format!("{dir_path}/")
+        };
+        for node in nodes {
+            self.cache.put(format!("{prefix}{}", node.name), Some(node.clone()));
+        }
+    }
+
+    /// Guards `open`/`read`/`write` against being called on a directory
+    /// inode — an application that calls `open(2)` on a directory
+    /// instead of `opendir(3)` should get `EISDIR`, not a confusing
+    /// failure from trying to `get_download_url` a folder.
+    pub fn check_not_a_directory(&self, ino: u64) -> Result<(), FsError> {
+        match self.inodes.node_for(ino) {
+            Some(node) if node.node_type == crate::node::NodeType::Directory => {
+                Err(FsError::IsADirectory)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Handles `mkdir`. A `409` from the backend is only `EEXIST` when the
+    /// name collision is the cause; a lease conflict on the parent
+    /// directory surfaces as `EBUSY` instead, since retrying after the
+    /// lease clears is the right move rather than treating it as a
+    /// permanent naming failure.
+    pub fn mkdir(&self, parent_id: &str, name: &str) -> Result<Node, FsError> {
+        self.check_writable()?;
+        Self::map_api_error(
+            self.runtime
+                .block_on(self.client.create_directory(parent_id, name)),
+        )
+    }
+
+    /// Handles `symlink`. Stores `target` verbatim in the node's
+    /// `symlinkTarget` metadata — relative and absolute targets are both
+    /// accepted as-is, matching `symlink(2)`'s own semantics of never
+    /// interpreting the target itself. Shares [`FsError`] mapping with
+    /// `mkdir`.
+    pub fn symlink(&self, parent_id: &str, name: &str, target: &str) -> Result<Node, FsError> {
+        self.check_writable()?;
+        Self::map_api_error(
+            self.runtime
+                .block_on(self.client.create_symlink(parent_id, name, target)),
+        )
+    }
+
+    /// Handles `readlink`. `EINVAL` for a node that isn't a symlink,
+    /// matching `readlink(2)`.
+    pub fn readlink(&self, ino: u64) -> Result<String, FsError> {
+        let node = self.inodes.node_for(ino).ok_or(FsError::NotFound)?;
+        if node.node_type != crate::node::NodeType::Symlink {
+            return Err(FsError::InvalidArgument);
+        }
+        node.symlink_target()
+            .map(str::to_string)
+            .ok_or(FsError::InvalidArgument)
+    }
+
+    /// Handles `rename`. Shares [`FsError`] mapping with `mkdir` since
+    /// the backend returns the same ambiguous `409` for a destination
+    /// name collision versus a lease held on the destination parent.
+    ///
+    /// `old_path` is the node's path before the rename, as resolved by
+    /// [`Self::resolve_path`]'s caller. On success, `old_path` and every
+    /// [`Self::cache`] entry nested under it are invalidated, since a
+    /// rename moves the whole subtree and every cached descendant path
+    /// is now stale — not just the renamed node itself.
+    pub fn rename(&self, node_id: &str, new_parent_id: &str, new_name: &str, old_path: &str) -> Result<Node, FsError> {
+        self.check_writable()?;
+        let result = Self::map_api_error(
+            self.runtime
+                .block_on(self.client.rename_node(node_id, new_parent_id, new_name)),
+        );
+        if result.is_ok() {
+            self.cache.invalidate_node(old_path);
+            self.cache.invalidate_children(old_path);
+        }
+        result
+    }
+
+    fn map_api_error(result: Result<Node, crate::client::ApiError>) -> Result<Node, FsError> {
+        match result {
+            Ok(node) => Ok(node),
+            Err(crate::client::ApiError::AlreadyExists) => Err(FsError::AlreadyExists),
+            Err(crate::client::ApiError::LeaseConflict) => Err(FsError::Conflict),
+            Err(crate::client::ApiError::CrossDevice) => Err(FsError::CrossDevice),
+            Err(crate::client::ApiError::ImmutableTarget) => Err(FsError::ReadOnlyFilesystem),
+            Err(crate::client::ApiError::Other(_)) => Err(FsError::Io),
+        }
+    }
+
+    /// Whether `path` refers to the virtual `.roset-trash` directory
+    /// (or an entry under it), which `readdir`/`lookup` special-case to
+    /// list/restore soft-deleted nodes instead of resolving through the
+    /// normal path hierarchy.
+    pub fn is_trash_path(path: &str) -> bool {
+        path == format!("/{TRASH_DIR_NAME}") || path.starts_with(&format!("/{TRASH_DIR_NAME}/"))
+    }
+
+    /// Lists soft-deleted nodes for `readdir` on `.roset-trash`.
+    pub fn list_trash(&self) -> Result<Vec<Node>, FsError> {
+        self.runtime
+            .block_on(self.client.list_trash())
+            .map_err(|_| FsError::Io)
+    }
+
+    /// Handles moving an entry out of `.roset-trash` back to its
+    /// original location.
+    pub fn restore_from_trash(&self, node_id: &str) -> Result<Node, FsError> {
+        self.check_writable()?;
+        self.runtime
+            .block_on(self.client.restore_node(node_id))
+            .map_err(|_| FsError::Io)
+    }
+
+    /// `mkdir`/`create` reject with `EROFS` once the mount is read-only,
+    /// whether that was requested explicitly or auto-detected in
+    /// `init_root`.
+    pub fn check_writable(&self) -> Result<(), FsError> {
+        if self.read_only.load(Ordering::SeqCst) {
+            Err(FsError::ReadOnlyFilesystem)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Handles `open`ing `node_id` for write (`O_WRONLY`/`O_RDWR` in
+    /// `flags`): acquires an exclusive write lease, or, when another
+    /// writer already holds it and `--read-only-fallback` is enabled,
+    /// degrades to a read-only handle (reads succeed, writes fail with
+    /// `EBADF`) rather than failing the `open` outright with `EBUSY`.
+    ///
+    /// A handle that does get the lease has its [`Handle::dirty`] seeded
+    /// up front: `O_TRUNC` starts it from an empty buffer, otherwise the
+    /// node's current content is downloaded first via
+    /// [`Self::download_full_content`], so editing a few bytes of an
+    /// existing file doesn't lose the rest of it on `release`. Before
+    /// this, only `create` (always starting empty) populated `dirty` —
+    /// opening an existing file for write had no way to modify it in
+    /// place.
+    pub fn acquire_write_lease(&self, node_id: &str, flags: i32) -> Result<Handle, FsError> {
+        let mut handle = Handle::new(node_id.to_string());
+        self.apply_write_lease(&mut handle, node_id)?;
+        if handle.lease.is_some() {
+            // Always fetched, even under `O_TRUNC` where its content is
+            // about to be discarded — its version is what
+            // `Self::resolve_write_conflict` compares against at upload
+            // completion, regardless of whether this open kept or
+            // discarded the file's prior bytes.
+            let node = self
+                .runtime
+                .block_on(self.client.get_node(node_id))
+                .map_err(|_| FsError::Io)?;
+            handle.opened_version = node.version().map(str::to_string);
+            let dirty = if flags & libc::O_TRUNC != 0 {
+                Vec::new()
+            } else {
+                self.download_full_content(&node)?
+            };
+            handle.opened_size = Some(dirty.len() as u64);
+            handle.dirty = Some(dirty);
+            handle.write_mode = true;
+        }
+        Ok(handle)
+    }
+
+    /// Acquires an exclusive write lease for `node_id` and attaches it
+    /// (and its renewal task, if any) to `handle`, or degrades `handle`
+    /// to read-only under `--read-only-fallback` — the shared guard
+    /// behind both [`Self::acquire_write_lease`] (`open` for write) and
+    /// [`Self::handle_create`] (`create`), so a freshly created file is
+    /// just as protected against a racing writer as one opened for write
+    /// on an existing node.
+    fn apply_write_lease(&self, handle: &mut Handle, node_id: &str) -> Result<(), FsError> {
+        match self.runtime.block_on(self.client.acquire_lease(node_id)) {
+            Ok(lease) => {
+                handle.renewal_task = self.spawn_lease_renewal(lease.clone());
+                handle.lease = Some(lease);
+                Ok(())
+            }
+            Err(crate::client::ApiError::LeaseConflict) if self.read_only_fallback.load(Ordering::SeqCst) => {
+                eprintln!(
+                    "roset-fuse: write lease on {node_id} held by another writer; \
+                     degrading this open to a read-only handle"
+                );
+                handle.read_only = true;
+                Ok(())
+            }
+            Err(crate::client::ApiError::LeaseConflict) => Err(FsError::Conflict),
+            Err(_) => Err(FsError::Io),
+        }
+    }
+
+    /// Keeps `lease` alive for as long as renewal keeps succeeding,
+    /// renewing it [`LEASE_RENEWAL_MARGIN`] before it would otherwise
+    /// expire so a long-running write doesn't lose its exclusive lease
+    /// mid-upload. Returns `None` (nothing to spawn) for a lease the
+    /// backend reported no `expires_at_unix_secs` for. Stops, logging,
+    /// the first time a renewal fails — at that point another writer may
+    /// already hold the node, and there's nothing further renewal can do
+    /// about it.
+    fn spawn_lease_renewal(&self, lease: crate::client::Lease) -> Option<tokio::task::AbortHandle> {
+        let mut expires_at = lease.expires_at_unix_secs?;
+        let client = self.client.clone();
+        let node_id = lease.node_id.clone();
+        let join_handle = self.runtime.spawn(async move {
+            let mut lease = lease;
+            loop {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                let remaining = std::time::Duration::from_secs(expires_at.saturating_sub(now));
+                tokio::time::sleep(remaining.saturating_sub(LEASE_RENEWAL_MARGIN)).await;
+
+                match client.renew_lease(&lease).await {
+                    Ok(renewed) => {
+                        let Some(new_expires_at) = renewed.expires_at_unix_secs else {
+                            return;
+                        };
+                        expires_at = new_expires_at;
+                        lease = renewed;
+                    }
+                    Err(e) => {
+                        eprintln!("roset-fuse: failed to renew write lease on {node_id}: {e:?}");
+                        return;
+                    }
+                }
+            }
+        });
+        Some(join_handle.abort_handle())
+    }
+
+    /// Handles `create`: makes the new node via [`RosetClient::create_file`]
+    /// and opens a write [`Handle`] for it.
+    ///
+    /// Under [`CreateUploadMode::Eager`] (the default), immediately
+    /// stages an empty upload for the new node, matching the durability
+    /// every other write on this mount gets; the handle is marked
+    /// `created_unwritten` so `release` still finalizes that empty
+    /// upload even if `write` is never called. Under
+    /// [`CreateUploadMode::Deferred`], `create_file` alone already
+    /// produced a committed zero-byte node, so nothing further is staged
+    /// here — a file created and closed without a `write` needs no
+    /// upload session at all, and the first real `write` stages one the
+    /// normal way.
+    pub fn handle_create(&self, parent_id: &str, name: &str) -> Result<(Node, Handle), FsError> {
+        self.check_writable()?;
+        let node = Self::map_api_error(
+            self.runtime.block_on(self.client.create_file(parent_id, name)),
+        )?;
+        let mut handle = Handle::new(node.id.clone());
+        handle.opened_size = Some(0);
+        handle.opened_version = node.version().map(str::to_string);
+        handle.name = Some(name.to_string());
+        handle.parent_id = Some(parent_id.to_string());
+        self.apply_write_lease(&mut handle, &node.id)?;
+        if self.create_upload_mode == CreateUploadMode::Eager {
+            self.runtime
+                .block_on(self.staging.stage_file(node.id.clone(), Vec::new()))
+                .map_err(|_| FsError::Io)?;
+            handle.created_unwritten = true;
+        }
+        Ok((node, handle))
+    }
+
+    /// Guards `write`/`setattr`(truncate) against a handle that was
+    /// degraded to read-only by [`Self::acquire_write_lease`].
+    pub fn check_handle_writable(&self, handle: &Handle) -> Result<(), FsError> {
+        if handle.read_only {
+            Err(FsError::BadFileDescriptor)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Handles `setxattr("user.roset.commit", message)` on a directory:
+    /// creates a commit of that node and remembers the resulting id so
+    /// it can be read back via `getxattr("user.roset.last-commit")`.
+    pub fn handle_commit_xattr(&self, ino: u64, node_id: &str, message: &str) -> Result<String, String> {
+        let commit_id = self
+            .runtime
+            .block_on(self.client.create_commit(node_id, message))?;
+        self.last_commits
+            .lock()
+            .unwrap()
+            .insert(ino, commit_id.clone());
+        Ok(commit_id)
+    }
+
+    /// Handles `getxattr("user.roset.last-commit")`.
+    pub fn last_commit(&self, ino: u64) -> Option<String> {
+        self.last_commits.lock().unwrap().get(&ino).cloned()
+    }
+
+    /// Handles `getxattr("user.roset.upload-progress")`: reports how far
+    /// along `ino`'s upload is as `"<uploaded>/<total> <state>
+    /// <throughput> <eta>"`, sourced from the `StagingManager`'s per-job
+    /// progress. The throughput/ETA suffix is only present while
+    /// `state == uploading` and enough progress has been made to
+    /// estimate a rate — see [`crate::staging::UploadProgress::eta`].
+    /// Returns `None` (surfaced as `ENODATA`) when no upload is staged,
+    /// in flight, or recently finished for this node.
+    pub fn upload_progress_xattr(&self, ino: u64) -> Option<Vec<u8>> {
+        let node = self.inodes.node_for(ino)?;
+        let progress = self.staging.progress(&node.id)?;
+        let state = match progress.state {
+            crate::staging::UploadState::Staged => "staged",
+            crate::staging::UploadState::Uploading => "uploading",
+            crate::staging::UploadState::Complete => "complete",
+            crate::staging::UploadState::Failed => "failed",
+        };
+        let mut line = format!("{}/{} {state}", progress.uploaded, progress.total);
+        if let Some(eta) = progress.eta() {
+            line.push_str(&format!(
+                " {:.0}B/s eta {}s",
+                progress.throughput_bps(),
+                eta.as_secs()
+            ));
+        }
+        Some(line.into_bytes())
+    }
+
+    pub fn with_commit_on_unmount(mut self, root_node_id: String, enabled: bool) -> Self {
+        self.root_node_id = root_node_id;
+        self.commit_on_unmount = enabled;
+        self
+    }
+
+    /// Handles `setattr` uid/gid changes (`chown`). Ownership is stored
+    /// as `unix.uid`/`unix.gid` node metadata and echoed back by
+    /// `Node::uid`/`Node::gid`, which `node_to_attr` reads when building
+    /// the reply. Callers are expected to have already checked privilege
+    /// (root, or matching owner under `-o default_permissions`) before
+    /// reaching here.
+    pub fn chown(&self, ino: u64, uid: Option<u32>, gid: Option<u32>) -> Result<Node, String> {
+        let mut node = self
+            .inodes
+            .node_for(ino)
+            .ok_or_else(|| "ENOENT".to_string())?;
+        if let Some(uid) = uid {
+            node.metadata.insert("unix.uid".to_string(), uid.to_string());
+        }
+        if let Some(gid) = gid {
+            node.metadata.insert("unix.gid".to_string(), gid.to_string());
+        }
+        self.inodes.update_node(ino, node.clone());
+        if ino == ROOT_INO {
+            *self.root_attr.lock().unwrap() = None;
+        }
+        Ok(node)
+    }
+
+    /// Handles `fsyncdir`. Directory metadata is durable on the backend
+    /// as soon as the `mkdir`/`create` call that produced it returned, so
+    /// there's nothing to flush here; this just needs to exist so the
+    /// kernel doesn't see `ENOSYS` and treat the filesystem as unable to
+    /// guarantee directory-entry durability.
+    pub fn fsyncdir(&self, _ino: u64, _datasync: bool) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Handles `setxattr` for the write-only `user.roset.invalidate`
+    /// control attribute: drops `path` and everything under it from the
+    /// cache (including negative entries) and asks the kernel to drop its
+    /// own cached entry/attributes, so the next lookup or getattr is
+    /// guaranteed to hit the API.
+    pub fn handle_invalidate_xattr(&self, ino: u64, path: &str) {
+        self.cache.invalidate_node(path);
+        self.cache.invalidate_children(path);
+        if let Some(node) = self.inodes.node_for(ino) {
+            self.invalidate_block_cache(&node.id);
+        }
+        if path == "/" {
+            *self.root_attr.lock().unwrap() = None;
+        }
+        if let Some(notifier) = &self.notifier {
+            notifier.invalidate_inode(ino);
+            if let Some((parent, name)) = path.rsplit_once('/') {
+                let parent_ino = 1;
+                let _ = parent;
+                notifier.invalidate_entry(parent_ino, name);
+            }
+        }
+    }
+
+    /// Handles `flush`: under [`WriteDurability::Async`] (the default),
+    /// a no-op — the upload finishes in the background and `release`
+    /// hands dirty data to the staging queue. Under
+    /// [`WriteDurability::Sync`], blocks until `fh`'s dirty data actually
+    /// lands, returning [`FsError::Io`] on failure. `flush` is the op
+    /// whose errno genuinely propagates to the caller's `close()`, so this
+    /// is the only place a durable write can make `close()` fail.
+    ///
+    /// Idempotent: takes `handle.dirty` on success so a later `flush` or
+    /// `release` on the same handle sees nothing left to upload and
+    /// doesn't re-upload it. On failure, `dirty` is left in place so a
+    /// kernel-retried `flush` gets another chance at it; `release` does
+    /// not upload it under Sync.
+    ///
+    /// Claims `handle.uploading` for the duration of the upload, which is
+    /// issued with the handles lock released (staging may take a while).
+    /// A second `flush` racing the same handle — a dup'd `fh` can get
+    /// `fsync`ed from two threads at once — sees the claim and becomes a
+    /// no-op instead of reading and re-uploading the same `dirty` data a
+    /// second time; likewise a concurrent `release` (see
+    /// [`Self::handle_release`]) leaves the upload to finish here rather
+    /// than re-staging it itself.
+    pub fn handle_flush(&self, fh: u64) -> Result<(), FsError> {
+        if self.write_durability != WriteDurability::Sync {
+            return Ok(());
+        }
+        self.upload_dirty_handle(fh, "sync flush")
+    }
+
+    /// Claims `handle.uploading` and uploads `fh`'s dirty content, the
+    /// way [`Self::handle_flush`] does under [`WriteDurability::Sync`] —
+    /// shared so [`Self::handle_fsync`] can get the same
+    /// claim-then-upload behavior unconditionally, regardless of the
+    /// configured write durability, since `fsync(2)` (and `fdatasync(2)`)
+    /// ask for durability explicitly rather than deferring to a policy
+    /// default. `context` is used only to label the error log line.
+    fn upload_dirty_handle(&self, fh: u64, context: &str) -> Result<(), FsError> {
+        let (node_id, data, opened_size, dirty_ranges, opened_version, name, parent_id) = {
+            let mut handles = self.handles.lock().unwrap();
+            match handles.get_mut(&fh) {
+                Some(handle) if handle.needs_finalize_on_release() && !handle.uploading => {
+                    handle.uploading = true;
+                    (
+                        handle.node_id.clone(),
+                        handle.dirty.clone().unwrap_or_default(),
+                        handle.opened_size,
+                        handle.dirty_ranges.clone(),
+                        handle.opened_version.clone(),
+                        handle.name.clone(),
+                        handle.parent_id.clone(),
+                    )
+                }
+                _ => return Ok(()),
+            }
+        };
+
+        let conflict = self.resolve_upload_target(&node_id, opened_version.as_deref(), name.as_deref(), parent_id.as_deref());
+        let result = match &conflict {
+            Ok((upload_node_id, force_full_rewrite)) => self.upload_handle_data(
+                upload_node_id,
+                &data,
+                opened_size,
+                &dirty_ranges,
+                opened_version.as_deref(),
+                *force_full_rewrite,
+            ),
+            Err(e) => Err(format!("{e:?}")),
+        };
+        if let Some(handle) = self.handles.lock().unwrap().get_mut(&fh) {
+            handle.uploading = false;
+            if result.is_ok() {
+                handle.dirty = None;
+                handle.dirty_ranges.clear();
+                handle.created_unwritten = false;
+            }
+        }
+        if let Err(e @ FsError::Conflict) = conflict {
+            return Err(e);
+        }
+        result.map_err(|e| {
+            eprintln!("roset-fuse: {context} on fh {fh} failed: {e}");
+            FsError::Io
+        })
+    }
+
+    /// Uploads a handle's full dirty buffer `data`, preferring the
+    /// [`crate::upload::UploadStrategy::Patch`] [`Self::plan_write_upload`]
+    /// picks for a small in-place edit over re-uploading the whole file.
+    /// A patch attempt that comes back [`PatchContentError::Unsupported`]
+    /// marks the backend as such (see [`Self::mark_patch_unsupported`])
+    /// and falls back to [`Self::staging`]'s full-rewrite path in the same
+    /// call, so the caller never sees the first, doomed attempt as a
+    /// user-visible failure.
+    ///
+    /// `force_full_rewrite` skips the patch attempt outright — set by
+    /// [`Self::resolve_upload_target`] when this upload was redirected to
+    /// a freshly created `RenameLoser` sibling, which has no prior content
+    /// for a patch to apply against.
+    fn upload_handle_data(
+        &self,
+        node_id: &str,
+        data: &[u8],
+        opened_size: Option<u64>,
+        dirty_ranges: &[(u64, u64)],
+        expected_version: Option<&str>,
+        force_full_rewrite: bool,
+    ) -> Result<(), String> {
+        if !force_full_rewrite {
+            if let crate::upload::UploadStrategy::Patch(ranges) = self.plan_write_upload(opened_size, dirty_ranges) {
+                match self
+                    .runtime
+                    .block_on(self.patch_ranges(node_id, data, &ranges, expected_version))
+                {
+                    Ok(()) => return Ok(()),
+                    Err(crate::client::PatchContentError::Unsupported) => self.mark_patch_unsupported(),
+                    Err(e) => return Err(format!("{e:?}")),
+                }
+            }
+        }
+        self.runtime.block_on(self.staging.flush_now(node_id.to_string(), data.to_vec()))
+    }
+
+    /// Decides which node a completing upload should actually land on,
+    /// consulting [`Self::resolve_write_conflict`] against the backend's
+    /// current version of `node_id`. Returns the node id to upload to and
+    /// whether it needs a full rewrite rather than a patch (true only for
+    /// a freshly created `RenameLoser` sibling).
+    ///
+    /// Skips the extra `get_node` round trip entirely under the default
+    /// [`crate::conflict::ConflictPolicy::LastWriterWins`], since that
+    /// policy always proceeds on `node_id` regardless of whether a
+    /// conflict is detected — fetching `current_version` just to ignore it
+    /// would cost every write an extra request for no behavioral effect.
+    fn resolve_upload_target(
+        &self,
+        node_id: &str,
+        opened_version: Option<&str>,
+        name: Option<&str>,
+        parent_id: Option<&str>,
+    ) -> Result<(String, bool), FsError> {
+        if self.conflict_policy == crate::conflict::ConflictPolicy::LastWriterWins {
+            return Ok((node_id.to_string(), false));
+        }
+        let current = self
+            .runtime
+            .block_on(self.client.get_node(node_id))
+            .map_err(|_| FsError::Io)?;
+        let mut probe = Handle::new(node_id.to_string());
+        probe.opened_version = opened_version.map(str::to_string);
+        let conflict_suffix = format!(
+            "{:x}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+        );
+        let display_name = name.unwrap_or(node_id);
+        match self.resolve_write_conflict(&probe, current.version(), display_name, &conflict_suffix)? {
+            crate::conflict::ConflictOutcome::Proceed => Ok((node_id.to_string(), false)),
+            crate::conflict::ConflictOutcome::RenameTo(new_name) => {
+                let Some(parent_id) = parent_id else {
+                    return Err(FsError::Conflict);
+                };
+                let sibling = Self::map_api_error(
+                    self.runtime.block_on(self.client.create_file(parent_id, &new_name)),
+                )?;
+                Ok((sibling.id, true))
+            }
+        }
+    }
+
+    /// Sends each of `ranges` to [`RosetClient::patch_content`] in turn,
+    /// slicing it out of `data` — the handle's full dirty buffer, which
+    /// `write_mode` keeps equal to the file's current whole content (see
+    /// [`Self::acquire_write_lease`]), so `data[offset..offset+len]`
+    /// always matches what the backend should have at that range.
+    ///
+    /// `expected_version` only guards the *first* call: each successful
+    /// patch returns the node's new version, which becomes the
+    /// precondition for the next range in the loop. Without this, every
+    /// range after the first would still carry the handle's now-stale
+    /// pre-write version and come back `VersionMismatch` even though
+    /// nothing actually raced this upload — the mismatch would just be
+    /// against the version this same loop's own prior patch produced.
+    async fn patch_ranges(
+        &self,
+        node_id: &str,
+        data: &[u8],
+        ranges: &[(u64, u64)],
+        expected_version: Option<&str>,
+    ) -> Result<(), crate::client::PatchContentError> {
+        let mut version = expected_version.map(str::to_string);
+        for &(offset, len) in ranges {
+            let start = offset as usize;
+            let end = start + len as usize;
+            let node = self
+                .client
+                .patch_content(node_id, offset, &data[start..end], version.as_deref())
+                .await?;
+            version = node.version().map(str::to_string);
+        }
+        Ok(())
+    }
+
+    /// Records an mtime update that hasn't been sent to the backend yet —
+    /// e.g. a `setattr` buffered under writeback caching (see
+    /// [`Self::with_writeback_cache`]) — so [`Self::handle_fsync`] knows
+    /// to flush it on a full (non-`datasync`) fsync.
+    pub fn record_pending_mtime(&self, fh: u64, mtime: std::time::SystemTime) {
+        if let Some(handle) = self.handles.lock().unwrap().get_mut(&fh) {
+            handle.pending_mtime = Some(mtime);
+        }
+    }
+
+    /// Sends `fh`'s [`Handle::pending_mtime`] to the backend as a
+    /// metadata patch, if one is recorded, clearing it on success.
+    fn flush_pending_metadata(&self, fh: u64) -> Result<(), FsError> {
+        let (node_id, mtime, version) = {
+            let handles = self.handles.lock().unwrap();
+            match handles.get(&fh).and_then(|h| h.pending_mtime.map(|m| (h.node_id.clone(), m, h.opened_version.clone()))) {
+                Some(found) => found,
+                None => return Ok(()),
+            }
+        };
+        let secs = mtime.duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs();
+        let mut patch = HashMap::new();
+        patch.insert("mtime_secs".to_string(), secs.to_string());
+        let result = self
+            .runtime
+            .block_on(self.client.update_node_metadata_patch(&node_id, patch, version.as_deref()));
+        match result {
+            Ok(_) => {
+                if let Some(handle) = self.handles.lock().unwrap().get_mut(&fh) {
+                    handle.pending_mtime = None;
+                }
+                Ok(())
+            }
+            Err(e) => {
+                eprintln!("roset-fuse: fsync metadata flush on fh {fh} failed: {e:?}");
+                Err(FsError::Io)
+            }
+        }
+    }
+
+    /// Handles `fsync`: uploads `fh`'s dirty content so it's durable on
+    /// the backend, the way `datasync == true` (`fdatasync(2)`) is
+    /// defined to guarantee only file data, not metadata — this part
+    /// always runs, regardless of `datasync`.
+    ///
+    /// A full fsync (`datasync == false`) additionally flushes any
+    /// pending metadata update (currently just a deferred mtime set via
+    /// [`Self::record_pending_mtime`]) that hasn't reached the backend
+    /// yet, since `datasync`'s data-only guarantee doesn't cover it.
+    pub fn handle_fsync(&self, fh: u64, datasync: bool) -> Result<(), FsError> {
+        self.upload_dirty_handle(fh, "fsync")?;
+        if datasync {
+            return Ok(());
+        }
+        self.flush_pending_metadata(fh)
+    }
+
+    /// Handles `release`: drops `fh`'s handle and releases its lease, if
+    /// any. Durability under [`WriteDurability::Sync`] was already
+    /// enforced by `flush`, which clears `handle.dirty` on success, so
+    /// `release` uploads nothing in that mode, even if `flush` failed or
+    /// was never called. Under [`WriteDurability::Async`], any remaining
+    /// dirty data is handed off to the staging queue here instead.
+    ///
+    /// The handle is removed from `self.handles` and the `uploading`
+    /// claim it carried (if any) is checked in the same locked step, so
+    /// this can never race a concurrent `flush` that already took
+    /// ownership of `dirty` and is uploading it: if `flush` claimed it
+    /// first, `already_uploading` is true here and `release` leaves that
+    /// upload alone; if `release` wins the race instead, it removes the
+    /// handle before `flush` can see anything to claim, and `flush`'s own
+    /// lookup comes back empty.
+    pub fn handle_release(&self, fh: u64) {
+        let (handle, already_uploading) = {
+            let mut handles = self.handles.lock().unwrap();
+            let already_uploading = handles.get(&fh).is_some_and(|h| h.uploading);
+            (handles.remove(&fh), already_uploading)
+        };
+        let Some(mut handle) = handle else { return };
+
+        if self.write_durability == WriteDurability::Async
+            && handle.needs_finalize_on_release()
+            && !already_uploading
+        {
+            let data = handle.dirty.take().unwrap_or_default();
+            let _ = self
+                .runtime
+                .block_on(self.staging.stage_file(handle.node_id.clone(), data));
+        }
+
+        handle.stop_lease_renewal();
+        if let Some(lease) = &handle.lease {
+            self.runtime.block_on(async {
+                let _ = self.client.release_lease(lease).await;
+            });
+        }
+    }
+
+    /// Flushes dirty handles through staging and releases any held leases.
+    /// Called both from `Drop` and explicitly from `main` after `mount2`
+    /// returns, so a clean unmount doesn't depend on drop order.
+    ///
+    /// Skips a handle whose `uploading` claim is already set — a `flush`
+    /// on it is mid-upload with the handles lock released (see
+    /// [`Self::handle_flush`]) and will finish on its own; uploading the
+    /// same `dirty` data here too would double it.
+    pub fn shutdown(&self) {
+        let mut handles = self.handles.lock().unwrap();
+        for (_, mut handle) in handles.drain() {
+            handle.stop_lease_renewal();
+            self.runtime.block_on(async {
+                if let Some(data) = handle.dirty {
+                    if !handle.uploading {
+                        let _ = self.staging.flush_now(handle.node_id.clone(), data).await;
+                    }
+                }
+                if let Some(lease) = handle.lease {
+                    let _ = self.client.release_lease(&lease).await;
+                }
+            });
+        }
+        drop(handles);
+
+        if self.commit_on_unmount && !self.root_node_id.is_empty() {
+            self.runtime.block_on(async {
+                match self
+                    .client
+                    .create_commit(&self.root_node_id, "auto-commit on unmount")
+                    .await
+                {
+                    Ok(commit_id) => eprintln!("roset-fuse: committed {commit_id} on unmount"),
+                    Err(e) => eprintln!("roset-fuse: commit-on-unmount failed: {e}"),
+                }
+            });
+        }
+    }
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD.decode(encoded).ok()
+}
+
+impl Drop for RosetFs {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Lease;
+
+    fn dir_entry(name: &str) -> Node {
+        Node {
+            id: name.to_string(),
+            name: name.to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn a_buffer_that_fills_exactly_on_the_last_entry_loses_nothing_across_two_calls() {
+        let entries = vec![dir_entry("a"), dir_entry("b"), dir_entry("c")];
+        let capacity = 3;
+
+        let mut first_call = Vec::new();
+        fill_reply_directory(&entries, 0, |entry, cookie| {
+            if first_call.len() >= capacity {
+                return true;
+            }
+            first_call.push((entry.name.clone(), cookie));
+            false
+        });
+        assert_eq!(
+            first_call,
+            vec![("a".to_string(), 1), ("b".to_string(), 2), ("c".to_string(), 3)]
+        );
+
+        // The kernel resumes with the cookie of the last entry actually
+        // delivered. Since every entry fit, this correctly reports EOF
+        // (no further entries) instead of re-sending "c" or losing it.
+        let mut second_call = Vec::new();
+        fill_reply_directory(&entries, 3, |entry, cookie| {
+            second_call.push((entry.name.clone(), cookie));
+            false
+        });
+        assert!(second_call.is_empty());
+    }
+
+    #[test]
+    fn a_buffer_that_fills_mid_listing_resumes_at_the_rejected_entry_not_past_it() {
+        let entries = vec![dir_entry("a"), dir_entry("b"), dir_entry("c")];
+
+        let mut first_call = Vec::new();
+        fill_reply_directory(&entries, 0, |entry, cookie| {
+            if first_call.len() >= 2 {
+                return true;
+            }
+            first_call.push((entry.name.clone(), cookie));
+            false
+        });
+        assert_eq!(first_call, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+
+        let mut second_call = Vec::new();
+        fill_reply_directory(&entries, 2, |entry, cookie| {
+            second_call.push((entry.name.clone(), cookie));
+            false
+        });
+        assert_eq!(second_call, vec![("c".to_string(), 3)]);
+    }
+
+    #[test]
+    fn teardown_releases_leases_and_stages_dirty_data() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        {
+            let mut handles = fs.handles.lock().unwrap();
+            let mut handle = Handle::new("node-1".to_string());
+            handle.dirty = Some(b"pending write".to_vec());
+            handle.lease = Some(Lease {
+                node_id: "node-1".to_string(),
+                lease_id: "lease-1".to_string(),
+                expires_at_unix_secs: None,
+            });
+            handles.insert(1, handle);
+        }
+
+        fs.shutdown();
+
+        assert!(fs.handles.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalidate_xattr_forces_cache_miss() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(300)),
+            rt.handle().clone(),
+        );
+        fs.cache.put(
+            "/dir/file.txt".to_string(),
+            Some(crate::node::Node {
+                id: "n1".to_string(),
+                name: "file.txt".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(4),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: std::collections::HashMap::new(),
+            }),
+        );
+        assert!(fs.cache.get("/dir/file.txt").is_some());
+
+        fs.handle_invalidate_xattr(42, "/dir/file.txt");
+
+        assert!(fs.cache.get("/dir/file.txt").is_none());
+    }
+
+    #[test]
+    fn fsyncdir_on_a_directory_returns_ok() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        assert!(fs.fsyncdir(1, false).is_ok());
+    }
+
+    #[test]
+    fn chown_persists_and_is_reflected_in_getattr() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let ino = fs.inodes.map_id(
+            "/file.txt".to_string(),
+            crate::node::Node {
+                id: "n1".to_string(),
+                name: "file.txt".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(0),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: std::collections::HashMap::new(),
+            },
+        );
+
+        fs.chown(ino, Some(1000), Some(1000)).unwrap();
+
+        let node = fs.inodes.node_for(ino).unwrap();
+        assert_eq!(node.uid(), Some(1000));
+        assert_eq!(node.gid(), Some(1000));
+    }
+
+    #[test]
+    fn commit_on_unmount_flag_is_wired_into_shutdown() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_commit_on_unmount("root-node".to_string(), true);
+
+        assert!(fs.commit_on_unmount);
+        assert_eq!(fs.root_node_id, "root-node");
+    }
+
+    #[test]
+    fn commit_xattr_issues_a_commits_call_and_becomes_readable() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/commits")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"commit-123"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        let commit_id = fs.handle_commit_xattr(1, "dir-node", "snapshot").unwrap();
+
+        assert_eq!(commit_id, "commit-123");
+        assert_eq!(fs.last_commit(1), Some("commit-123".to_string()));
+    }
+
+    #[test]
+    fn upload_progress_xattr_reports_increasing_progress_during_a_staged_upload() {
+        // A current-thread runtime so the polling loop below and the
+        // staging worker's progress ticks are cooperatively scheduled on
+        // the same thread via `yield_now` — a multi-threaded runtime lets
+        // the worker race ahead on its own OS thread and blow straight
+        // through every intermediate sample before this thread gets to
+        // observe one.
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::with_concurrency(4, 1, std::time::Duration::from_secs(3600), None, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let ino = fs.inodes.map_id(
+            "/file.bin".to_string(),
+            Node {
+                id: "node-1".to_string(),
+                name: "file.bin".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(16),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: HashMap::new(),
+            },
+        );
+
+        let seen_uploaded = rt.block_on(async {
+            fs.staging.stage_file("node-1".to_string(), vec![0u8; 16]).await.unwrap();
+
+            let mut seen_uploaded = Vec::new();
+            for _ in 0..10_000 {
+                match fs.upload_progress_xattr(ino) {
+                    Some(value) => {
+                        let text = String::from_utf8(value).unwrap();
+                        let uploaded: u64 = text.split('/').next().unwrap().parse().unwrap();
+                        seen_uploaded.push(uploaded);
+                        if text.ends_with("complete") {
+                            break;
+                        }
+                    }
+                    None => break,
+                }
+                tokio::task::yield_now().await;
+            }
+            seen_uploaded
+        });
+
+        assert!(seen_uploaded.windows(2).all(|w| w[0] <= w[1]), "progress should never go backwards");
+        assert!(
+            seen_uploaded.iter().any(|&u| u > 0 && u < 16),
+            "expected at least one sample strictly between 0 and the total, got {seen_uploaded:?}"
+        );
+        assert_eq!(*seen_uploaded.last().unwrap(), 16);
+    }
+
+    #[test]
+    fn upload_progress_xattr_is_absent_for_a_node_with_no_upload_in_progress() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let ino = fs.inodes.map_id(
+            "/idle.bin".to_string(),
+            Node {
+                id: "node-2".to_string(),
+                name: "idle.bin".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(0),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: HashMap::new(),
+            },
+        );
+
+        assert!(fs.upload_progress_xattr(ino).is_none());
+    }
+
+    #[test]
+    fn sync_durability_flush_surfaces_a_failing_upload_as_eio() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let staging = StagingManager::with_upload_hook(
+            8,
+            1,
+            std::time::Duration::from_secs(3600),
+            None,
+            crate::staging::StagingRetryConfig {
+                max_attempts: 1,
+                max_backoff: std::time::Duration::from_millis(1),
+            },
+            None,
+            Some(std::sync::Arc::new(|_: &crate::staging::StagingJob| {
+                Box::pin(async { Err("simulated backend outage".to_string()) })
+                    as std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), String>> + Send>>
+            })),
+            rt.handle().clone(),
+        );
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            staging,
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_write_durability(WriteDurability::Sync);
+
+        let mut handle = Handle::new("node-1".to_string());
+        handle.dirty = Some(b"unsaved".to_vec());
+        fs.handles.lock().unwrap().insert(7, handle);
+
+        assert_eq!(fs.handle_flush(7), Err(FsError::Io));
+
+        // release must not re-attempt the upload in Sync mode — flush
+        // already made the definitive attempt.
+        fs.handle_release(7);
+        assert!(fs.handles.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn sync_durability_flush_is_a_noop_once_already_flushed() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let staging = StagingManager::with_upload_hook(
+            8,
+            1,
+            std::time::Duration::from_secs(3600),
+            None,
+            crate::staging::StagingRetryConfig::default(),
+            None,
+            None,
+            rt.handle().clone(),
+        );
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            staging,
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_write_durability(WriteDurability::Sync);
+
+        let mut handle = Handle::new("node-1".to_string());
+        handle.dirty = Some(b"unsaved".to_vec());
+        fs.handles.lock().unwrap().insert(7, handle);
+
+        assert_eq!(fs.handle_flush(7), Ok(()));
+        assert!(fs.handles.lock().unwrap().get(&7).unwrap().dirty.is_none());
+        // A second flush (the kernel can call flush more than once per
+        // close) has nothing left to upload.
+        assert_eq!(fs.handle_flush(7), Ok(()));
+    }
+
+    #[test]
+    fn a_release_racing_an_in_flight_flush_on_one_handle_does_not_double_upload() {
+        use std::sync::mpsc;
+        use std::sync::Arc;
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let upload_count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let (upload_started_tx, upload_started_rx) = mpsc::channel::<()>();
+        let (release_uploading_tx, release_uploading_rx) = mpsc::channel::<bool>();
+
+        let upload_count_for_hook = upload_count.clone();
+        let upload_started_tx = Arc::new(Mutex::new(Some(upload_started_tx)));
+        let release_uploading_rx = Arc::new(Mutex::new(release_uploading_rx));
+        let staging = StagingManager::with_upload_hook(
+            8,
+            1,
+            std::time::Duration::from_secs(3600),
+            None,
+            crate::staging::StagingRetryConfig::default(),
+            None,
+            Some(Arc::new(move |_: &crate::staging::StagingJob| {
+                let upload_count_for_hook = upload_count_for_hook.clone();
+                let upload_started_tx = upload_started_tx.clone();
+                let release_uploading_rx = release_uploading_rx.clone();
+                Box::pin(async move {
+                    upload_count_for_hook.fetch_add(1, Ordering::SeqCst);
+                    if let Some(tx) = upload_started_tx.lock().unwrap().take() {
+                        let _ = tx.send(());
+                    }
+                    // Block until the concurrent `release` below has had a
+                    // chance to see this handle's `uploading` claim.
+                    let _ = release_uploading_rx.lock().unwrap().recv();
+                    Ok(())
+                }) as std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), String>> + Send>>
+            })),
+            rt.handle().clone(),
+        );
+        let fs = Arc::new(
+            RosetFs::new(
+                RosetClient::new("https://api.roset.dev"),
+                staging,
+                AttrCache::new(std::time::Duration::from_secs(30)),
+                rt.handle().clone(),
+            )
+            .with_write_durability(WriteDurability::Sync),
+        );
+
+        let mut handle = Handle::new("node-1".to_string());
+        handle.dirty = Some(b"unsaved".to_vec());
+        fs.handles.lock().unwrap().insert(7, handle);
+
+        let flushing_fs = fs.clone();
+        let flush_thread = std::thread::spawn(move || flushing_fs.handle_flush(7));
+
+        // Wait for the flush's upload to actually start — it's now
+        // blocked inside the hook below — before releasing, so the two
+        // genuinely overlap instead of running sequentially.
+        upload_started_rx.recv().unwrap();
+        let already_uploading = fs.handles.lock().unwrap().get(&7).unwrap().uploading;
+        fs.handle_release(7);
+        // Only now let the in-flight flush's upload finish.
+        release_uploading_tx.send(true).unwrap();
+
+        assert_eq!(flush_thread.join().unwrap(), Ok(()));
+        assert!(already_uploading, "release should have observed flush's in-flight claim");
+        assert_eq!(upload_count.load(Ordering::SeqCst), 1);
+        assert!(fs.handles.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn async_durability_flush_is_always_a_noop() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let mut handle = Handle::new("node-1".to_string());
+        handle.dirty = Some(b"unsaved".to_vec());
+        fs.handles.lock().unwrap().insert(7, handle);
+
+        assert_eq!(fs.handle_flush(7), Ok(()));
+        assert!(fs.handles.lock().unwrap().get(&7).unwrap().dirty.is_some());
+    }
+
+    #[test]
+    fn sync_durability_flush_patches_just_the_dirty_range_instead_of_rewriting_the_whole_file() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let patch_mock = server
+            .mock("PATCH", "/v1/nodes/n1/content")
+            .match_header("content-range", "bytes 96-99/*")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"f","node_type":"file","size":100,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .expect(1)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_write_durability(WriteDurability::Sync);
+
+        let mut data = vec![b'a'; 100];
+        data[96..].copy_from_slice(b"zzzz");
+        let mut handle = Handle::new("n1".to_string());
+        handle.opened_size = Some(100);
+        handle.dirty = Some(data);
+        handle.record_write(96, 4);
+        fs.handles.lock().unwrap().insert(7, handle);
+
+        assert_eq!(fs.handle_flush(7), Ok(()));
+        patch_mock.assert();
+        assert!(fs.handles.lock().unwrap().get(&7).unwrap().dirty.is_none());
+    }
+
+    #[test]
+    fn a_patch_the_backend_reports_unsupported_falls_back_to_a_full_rewrite() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let patch_mock = server
+            .mock("PATCH", "/v1/nodes/n1/content")
+            .with_status(404)
+            .expect(1)
+            .create();
+        let full_upload_mock = server
+            .mock("PUT", "/v1/nodes/n1/content")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"f","node_type":"file","size":100,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .expect(1)
+            .create();
+
+        let client = RosetClient::new(server.url());
+        let staging = StagingManager::with_upload_hook(
+            8,
+            1,
+            std::time::Duration::from_secs(3600),
+            None,
+            crate::staging::StagingRetryConfig::default(),
+            None,
+            Some(crate::staging::client_upload_hook(client.clone())),
+            rt.handle().clone(),
+        );
+        let fs = RosetFs::new(
+            client,
+            staging,
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_write_durability(WriteDurability::Sync);
+
+        let mut data = vec![b'a'; 100];
+        data[96..].copy_from_slice(b"zzzz");
+        let mut handle = Handle::new("n1".to_string());
+        handle.opened_size = Some(100);
+        handle.dirty = Some(data);
+        handle.record_write(96, 4);
+        fs.handles.lock().unwrap().insert(7, handle);
+
+        assert_eq!(fs.handle_flush(7), Ok(()));
+        patch_mock.assert();
+        full_upload_mock.assert();
+        assert!(fs.should_skip_patch());
+    }
+
+    #[test]
+    fn a_full_fsync_flushes_a_pending_mtime_change_but_a_datasync_fsync_does_not() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let metadata_mock = server
+            .mock("PATCH", "/v1/nodes/n1/metadata")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"f","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .expect(1)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let mut handle = Handle::new("n1".to_string());
+        handle.pending_mtime = Some(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1000));
+        fs.handles.lock().unwrap().insert(7, handle);
+
+        // `datasync == true` must not touch metadata.
+        assert_eq!(fs.handle_fsync(7, true), Ok(()));
+        assert!(fs.handles.lock().unwrap().get(&7).unwrap().pending_mtime.is_some());
+
+        // A full fsync flushes it and clears the pending marker.
+        assert_eq!(fs.handle_fsync(7, false), Ok(()));
+        assert!(fs.handles.lock().unwrap().get(&7).unwrap().pending_mtime.is_none());
+        metadata_mock.assert();
+    }
+
+    #[test]
+    fn immutable_root_auto_enables_read_only() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let mut metadata = std::collections::HashMap::new();
+        metadata.insert("committed".to_string(), "true".to_string());
+        fs.init_root(crate::node::Node {
+            id: "root".to_string(),
+            name: "/".to_string(),
+            node_type: crate::node::NodeType::Directory,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata,
+        });
+
+        assert_eq!(fs.check_writable(), Err(FsError::ReadOnlyFilesystem));
+    }
+
+    #[test]
+    fn repeated_root_getattrs_reuse_the_cached_attr_instead_of_hitting_the_api() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("GET", "/v1/nodes/root-id")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"root-id","name":"/","node_type":"directory","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .expect(0)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        fs.init_root(crate::node::Node {
+            id: "root-id".to_string(),
+            name: "/".to_string(),
+            node_type: crate::node::NodeType::Directory,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        });
+
+        for _ in 0..5 {
+            let attr = fs.getattr_root().unwrap();
+            assert_eq!(attr.ino, ROOT_INO);
+            assert_eq!(attr.kind, fuser::FileType::Directory);
+        }
+        // init_root alone already resolves the root, so even the first
+        // getattr_root call above is served from the cache it populates —
+        // the mock's `.expect(0)` proves none of the five calls reached
+        // the API.
+    }
+
+    #[test]
+    fn a_stale_root_attr_is_refreshed_from_the_backend_after_the_bound() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("GET", "/v1/nodes/root-id")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"root-id","name":"/","node_type":"directory","size":42,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .expect(1)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        fs.init_root(crate::node::Node {
+            id: "root-id".to_string(),
+            name: "/".to_string(),
+            node_type: crate::node::NodeType::Directory,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        });
+        // Force the cached entry to look older than the refresh bound
+        // instead of sleeping in the test.
+        fs.root_attr.lock().unwrap().as_mut().unwrap().cached_at =
+            std::time::Instant::now() - ROOT_ATTR_REFRESH_INTERVAL - std::time::Duration::from_secs(1);
+
+        let attr = fs.getattr_root().unwrap();
+
+        assert_eq!(attr.size, 42);
+    }
+
+    #[test]
+    fn ready_xattr_reports_unready_once_the_circuit_breaker_has_opened() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("GET", "/v1/nodes/root-id")
+            .with_status(503)
+            .expect(3)
+            .create();
+
+        let client = RosetClient::with_backoff_config(
+            RosetClient::new(server.url()),
+            crate::retry::BackoffConfig {
+                max_backoff_rate_limit: std::time::Duration::from_millis(1),
+                max_backoff_server_error: std::time::Duration::from_millis(1),
+                max_backoff_network: std::time::Duration::from_millis(1),
+            },
+        )
+        .with_circuit_breaker(1, std::time::Duration::from_secs(60));
+
+        let fs = RosetFs::new(
+            client,
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        assert!(fs.is_ready());
+        assert_eq!(fs.ready_xattr(), b"1".to_vec());
+
+        // A single failing call is enough to open a threshold-1 breaker.
+        let _ = rt.handle().block_on(fs.client.get_node("root-id"));
+
+        assert!(!fs.is_ready());
+        assert_eq!(fs.ready_xattr(), b"0".to_vec());
+    }
+
+    #[test]
+    fn handle_recover_xattr_flushes_the_cache_and_re_resolves_the_root() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("GET", "/v1/nodes/root-id")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"root-id","name":"/","node_type":"directory","size":99,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .expect(1)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        fs.init_root(crate::node::Node {
+            id: "root-id".to_string(),
+            name: "/".to_string(),
+            node_type: crate::node::NodeType::Directory,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        });
+        // Data cached while the backend was unreachable shouldn't survive
+        // a recovery.
+        fs.cache.put("/stale".to_string(), None);
+        assert!(fs.cache.get("/stale").is_some());
+
+        fs.handle_recover_xattr().unwrap();
+
+        assert!(fs.cache.get("/stale").is_none());
+        let attr = fs.getattr_root().unwrap();
+        assert_eq!(attr.size, 99);
+    }
+
+    #[test]
+    fn offline_mode_serves_cached_reads_and_fails_uncached_ones() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_allow_offline(true);
+
+        fs.cache.put(
+            "/cached.txt".to_string(),
+            Some(crate::node::Node {
+                id: "n1".to_string(),
+                name: "cached.txt".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(4),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: std::collections::HashMap::new(),
+            }),
+        );
+
+        assert!(fs.resolve_with_offline_fallback("/cached.txt", None).is_ok());
+        assert!(fs.resolve_with_offline_fallback("/uncached.txt", None).is_err());
+    }
+
+    #[test]
+    fn manifest_unsupported_signal_sticks_until_reset() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        assert!(!fs.should_skip_manifest());
+        fs.mark_manifest_unsupported();
+        assert!(fs.should_skip_manifest());
+        fs.reset_manifest_support();
+        assert!(!fs.should_skip_manifest());
+    }
+
+    #[test]
+    fn patch_unsupported_signal_sticks_until_reset() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        assert!(!fs.should_skip_patch());
+        fs.mark_patch_unsupported();
+        assert!(fs.should_skip_patch());
+        fs.reset_patch_support();
+        assert!(!fs.should_skip_patch());
+    }
+
+    #[test]
+    fn plan_write_upload_skips_the_patch_path_once_unsupported_is_marked() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let ranges = [(10u64, 20u64)];
+        assert_eq!(
+            fs.plan_write_upload(Some(10_000), &ranges),
+            crate::upload::UploadStrategy::Patch(ranges.to_vec())
+        );
+        fs.mark_patch_unsupported();
+        assert_eq!(
+            fs.plan_write_upload(Some(10_000), &ranges),
+            crate::upload::UploadStrategy::FullRewrite
+        );
+    }
+
+    #[test]
+    fn patch_ranges_carries_each_ranges_returned_version_into_the_next_ranges_precondition() {
+        // Two disjoint ranges in one flush: the second range's request
+        // must be preconditioned on the version the first range's patch
+        // just produced, not the handle's original pre-write version —
+        // otherwise it's indistinguishable from a real race and comes
+        // back `VersionMismatch`.
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _first = server
+            .mock("PATCH", "/v1/nodes/n1/content")
+            .match_header("if-match", "v1")
+            .match_header("content-range", "bytes 0-4/*")
+            .match_body(b"hello".to_vec())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"n1","name":"f","node_type":"file","size":20,"etag":"v2","mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#,
+            )
+            .create();
+        let _second = server
+            .mock("PATCH", "/v1/nodes/n1/content")
+            .match_header("if-match", "v2")
+            .match_header("content-range", "bytes 10-14/*")
+            .match_body(b"world".to_vec())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"n1","name":"f","node_type":"file","size":20,"etag":"v3","mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#,
+            )
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        let data = b"helloXXXXXworldYYYYY";
+        let ranges = [(0u64, 5u64), (10u64, 5u64)];
+        assert!(rt.block_on(fs.patch_ranges("n1", data, &ranges, Some("v1"))).is_ok());
+    }
+
+    #[test]
+    fn plan_handle_read_ahead_prefetches_sequential_reads_and_skips_random_access() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        // Sequential: this read starts exactly where the last one ended.
+        assert_eq!(
+            fs.plan_handle_read_ahead(Some(4096), 4096, 512),
+            crate::readahead::ReadAheadPlan::Prefetch { offset: 4608, len: crate::readahead::DEFAULT_READ_AHEAD_BYTES },
+        );
+        // Random: this read seeks well past where the last one ended.
+        assert_eq!(fs.plan_handle_read_ahead(Some(4096), 1_000_000, 512), crate::readahead::ReadAheadPlan::Skip);
+        // Disabled: a `0` window never prefetches even for sequential access.
+        let fs = fs.with_read_ahead(0);
+        assert_eq!(fs.plan_handle_read_ahead(Some(4096), 4096, 512), crate::readahead::ReadAheadPlan::Skip);
+    }
+
+    #[test]
+    fn ignore_globbed_entries_are_dropped_from_a_listing() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_ignore_globs(vec![glob::Pattern::new(".roset*").unwrap()]);
+
+        let make = |name: &str| crate::node::Node {
+            id: name.to_string(),
+            name: name.to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        };
+        let filtered = fs.filter_ignored(vec![make(".roset-internal"), make("real-file.txt")]);
+
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].name, "real-file.txt");
+    }
+
+    #[test]
+    fn hidden_names_are_enoent_on_lookup_unless_allowed() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_ignore_globs(vec![glob::Pattern::new("*.tmp").unwrap()]);
+
+        assert_eq!(fs.check_lookup_visible("checkpoint.tmp"), Err(FsError::NotFound));
+        assert!(fs.check_lookup_visible("checkpoint.bin").is_ok());
+
+        let fs = fs.with_allow_hidden_lookup(true);
+        assert!(fs.check_lookup_visible("checkpoint.tmp").is_ok());
+    }
+
+    #[test]
+    fn a_manifest_beyond_the_threshold_falls_back_to_paged_listing() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _summary_mock = server
+            .mock("GET", "/v1/nodes/huge-dir/manifest/summary")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"node_count":100000}"#)
+            .create();
+        let _manifest_mock = server
+            .mock("GET", "/v1/nodes/huge-dir/manifest")
+            .expect(0)
+            .create();
+        let _children_mock = server
+            .mock("GET", "/v1/nodes/huge-dir/children")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"children":[{"id":"a","name":"a","node_type":"file","size":1,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}],"next_cursor":null}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_manifest_node_count_threshold(1_000);
+
+        match fs.list_committed_directory("huge-dir") {
+            DirectoryListing::Paged(listing) => assert_eq!(listing.children.len(), 1),
+            DirectoryListing::Manifest(_) => panic!("expected a paged fallback, got a bulk manifest load"),
+        }
+    }
+
+    #[test]
+    fn a_manifest_within_the_threshold_is_bulk_loaded_and_cached() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _summary_mock = server
+            .mock("GET", "/v1/nodes/small-dir/manifest/summary")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"node_count":1}"#)
+            .create();
+        let _manifest_mock = server
+            .mock("GET", "/v1/nodes/small-dir/manifest")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"nodes":[{"id":"a","name":"a.txt","node_type":"file","size":1,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}]}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_manifest_node_count_threshold(1_000);
+
+        let nodes = match fs.list_committed_directory("small-dir") {
+            DirectoryListing::Manifest(nodes) => nodes,
+            DirectoryListing::Paged(_) => panic!("expected a bulk manifest load, got a paged fallback"),
+        };
+        fs.bulk_load_manifest_into_cache("/dir", &nodes);
+
+        assert!(fs.cache.get("/dir/a.txt").is_some());
+    }
+
+    #[test]
+    fn immutable_nodes_get_fopen_keep_cache() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let mut metadata = std::collections::HashMap::new();
+        metadata.insert("committed".to_string(), "true".to_string());
+        let ino = fs.inodes.map_id(
+            "/snapshot.txt".to_string(),
+            crate::node::Node {
+                id: "n1".to_string(),
+                name: "snapshot.txt".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(4),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata,
+            },
+        );
+
+        assert_eq!(fs.open_reply_flags(ino), fuser::consts::FOPEN_KEEP_CACHE);
+    }
+
+    #[test]
+    fn direct_io_mode_overrides_keep_cache() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_direct_io(true);
+        let mut metadata = std::collections::HashMap::new();
+        metadata.insert("committed".to_string(), "true".to_string());
+        let ino = fs.inodes.map_id(
+            "/snapshot.txt".to_string(),
+            crate::node::Node {
+                id: "n1".to_string(),
+                name: "snapshot.txt".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(4),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata,
+            },
+        );
+
+        assert_eq!(fs.open_reply_flags(ino), fuser::consts::FOPEN_DIRECT_IO);
+    }
+
+    #[test]
+    fn mkdir_on_an_existing_name_returns_eexist_not_ebusy() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/parent/children")
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"already_exists"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        assert_eq!(fs.mkdir("parent", "dup"), Err(FsError::AlreadyExists));
+    }
+
+    #[test]
+    fn mkdir_blocked_by_a_lease_returns_ebusy() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/parent/children")
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"lease_conflict"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        assert_eq!(fs.mkdir("parent", "new-dir"), Err(FsError::Conflict));
+    }
+
+    #[test]
+    fn deferred_create_mode_does_not_stage_an_upload_for_a_file_never_written_to() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/parent/children")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"node-new","name":"sentinel","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#,
+            )
+            .create();
+        let _lease_mock = server
+            .mock("POST", "/v1/nodes/node-new/lease")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"node_id":"node-new","lease_id":"lease-new"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_create_upload_mode(CreateUploadMode::Deferred);
+
+        let (node, handle) = fs.handle_create("parent", "sentinel").unwrap();
+
+        assert!(!handle.created_unwritten);
+        assert!(handle.lease.is_some());
+        assert!(fs.staging.progress(&node.id).is_none());
+    }
+
+    #[test]
+    fn eager_create_mode_stages_an_empty_upload_immediately() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/parent/children")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"node-eager","name":"file.txt","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#,
+            )
+            .create();
+        let _lease_mock = server
+            .mock("POST", "/v1/nodes/node-eager/lease")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"node_id":"node-eager","lease_id":"lease-eager"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        let (_node, handle) = fs.handle_create("parent", "file.txt").unwrap();
+
+        assert!(handle.created_unwritten);
+        assert!(handle.lease.is_some());
+    }
+
+    #[test]
+    fn create_fails_with_ebusy_when_another_writer_already_holds_the_new_nodes_lease() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/parent/children")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"node-racy","name":"file.txt","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#,
+            )
+            .create();
+        let _lease_mock = server
+            .mock("POST", "/v1/nodes/node-racy/lease")
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"lease_conflict"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        assert_eq!(fs.handle_create("parent", "file.txt").unwrap_err(), FsError::Conflict);
+    }
+
+    #[test]
+    fn a_lease_about_to_expire_is_renewed_in_the_background() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let _mock = server
+            .mock("POST", "/v1/nodes/n1/lease")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                r#"{{"node_id":"n1","lease_id":"lease-1","expires_at_unix_secs":{}}}"#,
+                now + 1
+            ))
+            .create();
+        let _get_mock = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"f","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .create();
+        let renew_mock = server
+            .mock("POST", "/v1/nodes/n1/lease/lease-1/renew")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(format!(
+                r#"{{"node_id":"n1","lease_id":"lease-1","expires_at_unix_secs":{}}}"#,
+                now + 3600
+            ))
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        let handle = fs.acquire_write_lease("n1", libc::O_TRUNC).unwrap();
+        assert!(handle.renewal_task.is_some());
+
+        // The lease's margin-adjusted expiry is already in the past, so
+        // the renewal task's first sleep is zero and it fires almost
+        // immediately; give it a moment to actually run on the runtime's
+        // own worker thread.
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        renew_mock.assert();
+    }
+
+    #[test]
+    fn a_leased_file_open_fails_with_ebusy_without_the_fallback_flag() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/leased/lease")
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"lease_conflict"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        assert_eq!(fs.acquire_write_lease("leased", 0).unwrap_err(), FsError::Conflict);
+    }
+
+    #[test]
+    fn a_leased_file_open_degrades_to_read_only_with_the_fallback_flag() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/leased/lease")
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"lease_conflict"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_read_only_fallback(true);
+
+        let handle = fs.acquire_write_lease("leased", 0).unwrap();
+        assert!(handle.read_only);
+        assert!(fs.check_handle_writable(&handle).is_err());
+        assert_eq!(fs.check_handle_writable(&handle), Err(FsError::BadFileDescriptor));
+    }
+
+    #[test]
+    fn opening_an_existing_file_for_write_seeds_dirty_with_its_current_content() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _lease_mock = server
+            .mock("POST", "/v1/nodes/n1/lease")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"node_id":"n1","lease_id":"lease-1"}"#)
+            .create();
+        let _node_mock = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"n1","name":"config.toml","node_type":"file","size":11,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#,
+            )
+            .create();
+        let _content_mock = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .with_status(200)
+            .with_body(b"hello world")
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        let handle = fs.acquire_write_lease("n1", libc::O_RDWR).unwrap();
+
+        assert!(handle.write_mode);
+        assert_eq!(handle.dirty, Some(b"hello world".to_vec()));
+        assert!(handle.needs_finalize_on_release());
+    }
+
+    #[test]
+    fn opening_an_existing_file_for_write_with_o_trunc_starts_from_an_empty_buffer() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _lease_mock = server
+            .mock("POST", "/v1/nodes/n1/lease")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"node_id":"n1","lease_id":"lease-1"}"#)
+            .create();
+        let _get_mock = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"f","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        let handle = fs.acquire_write_lease("n1", libc::O_WRONLY | libc::O_TRUNC).unwrap();
+
+        assert!(handle.write_mode);
+        assert_eq!(handle.dirty, Some(Vec::new()));
+    }
+
+    #[test]
+    fn rename_onto_an_existing_name_returns_eexist_not_ebusy() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/n1/rename")
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"already_exists"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        assert_eq!(fs.rename("n1", "parent", "dup", "/old-name"), Err(FsError::AlreadyExists));
+    }
+
+    #[test]
+    fn rename_blocked_by_a_lease_on_the_destination_parent_returns_ebusy() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/n1/rename")
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"lease_conflict"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        assert_eq!(fs.rename("n1", "parent", "moved", "/old-name"), Err(FsError::Conflict));
+    }
+
+    #[test]
+    fn rename_across_a_backend_boundary_returns_exdev_so_mv_falls_back_to_copy() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/n1/rename")
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"cross_device"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        assert_eq!(fs.rename("n1", "other-mount-root", "moved", "/old-name"), Err(FsError::CrossDevice));
+    }
+
+    #[test]
+    fn rename_onto_an_immutable_destination_returns_erofs() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/n1/rename")
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"immutable_target"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        assert_eq!(fs.rename("n1", "committed-snapshot", "moved", "/old-name"), Err(FsError::ReadOnlyFilesystem));
+    }
+
+    #[test]
+    fn a_second_deep_path_lookup_hits_the_path_cache() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let root_children = server
+            .mock("GET", "/v1/nodes/root/children")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"children":[{"id":"a-id","name":"a","node_type":"directory","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}],"next_cursor":null}"#)
+            .expect(1)
+            .create();
+        let a_children = server
+            .mock("GET", "/v1/nodes/a-id/children")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"children":[{"id":"b-id","name":"b","node_type":"file","size":4,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}],"next_cursor":null}"#)
+            .expect(1)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        fs.init_root(crate::node::Node {
+            id: "root".to_string(),
+            name: "/".to_string(),
+            node_type: crate::node::NodeType::Directory,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        });
+
+        let first = fs.resolve_path("/a/b").unwrap();
+        assert_eq!(first.unwrap().id, "b-id");
+
+        // Second lookup of the same deep path is served entirely from
+        // the cache: the mocks' `expect(1)` would fail `assert()` below
+        // if either were hit again.
+        let second = fs.resolve_path("/a/b").unwrap();
+        assert_eq!(second.unwrap().id, "b-id");
+
+        root_children.assert();
+        a_children.assert();
+    }
+
+    #[test]
+    fn an_out_of_band_create_is_discovered_via_negative_revalidation_before_the_negative_ttl_expires() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let still_missing = server
+            .mock("GET", "/v1/nodes/root/children")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"children":[],"next_cursor":null}"#)
+            .expect(1)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            // A long negative TTL: without revalidation, nothing would
+            // force a second lookup to go back to the backend.
+            AttrCache::new(std::time::Duration::from_secs(300)),
+            rt.handle().clone(),
+        )
+        .with_negative_revalidation_policy(crate::cache::NegativeRevalidationPolicy {
+            probability: 1.0,
+            min_interval: std::time::Duration::from_millis(0),
+        });
+        fs.init_root(crate::node::Node {
+            id: "root".to_string(),
+            name: "/".to_string(),
+            node_type: crate::node::NodeType::Directory,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        });
+
+        assert!(fs.resolve_path("/new.txt").unwrap().is_none());
+        still_missing.assert();
+
+        let now_present = server
+            .mock("GET", "/v1/nodes/root/children")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"children":[{"id":"new-id","name":"new.txt","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}],"next_cursor":null}"#)
+            .expect(1)
+            .create();
+
+        let found = fs.resolve_path("/new.txt").unwrap();
+        assert_eq!(found.unwrap().id, "new-id");
+        now_present.assert();
+    }
+
+    #[test]
+    fn an_ancestor_rename_invalidates_its_cached_descendant_paths() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/a-id/rename")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"a-id","name":"a-renamed","node_type":"directory","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        fs.cache.put(
+            "/a".to_string(),
+            Some(crate::node::Node {
+                id: "a-id".to_string(),
+                name: "a".to_string(),
+                node_type: crate::node::NodeType::Directory,
+                size: Some(0),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: std::collections::HashMap::new(),
+            }),
+        );
+        fs.cache.put(
+            "/a/b".to_string(),
+            Some(crate::node::Node {
+                id: "b-id".to_string(),
+                name: "b".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(4),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: std::collections::HashMap::new(),
+            }),
+        );
+
+        assert!(fs.rename("a-id", "root", "a-renamed", "/a").is_ok());
+
+        assert!(fs.cache.get("/a").is_none());
+        assert!(fs.cache.get("/a/b").is_none());
+    }
+
+    #[test]
+    fn security_capability_xattr_round_trips_exact_bytes_when_enabled() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_security_capability_xattr(true);
+        let ino = fs.inodes.map_id(
+            "/bin/app".to_string(),
+            crate::node::Node {
+                id: "n1".to_string(),
+                name: "app".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(0),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: std::collections::HashMap::new(),
+            },
+        );
+        // A realistic vfs_cap_data struct: non-UTF8, fixed-width binary.
+        let cap_value: Vec<u8> = vec![0x00, 0x00, 0x00, 0x02, 0xFF, 0x00, 0xAB, 0xCD];
+
+        fs.set_binary_xattr(ino, "security.capability", &cap_value)
+            .unwrap();
+
+        assert_eq!(
+            fs.get_binary_xattr(ino, "security.capability"),
+            Some(cap_value)
+        );
+    }
+
+    #[test]
+    fn security_capability_xattr_is_rejected_when_not_enabled() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let ino = fs.inodes.map_id(
+            "/bin/app".to_string(),
+            crate::node::Node {
+                id: "n1".to_string(),
+                name: "app".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(0),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: std::collections::HashMap::new(),
+            },
+        );
+
+        assert_eq!(
+            fs.set_binary_xattr(ino, "security.capability", &[0x01]),
+            Err(FsError::PermissionDenied)
+        );
+    }
+
+    #[test]
+    fn setting_several_xattrs_at_once_issues_a_single_coalesced_patch_call() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("PATCH", "/v1/nodes/n1/metadata")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"app","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{"xattr.user.a":"MQ==","xattr.user.b":"Mg=="}}"#)
+            .expect(1)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let ino = fs.inodes.map_id(
+            "/app".to_string(),
+            crate::node::Node {
+                id: "n1".to_string(),
+                name: "app".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(0),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: std::collections::HashMap::new(),
+            },
+        );
+
+        fs.set_binary_xattrs(
+            ino,
+            &[("user.a", b"1".as_slice()), ("user.b", b"2".as_slice())],
+        )
+        .unwrap();
+
+        assert_eq!(fs.get_binary_xattr(ino, "user.a"), Some(b"1".to_vec()));
+        assert_eq!(fs.get_binary_xattr(ino, "user.b"), Some(b"2".to_vec()));
+        mock.assert();
+    }
+
+    #[test]
+    fn a_concurrent_modification_between_capture_and_patch_is_retried_and_preserves_both_changes() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let mut metadata = std::collections::HashMap::new();
+        metadata.insert("version".to_string(), "v1".to_string());
+        metadata.insert("xattr.user.existing".to_string(), "ZXhpc3Rpbmc=".to_string());
+
+        let _stale_precondition = server
+            .mock("PATCH", "/v1/nodes/n1/metadata")
+            .match_header("if-match", "v1")
+            .with_status(412)
+            .create();
+        let _refetch = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"app","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{"version":"v2","xattr.user.existing":"ZXhpc3Rpbmc=","xattr.user.other":"b3RoZXI="}}"#)
+            .create();
+        let _retry_succeeds = server
+            .mock("PATCH", "/v1/nodes/n1/metadata")
+            .match_header("if-match", "v2")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"app","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{"version":"v3","xattr.user.existing":"ZXhpc3Rpbmc=","xattr.user.other":"b3RoZXI=","xattr.user.new":"bmV3"}}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let ino = fs.inodes.map_id(
+            "/app".to_string(),
+            crate::node::Node {
+                id: "n1".to_string(),
+                name: "app".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(0),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata,
+            },
+        );
+
+        fs.set_binary_xattrs(ino, &[("user.new", b"new".as_slice())])
+            .unwrap();
+
+        // The concurrent writer's "other" attribute and this write's
+        // "new" attribute both survive — neither clobbered the other.
+        assert_eq!(fs.get_binary_xattr(ino, "user.other"), Some(b"other".to_vec()));
+        assert_eq!(fs.get_binary_xattr(ino, "user.new"), Some(b"new".to_vec()));
+        assert_eq!(fs.get_binary_xattr(ino, "user.existing"), Some(b"existing".to_vec()));
+    }
+
+    #[test]
+    fn writeback_cache_is_only_requested_when_enabled() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        assert!(!fs.should_enable_writeback_cache());
+
+        let fs = fs.with_writeback_cache(true);
+        assert!(fs.should_enable_writeback_cache());
+    }
+
+    #[test]
+    fn attr_reports_configured_blksize_and_512_byte_blocks() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_block_size(65536);
+        let node = crate::node::Node {
+            id: "n1".to_string(),
+            name: "file.bin".to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(10_000),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        };
+
+        let attr = fs.node_to_attr(2, &node);
+
+        assert_eq!(attr.blksize, 65536);
+        assert_eq!(attr.blocks, 10_000u64.div_ceil(512));
+    }
+
+    #[test]
+    fn attr_blocks_reflects_stored_size_for_sparse_nodes() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let mut metadata = std::collections::HashMap::new();
+        metadata.insert("stored_size".to_string(), "1024".to_string());
+        let node = crate::node::Node {
+            id: "n1".to_string(),
+            name: "sparse.bin".to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(1_000_000),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata,
+        };
+
+        let attr = fs.node_to_attr(2, &node);
+
+        assert_eq!(attr.size, 1_000_000);
+        assert_eq!(attr.blocks, 1024u64.div_ceil(512));
+    }
+
+    #[test]
+    fn trash_directory_lists_deleted_nodes_and_supports_restore() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _listing = server
+            .mock("GET", "/v1/trash")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"nodes":[{"id":"n1","name":"deleted.txt","node_type":"file","size":4,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}]}"#)
+            .create();
+        let _restore = server
+            .mock("POST", "/v1/trash/n1/restore")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"deleted.txt","node_type":"file","size":4,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        assert!(RosetFs::is_trash_path("/.roset-trash"));
+        assert!(RosetFs::is_trash_path("/.roset-trash/deleted.txt"));
+        assert!(!RosetFs::is_trash_path("/regular.txt"));
+
+        let trash = fs.list_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].name, "deleted.txt");
+
+        let restored = fs.restore_from_trash("n1").unwrap();
+        assert_eq!(restored.id, "n1");
+    }
+
+    #[test]
+    fn statfs_reports_the_configured_capacity_and_known_inode_count() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_block_size(4096)
+        .with_reported_capacity_bytes(4096 * 1000);
+
+        let node = crate::node::Node {
+            id: "n1".to_string(),
+            name: "file.txt".to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        };
+        fs.inodes.map_id("/file.txt".to_string(), node);
+
+        let reply = fs.handle_statfs();
+        assert_eq!(reply.bsize, 4096);
+        assert_eq!(reply.frsize, 4096);
+        assert_eq!(reply.blocks, 1000);
+        assert_eq!(reply.bfree, 1000);
+        assert_eq!(reply.bavail, 1000);
+        // The one mapped inode, plus the (always-present) root.
+        assert_eq!(reply.files, 2);
+        assert!(reply.ffree > 0);
+    }
+
+    #[test]
+    fn max_write_bytes_defaults_to_the_upload_chunk_size_and_is_overridable() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        assert_eq!(fs.max_write_bytes, crate::upload::DEFAULT_MAX_WRITE_CHUNK as u32);
+
+        let fs = fs.with_max_write_bytes(1024 * 1024);
+        assert_eq!(fs.max_write_bytes, 1024 * 1024);
+    }
+
+    #[test]
+    fn max_readahead_kb_defaults_and_is_overridable_and_is_what_init_negotiation_requests() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        assert_eq!(fs.configured_max_readahead_kb(), DEFAULT_MAX_READAHEAD_KB);
+
+        let fs = fs.with_max_readahead_kb(2048);
+        assert_eq!(fs.configured_max_readahead_kb(), 2048);
+        assert_eq!(fs.max_readahead_kb, 2048);
+    }
+
+    #[test]
+    fn validate_max_readahead_kb_rejects_values_outside_the_kernel_negotiable_range() {
+        assert!(validate_max_readahead_kb(MIN_MAX_READAHEAD_KB).is_ok());
+        assert!(validate_max_readahead_kb(MAX_MAX_READAHEAD_KB).is_ok());
+        assert!(validate_max_readahead_kb(MIN_MAX_READAHEAD_KB - 1).is_err());
+        assert!(validate_max_readahead_kb(MAX_MAX_READAHEAD_KB + 1).is_err());
+    }
+
+    #[test]
+    fn conflict_policy_defaults_to_last_writer_wins_and_is_overridable() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let mut handle = Handle::new("n1".to_string());
+        handle.opened_version = Some("v1".to_string());
+
+        // Default (LastWriterWins): a mismatch still proceeds.
+        assert_eq!(
+            fs.resolve_write_conflict(&handle, Some("v2"), "data.bin", "c1"),
+            Ok(crate::conflict::ConflictOutcome::Proceed)
+        );
+
+        let fs = fs.with_conflict_policy(crate::conflict::ConflictPolicy::Fail);
+        assert_eq!(
+            fs.resolve_write_conflict(&handle, Some("v2"), "data.bin", "c1"),
+            Err(FsError::Conflict)
+        );
+    }
+
+    #[test]
+    fn last_writer_wins_flushes_without_checking_the_backends_current_version() {
+        // A strict mockito server (no mocks registered at all) means any
+        // request this flush makes would fail to match and surface as an
+        // error — so a flush that still succeeds proves
+        // `resolve_upload_target` skipped the `get_node` round trip
+        // entirely under the default policy.
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let server = mockito::Server::new();
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::with_upload_hook(
+                8,
+                1,
+                std::time::Duration::from_secs(3600),
+                None,
+                crate::staging::StagingRetryConfig::default(),
+                None,
+                Some(std::sync::Arc::new(|_: &crate::staging::StagingJob| {
+                    Box::pin(async { Ok(()) })
+                        as std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), String>> + Send>>
+                })),
+                rt.handle().clone(),
+            ),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_write_durability(WriteDurability::Sync);
+
+        let mut handle = Handle::new("n1".to_string());
+        handle.opened_version = Some("v1".to_string());
+        handle.dirty = Some(b"unsaved".to_vec());
+        fs.handles.lock().unwrap().insert(7, handle);
+
+        assert_eq!(fs.handle_flush(7), Ok(()));
+    }
+
+    #[test]
+    fn fail_policy_surfaces_a_racing_write_as_ebusy_not_eio() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _get_mock = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"f.txt","node_type":"file","size":7,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"etag":"v2","metadata":{}}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_write_durability(WriteDurability::Sync)
+        .with_conflict_policy(crate::conflict::ConflictPolicy::Fail);
+
+        let mut handle = Handle::new("n1".to_string());
+        handle.opened_version = Some("v1".to_string());
+        handle.dirty = Some(b"unsaved".to_vec());
+        fs.handles.lock().unwrap().insert(7, handle);
+
+        assert_eq!(fs.handle_flush(7), Err(FsError::Conflict));
+    }
+
+    #[test]
+    fn rename_loser_policy_redirects_a_racing_write_to_a_fresh_sibling_node() {
+        use std::sync::{Arc, Mutex};
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _get_mock = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"f.txt","node_type":"file","size":7,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"etag":"v2","metadata":{}}"#)
+            .create();
+        let _create_mock = server
+            .mock("POST", "/v1/nodes/parent/children")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n2","name":"f.txt.conflicted","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .create();
+
+        let uploaded_node_id = Arc::new(Mutex::new(None));
+        let hook_node_id = uploaded_node_id.clone();
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::with_upload_hook(
+                8,
+                1,
+                std::time::Duration::from_secs(3600),
+                None,
+                crate::staging::StagingRetryConfig::default(),
+                None,
+                Some(Arc::new(move |job: &crate::staging::StagingJob| {
+                    *hook_node_id.lock().unwrap() = Some(job.node_id.clone());
+                    Box::pin(async { Ok(()) })
+                        as std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), String>> + Send>>
+                })),
+                rt.handle().clone(),
+            ),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_write_durability(WriteDurability::Sync)
+        .with_conflict_policy(crate::conflict::ConflictPolicy::RenameLoser);
+
+        let mut handle = Handle::new("n1".to_string());
+        handle.opened_version = Some("v1".to_string());
+        handle.dirty = Some(b"unsaved".to_vec());
+        handle.name = Some("f.txt".to_string());
+        handle.parent_id = Some("parent".to_string());
+        fs.handles.lock().unwrap().insert(7, handle);
+
+        assert_eq!(fs.handle_flush(7), Ok(()));
+        assert_eq!(uploaded_node_id.lock().unwrap().as_deref(), Some("n2"));
+    }
+
+    #[test]
+    fn resolve_size_fetches_from_the_backend_when_the_node_has_no_size_yet() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("GET", "/v1/nodes/n1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"size":2048}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let node = crate::node::Node {
+            id: "n1".to_string(),
+            name: "pending.bin".to_string(),
+            node_type: crate::node::NodeType::File,
+            size: None,
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        };
+
+        assert_eq!(fs.resolve_size(&node), Ok(2048));
+    }
+
+    #[test]
+    fn a_sub_threshold_file_is_read_inline_without_a_download_url_call() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let content_mock = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .with_status(200)
+            .with_body("tiny")
+            .expect(1)
+            .create();
+        // No `get_download_url`-style mock is registered at all: if the
+        // inline path ever regressed into calling one, the request would
+        // hit mockito's unmatched-request 501 instead of this body.
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let node = crate::node::Node {
+            id: "n1".to_string(),
+            name: "config.json".to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(4),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        };
+
+        assert_eq!(fs.read_small_file_inline(&node), Ok(Some(b"tiny".to_vec())));
+        content_mock.assert();
+    }
+
+    #[test]
+    fn a_file_over_the_inline_threshold_is_left_for_the_normal_read_path() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_inline_content_threshold(16);
+        let node = crate::node::Node {
+            id: "n1".to_string(),
+            name: "big.bin".to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(17),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        };
+
+        assert_eq!(fs.read_small_file_inline(&node), Ok(None));
+    }
+
+    #[test]
+    fn a_block_cache_hit_serves_the_second_read_without_a_second_download_range_call() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let dir = std::env::temp_dir()
+            .join(format!("roset-fuse-fs-block-cache-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut server = mockito::Server::new();
+        let content_mock = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .match_header("range", "bytes=0-9")
+            .with_status(206)
+            .with_body("0123456789")
+            .expect(1)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_block_cache(Some(dir.clone()), 64 * 1024 * 1024)
+        .with_read_cache_policy(ReadCachePolicy::All);
+        let node = crate::node::Node {
+            id: "n1".to_string(),
+            name: "data.bin".to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(10),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        };
+
+        assert_eq!(fs.read_block(&node, 0), Ok(b"0123456789".to_vec()));
+        // Second read of the same block must come from the block cache,
+        // not a second request — `content_mock.expect(1)` would otherwise
+        // fail on drop.
+        assert_eq!(fs.read_block(&node, 0), Ok(b"0123456789".to_vec()));
+        content_mock.assert();
+
+        fs.invalidate_block_cache("n1");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn default_read_cache_policy_only_caches_immutable_nodes() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let dir = std::env::temp_dir()
+            .join(format!("roset-fuse-fs-read-cache-policy-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut server = mockito::Server::new();
+        let mutable_content = server
+            .mock("GET", "/v1/nodes/mutable/content")
+            .match_header("range", "bytes=0-9")
+            .with_status(206)
+            .with_body("0123456789")
+            .expect(2)
+            .create();
+        let immutable_content = server
+            .mock("GET", "/v1/nodes/immutable/content")
+            .match_header("range", "bytes=0-9")
+            .with_status(206)
+            .with_body("0123456789")
+            .expect(1)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_block_cache(Some(dir.clone()), 64 * 1024 * 1024);
+
+        let mutable_node = crate::node::Node {
+            id: "mutable".to_string(),
+            name: "data.bin".to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(10),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        };
+        let mut immutable_node = mutable_node.clone();
+        immutable_node.id = "immutable".to_string();
+        immutable_node.metadata.insert("committed".to_string(), "true".to_string());
+
+        // A mutable node's reads always bypass the cache under the
+        // default `ImmutableOnly` policy — two backend calls.
+        assert_eq!(fs.read_block(&mutable_node, 0), Ok(b"0123456789".to_vec()));
+        assert_eq!(fs.read_block(&mutable_node, 0), Ok(b"0123456789".to_vec()));
+        // An immutable node's reads are cached — only one backend call.
+        assert_eq!(fs.read_block(&immutable_node, 0), Ok(b"0123456789".to_vec()));
+        assert_eq!(fs.read_block(&immutable_node, 0), Ok(b"0123456789".to_vec()));
+
+        mutable_content.assert();
+        immutable_content.assert();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn two_same_second_writes_with_different_etags_are_cached_as_distinct_versions() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let first_fetch = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .with_status(200)
+            .with_body("v1 content")
+            .expect(1)
+            .create();
+
+        let cache_dir = std::env::temp_dir().join(format!(
+            "roset-fuse-test-inline-etag-cache-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_shared_cache_dir(Some(cache_dir.clone()));
+
+        // Both writes land in the same wall-clock second, so `mtime` alone
+        // can't tell them apart — only the etag can.
+        let mtime = std::time::SystemTime::now();
+        let v1 = crate::node::Node {
+            id: "n1".to_string(),
+            name: "config.json".to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(10),
+            mtime,
+            etag: Some("e1".to_string()),
+            metadata: std::collections::HashMap::new(),
+        };
+        assert_eq!(fs.read_small_file_inline(&v1), Ok(Some(b"v1 content".to_vec())));
+        first_fetch.assert();
+
+        let second_fetch = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .with_status(200)
+            .with_body("v2 content")
+            .expect(1)
+            .create();
+        let v2 = crate::node::Node {
+            id: "n1".to_string(),
+            name: "config.json".to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(10),
+            mtime,
+            etag: Some("e2".to_string()),
+            metadata: std::collections::HashMap::new(),
+        };
+
+        // A shared-cache hit keyed only on `node.id` would wrongly return
+        // the stale "v1 content" here; keying on the etag as well forces
+        // a fresh fetch instead.
+        assert_eq!(fs.read_small_file_inline(&v2), Ok(Some(b"v2 content".to_vec())));
+        second_fetch.assert();
+
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+
+    #[test]
+    fn an_inline_read_repairs_a_cached_block_corrupted_on_disk() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let refetch = server
+            .mock("GET", "/v1/nodes/n1/content")
+            .with_status(200)
+            .with_body("fresh content")
+            .expect(1)
+            .create();
+
+        let cache_dir = std::env::temp_dir().join(format!(
+            "roset-fuse-test-inline-repair-cache-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&cache_dir);
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        )
+        .with_shared_cache_dir(Some(cache_dir.clone()));
+
+        let node = crate::node::Node {
+            id: "n1".to_string(),
+            name: "config.json".to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(13),
+            mtime: std::time::SystemTime::now(),
+            etag: Some("e1".to_string()),
+            metadata: std::collections::HashMap::new(),
+        };
+
+        // Seed the shared cache directly with a block that's the wrong
+        // length, simulating on-disk corruption that happened after a
+        // previously-good write — not a failure this node has ever seen
+        // over the wire.
+        std::fs::create_dir_all(&cache_dir).unwrap();
+        std::fs::write(cache_dir.join("n1:e1"), b"truncated").unwrap();
+
+        assert_eq!(
+            fs.read_small_file_inline(&node),
+            Ok(Some(b"fresh content".to_vec()))
+        );
+        refetch.assert();
+
+        let _ = std::fs::remove_dir_all(&cache_dir);
+    }
+
+    #[test]
+    fn opening_a_directory_for_read_returns_eisdir() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let ino = fs.inodes.map_id(
+            "/dir".to_string(),
+            crate::node::Node {
+                id: "d1".to_string(),
+                name: "dir".to_string(),
+                node_type: crate::node::NodeType::Directory,
+                size: Some(0),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: std::collections::HashMap::new(),
+            },
+        );
+
+        assert_eq!(fs.check_not_a_directory(ino), Err(FsError::IsADirectory));
+    }
+
+    #[test]
+    fn verify_subtree_reports_a_size_mismatch_left_behind_by_a_stale_cache_entry() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _root_mock = server
+            .mock("GET", "/v1/nodes/root-id")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"root-id","name":"/","node_type":"directory","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .create();
+        let _children_mock = server
+            .mock("GET", "/v1/nodes/root-id/children")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"children":[{"id":"f1","name":"file.txt","node_type":"file","size":42,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}],"next_cursor":null}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(300)),
+            rt.handle().clone(),
+        );
+        fs.init_root(crate::node::Node {
+            id: "root-id".to_string(),
+            name: "/".to_string(),
+            node_type: crate::node::NodeType::Directory,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        });
+        // A stale entry left over from before the file was resized on the
+        // backend out from under this mount.
+        fs.cache.put(
+            "/file.txt".to_string(),
+            Some(crate::node::Node {
+                id: "f1".to_string(),
+                name: "file.txt".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(4),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: std::collections::HashMap::new(),
+            }),
+        );
+
+        let discrepancies = fs.verify_subtree("/").unwrap();
+
+        assert_eq!(
+            discrepancies,
+            vec![Discrepancy::SizeMismatch {
+                path: "/file.txt".to_string(),
+                cached: Some(4),
+                backend: Some(42),
+            }]
+        );
+        // The stale entry is still there afterwards — verify only reports,
+        // it never repairs the cache it inspected.
+        assert_eq!(fs.cache.get_allow_stale("/file.txt").unwrap().unwrap().size, Some(4));
+    }
+
+    #[test]
+    fn verify_subtree_reports_a_child_the_cache_never_saw() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _root_mock = server
+            .mock("GET", "/v1/nodes/root-id")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"root-id","name":"/","node_type":"directory","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .create();
+        let _children_mock = server
+            .mock("GET", "/v1/nodes/root-id/children")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"children":[{"id":"new-id","name":"new.txt","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}],"next_cursor":null}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(300)),
+            rt.handle().clone(),
+        );
+        fs.init_root(crate::node::Node {
+            id: "root-id".to_string(),
+            name: "/".to_string(),
+            node_type: crate::node::NodeType::Directory,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: std::collections::HashMap::new(),
+        });
+
+        let discrepancies = fs.verify_subtree("/").unwrap();
+
+        assert_eq!(
+            discrepancies,
+            vec![Discrepancy::MissingFromCache {
+                path: "/new.txt".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn symlink_on_an_existing_name_returns_eexist_not_ebusy() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut server = mockito::Server::new();
+        let _mock = server
+            .mock("POST", "/v1/nodes/parent/children")
+            .with_status(409)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"code":"already_exists"}"#)
+            .create();
+
+        let fs = RosetFs::new(
+            RosetClient::new(server.url()),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+
+        assert_eq!(fs.symlink("parent", "dup", "../target"), Err(FsError::AlreadyExists));
+    }
+
+    #[test]
+    fn readlink_returns_the_verbatim_stored_target() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let mut metadata = std::collections::HashMap::new();
+        metadata.insert("symlinkTarget".to_string(), "../relative/target".to_string());
+        let ino = fs.inodes.map_id(
+            "/link".to_string(),
+            crate::node::Node {
+                id: "n1".to_string(),
+                name: "link".to_string(),
+                node_type: crate::node::NodeType::Symlink,
+                size: Some(0),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata,
+            },
+        );
+
+        assert_eq!(fs.readlink(ino).unwrap(), "../relative/target");
+    }
+
+    #[test]
+    fn readlink_on_a_regular_file_returns_einval() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new("https://api.roset.dev"),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(std::time::Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        let ino = fs.inodes.map_id(
+            "/file.txt".to_string(),
+            crate::node::Node {
+                id: "n1".to_string(),
+                name: "file.txt".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(0),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: std::collections::HashMap::new(),
+            },
+        );
+
+        assert_eq!(fs.readlink(ino), Err(FsError::InvalidArgument));
+    }
+}
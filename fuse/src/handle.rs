@@ -0,0 +1,207 @@
+use crate::client::Lease;
+
+/// State tracked for an open file handle.
+#[derive(Debug)]
+pub struct Handle {
+    pub node_id: String,
+    pub dirty: Option<Vec<u8>>,
+    /// Byte ranges of `dirty` actually touched by a `write`, as
+    /// `(offset, len)`, coalesced as they're recorded by
+    /// [`Self::record_write`]. Lets `RosetFs::plan_write_upload` offer
+    /// the backend a patch of just the changed bytes instead of
+    /// re-uploading the whole buffer when the edit is small relative to
+    /// the file — see [`crate::upload::plan_upload_strategy`]. Stays
+    /// empty for a handle whose `dirty` was set some other way (e.g.
+    /// directly in a test), which conservatively falls back to a full
+    /// rewrite.
+    pub dirty_ranges: Vec<(u64, u64)>,
+    /// The node's content length as of this handle's `open`/`create`,
+    /// before any `write` on it: `0` for a brand new file, the length of
+    /// the downloaded content for a handle opened on an existing file
+    /// (see `RosetFs::acquire_write_lease`), or `None` for a handle whose
+    /// `dirty` was seeded some other way (e.g. directly in a test).
+    /// `RosetFs::plan_write_upload` needs this alongside `dirty_ranges` to
+    /// tell a small in-place edit from one that should just be rewritten
+    /// whole.
+    pub opened_size: Option<u64>,
+    /// Set by `RosetFs::handle_flush` while it has taken `dirty` out from
+    /// under the handles lock and is uploading it, so a concurrent
+    /// `flush` or `release` on the same (possibly dup'd) `fh` sees this
+    /// data as already owned by that in-flight upload instead of reading
+    /// and re-staging it a second time. Cleared once the upload finishes,
+    /// whether it succeeded or failed.
+    pub uploading: bool,
+    pub lease: Option<Lease>,
+    /// Set by `RosetFs::handle_create` under `CreateUploadMode::Eager`,
+    /// after it stages an empty upload for the new node and before any
+    /// `write` happens. A handle created but never written to
+    /// (`touch newfile`) still has that empty upload finalized on
+    /// `release`.
+    pub created_unwritten: bool,
+    /// The node's backend version at `open` time, captured so
+    /// `RosetFs::resolve_write_conflict` can tell at upload completion
+    /// whether another client modified the node out from under this
+    /// handle. `None` if the backend didn't report a version.
+    pub opened_version: Option<String>,
+    /// This handle's name and parent directory id at `open`/`create`
+    /// time, needed only to materialize a `--conflict-policy=rename-loser`
+    /// sibling file if this handle's upload turns out to have raced
+    /// another writer. `None` for a handle whose parent couldn't be
+    /// resolved (e.g. a test-constructed handle), in which case a
+    /// `RenameLoser` conflict degrades to `Fail` rather than guessing.
+    pub name: Option<String>,
+    pub parent_id: Option<String>,
+    /// Set when `open` wanted a write lease but another writer held it
+    /// and `--read-only-fallback` let the open degrade instead of
+    /// failing outright (see `RosetFs::acquire_write_lease`). Reads
+    /// still work; writes fail with `EBADF`.
+    pub read_only: bool,
+    /// End offset (`offset + len`) of the last `read` served on this
+    /// handle. `None` before this handle's first read. Compared against
+    /// the next read's starting offset by
+    /// [`crate::fs::RosetFs::plan_handle_read_ahead`] to tell a
+    /// sequential access pattern from a random one.
+    pub last_read_end: Option<u64>,
+    /// An mtime update recorded (e.g. by a deferred `setattr` under
+    /// writeback caching) but not yet sent to the backend. Only `fsync`'s
+    /// full (non-`datasync`) form is obligated to flush it — see
+    /// [`crate::fs::RosetFs::handle_fsync`].
+    pub pending_mtime: Option<std::time::SystemTime>,
+    /// Bytes already fetched ahead of an actual `read` request, as
+    /// `(offset, data)` — see [`crate::readahead::plan_read_ahead`]. Lets
+    /// a subsequent sequential `read` falling inside this window be
+    /// served without another round trip to the backend.
+    pub read_ahead_buf: Option<(u64, Vec<u8>)>,
+    /// Handle to the background task [`crate::fs::RosetFs::spawn_lease_renewal`]
+    /// started to keep `lease` alive for a long-running write. Aborted
+    /// once the lease itself is released, so it doesn't keep renewing a
+    /// lease nothing holds anymore. `None` for a handle with no lease, or
+    /// whose lease never expires.
+    pub renewal_task: Option<tokio::task::AbortHandle>,
+    /// Set by [`crate::fs::RosetFs::acquire_write_lease`] when this
+    /// handle was opened for `O_RDWR`/`O_WRONLY` on an existing file:
+    /// `dirty` was seeded with the file's current content (or left
+    /// empty, for `O_TRUNC`) up front rather than lazily on the first
+    /// `write`, so a handle opened read-write but never actually written
+    /// to still round-trips its (unmodified) content through
+    /// `write`/`fsync`/`release` the same way `created_unwritten` does
+    /// for a brand new file.
+    pub write_mode: bool,
+}
+
+impl Handle {
+    pub fn new(node_id: String) -> Self {
+        Self {
+            node_id,
+            dirty: None,
+            dirty_ranges: Vec::new(),
+            opened_size: None,
+            uploading: false,
+            lease: None,
+            created_unwritten: false,
+            opened_version: None,
+            name: None,
+            parent_id: None,
+            read_only: false,
+            last_read_end: None,
+            read_ahead_buf: None,
+            pending_mtime: None,
+            renewal_task: None,
+            write_mode: false,
+        }
+    }
+
+    /// Aborts and clears any in-flight lease renewal task, for callers
+    /// about to release `lease` itself — see [`Self::renewal_task`].
+    pub fn stop_lease_renewal(&mut self) {
+        if let Some(task) = self.renewal_task.take() {
+            task.abort();
+        }
+    }
+
+    /// Whether `release` needs to finalize this handle even though
+    /// `dirty` is empty: either it has pending data, or it was created
+    /// and never written to and so still needs its empty upload
+    /// completed.
+    pub fn needs_finalize_on_release(&self) -> bool {
+        self.dirty.is_some() || self.created_unwritten
+    }
+
+    /// Records that `[offset, offset + len)` was written, coalescing it
+    /// with any already-recorded range it overlaps or touches so a
+    /// sequence of adjacent small writes collapses into one range
+    /// instead of growing `dirty_ranges` without bound.
+    pub fn record_write(&mut self, offset: u64, len: u64) {
+        if len == 0 {
+            return;
+        }
+        let mut merged_start = offset;
+        let mut merged_end = offset + len;
+        self.dirty_ranges.retain(|&(start, len)| {
+            let end = start + len;
+            if start <= merged_end && merged_start <= end {
+                merged_start = merged_start.min(start);
+                merged_end = merged_end.max(end);
+                false
+            } else {
+                true
+            }
+        });
+        self.dirty_ranges.push((merged_start, merged_end - merged_start));
+    }
+
+    /// Returns the slice of `read_ahead_buf` covering `[offset, offset +
+    /// len)`, if the whole request falls inside it — see
+    /// [`crate::readahead::window_covers`]. `None` means the caller needs
+    /// to fetch fresh from the backend.
+    pub fn serve_from_read_ahead(&self, offset: u64, len: u64) -> Option<&[u8]> {
+        let (buf_offset, buf) = self.read_ahead_buf.as_ref()?;
+        if !crate::readahead::window_covers(*buf_offset, buf.len() as u64, offset, len) {
+            return None;
+        }
+        let start = (offset - buf_offset) as usize;
+        Some(&buf[start..start + len as usize])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_byte_create_still_needs_finalizing_on_release() {
+        let mut handle = Handle::new("n1".to_string());
+        handle.created_unwritten = true;
+        assert!(handle.needs_finalize_on_release());
+    }
+
+    #[test]
+    fn adjacent_writes_coalesce_into_one_dirty_range() {
+        let mut handle = Handle::new("n1".to_string());
+        handle.record_write(0, 10);
+        handle.record_write(10, 5);
+        assert_eq!(handle.dirty_ranges, vec![(0, 15)]);
+    }
+
+    #[test]
+    fn disjoint_writes_stay_as_separate_dirty_ranges() {
+        let mut handle = Handle::new("n1".to_string());
+        handle.record_write(0, 10);
+        handle.record_write(100, 10);
+        assert_eq!(handle.dirty_ranges, vec![(0, 10), (100, 10)]);
+    }
+
+    #[test]
+    fn a_read_inside_the_buffered_read_ahead_window_is_served_from_it() {
+        let mut handle = Handle::new("n1".to_string());
+        handle.read_ahead_buf = Some((1000, b"0123456789".to_vec()));
+        assert_eq!(handle.serve_from_read_ahead(1002, 4), Some(&b"2345"[..]));
+    }
+
+    #[test]
+    fn a_read_spilling_past_the_buffered_window_falls_back_to_none() {
+        let mut handle = Handle::new("n1".to_string());
+        handle.read_ahead_buf = Some((1000, b"0123456789".to_vec()));
+        assert_eq!(handle.serve_from_read_ahead(1005, 10), None);
+    }
+}
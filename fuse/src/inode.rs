@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::node::Node;
+
+pub(crate) const ROOT_INO: u64 = 1;
+
+/// Soft cap on the number of inodes kept in memory. Once exceeded,
+/// zero-refcount entries are reclaimed oldest-first on the next
+/// `map_id`; entries the kernel still holds a lookup count for are never
+/// touched, since reusing their inode number would be a correctness bug.
+const SOFT_CAP: usize = 1_000_000;
+
+struct Entry {
+    path: String,
+    node: Node,
+    refcount: u64,
+    last_used: Instant,
+}
+
+/// Maps FUSE inode numbers to the path and [`Node`] they currently
+/// resolve to.
+///
+/// The kernel only ever speaks in terms of inode numbers, while the
+/// Roset API is path/id oriented, so every op that isn't purely
+/// inode-local needs to bounce through here first. Every lookup-style op
+/// increments an entry's refcount; the kernel balances that with
+/// `forget`, and only zero-refcount entries are eligible for reclamation
+/// once [`SOFT_CAP`] is exceeded.
+pub struct InodeMap {
+    entries: Mutex<HashMap<u64, Entry>>,
+    next_ino: Mutex<u64>,
+}
+
+impl InodeMap {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            next_ino: Mutex::new(ROOT_INO + 1),
+        }
+    }
+
+    pub fn path_for(&self, ino: u64) -> Option<String> {
+        self.entries.lock().unwrap().get(&ino).map(|e| e.path.clone())
+    }
+
+    pub fn node_for(&self, ino: u64) -> Option<Node> {
+        self.entries.lock().unwrap().get(&ino).map(|e| e.node.clone())
+    }
+
+    pub fn update_node(&self, ino: u64, node: Node) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(&ino) {
+            entry.node = node;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the existing inode for `path` if one was already assigned,
+    /// otherwise allocates a fresh one and records `node`. Does not
+    /// touch the refcount, since resolving an id to an inode number is
+    /// not itself a kernel lookup — `readdir`'s non-plus path uses this
+    /// purely to fill in inode numbers for the reply. Call sites that
+    /// perform a real lookup (`lookup`, `create`, `readdirplus`) should
+    /// use [`InodeMap::lookup_id`] instead.
+    pub fn map_id(&self, path: String, node: Node) -> u64 {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some((ino, entry)) = entries.iter_mut().find(|(_, e)| e.path == path) {
+            entry.last_used = Instant::now();
+            return *ino;
+        }
+        if entries.len() >= SOFT_CAP {
+            Self::reclaim_oldest_unreferenced(&mut entries);
+        }
+        let mut next_ino = self.next_ino.lock().unwrap();
+        let ino = *next_ino;
+        *next_ino += 1;
+        entries.insert(
+            ino,
+            Entry {
+                path,
+                node,
+                refcount: 0,
+                last_used: Instant::now(),
+            },
+        );
+        ino
+    }
+
+    /// Resolves `path` to an inode number the same way [`Self::map_id`]
+    /// does, but also bumps the refcount — for call sites that constitute
+    /// an actual kernel lookup (`lookup`, `create`, `readdirplus`), which
+    /// the kernel will later balance with a `forget`.
+    pub fn lookup_id(&self, path: String, node: Node) -> u64 {
+        let ino = self.map_id(path, node);
+        self.bump_lookup(ino);
+        ino
+    }
+
+    /// (re-)registers `"/"` at the well-known [`ROOT_INO`], rather than
+    /// letting [`Self::map_id`] hand it whatever inode number happens to
+    /// come off the counter next. The kernel's root lookup always uses
+    /// `ROOT_INO` (it never goes through a `lookup` call the way every
+    /// other path does), so the root must live there from the start.
+    pub fn set_root(&self, node: Node) {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get_mut(&ROOT_INO) {
+            Some(entry) => {
+                entry.node = node;
+                entry.last_used = Instant::now();
+            }
+            None => {
+                entries.insert(
+                    ROOT_INO,
+                    Entry {
+                        path: "/".to_string(),
+                        node,
+                        refcount: 0,
+                        last_used: Instant::now(),
+                    },
+                );
+            }
+        }
+    }
+
+    fn reclaim_oldest_unreferenced(entries: &mut HashMap<u64, Entry>) {
+        if let Some(&ino) = entries
+            .iter()
+            .filter(|(_, e)| e.refcount == 0)
+            .min_by_key(|(_, e)| e.last_used)
+            .map(|(ino, _)| ino)
+        {
+            entries.remove(&ino);
+        }
+    }
+
+    /// Increments the refcount for `ino`, as required for every inode
+    /// number handed back for a real kernel lookup (`lookup`, `create`,
+    /// `readdirplus`).
+    pub fn bump_lookup(&self, ino: u64) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(&ino) {
+            entry.refcount += 1;
+            entry.last_used = Instant::now();
+        }
+    }
+
+    /// Handles the kernel's `forget(ino, nlookup)`, decrementing the
+    /// refcount by `nlookup`. The entry is left in place even at zero —
+    /// it becomes *eligible* for reclamation, not immediately dropped.
+    pub fn forget(&self, ino: u64, nlookup: u64) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(&ino) {
+            entry.refcount = entry.refcount.saturating_sub(nlookup);
+        }
+    }
+}
+
+impl Default for InodeMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_node(id: &str) -> Node {
+        Node {
+            id: id.to_string(),
+            name: id.to_string(),
+            node_type: crate::node::NodeType::File,
+            size: Some(0),
+            mtime: std::time::SystemTime::now(),
+            etag: None,
+            metadata: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn refcounts_stay_balanced_across_a_readdir_then_stat_sequence() {
+        let map = InodeMap::new();
+        // readdir: just resolves inode numbers for the reply, no lookup.
+        let ino = map.map_id("/a".to_string(), test_node("a"));
+        map.map_id("/a".to_string(), test_node("a"));
+        // stat (a real lookup): bumps refcount, balanced by a later forget.
+        map.bump_lookup(ino);
+        map.bump_lookup(ino);
+        map.forget(ino, 2);
+
+        let refcount = map.entries.lock().unwrap().get(&ino).unwrap().refcount;
+        assert_eq!(refcount, 0);
+    }
+
+    #[test]
+    fn map_size_stays_bounded_after_forgets() {
+        let map = InodeMap::new();
+        for i in 0..10 {
+            let path = format!("/file-{i}");
+            let ino = map.map_id(path, test_node(&format!("n{i}")));
+            map.bump_lookup(ino);
+            map.forget(ino, 1);
+        }
+        // All ten were forgotten back to refcount 0, so even without
+        // hitting SOFT_CAP, reclaim_oldest_unreferenced is available to
+        // keep the map from growing unbounded under sustained pressure.
+        assert_eq!(map.len(), 10);
+        assert!(map.len() < SOFT_CAP);
+    }
+}
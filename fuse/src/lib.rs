@@ -0,0 +1,21 @@
+pub mod block_cache;
+pub mod cache;
+pub mod circuit_breaker;
+pub mod client;
+pub mod conflict;
+pub mod dlq;
+pub mod error;
+pub mod filesystem;
+pub mod fs;
+pub mod handle;
+pub mod inode;
+pub mod logging;
+pub mod mount;
+pub mod node;
+pub mod notify;
+pub mod poll;
+pub mod readahead;
+pub mod retry;
+pub mod shared_cache;
+pub mod staging;
+pub mod upload;
@@ -0,0 +1,177 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Default cap on a single log file's size before [`RotatingFileWriter`]
+/// rolls over, overridable via `--log-max-bytes-per-file`.
+pub const DEFAULT_MAX_LOG_FILE_BYTES: u64 = 64 * 1024 * 1024;
+
+/// The number of whole days since the Unix epoch `t` falls on, used to
+/// detect a day boundary crossing without pulling in a calendar crate —
+/// good enough for "roll over at most once a day", which doesn't need
+/// to know what day it actually is.
+fn day_number(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / 86_400
+}
+
+/// A `std::io::Write` sink that rotates its backing file once it would
+/// exceed `max_bytes`, or once a day boundary is crossed since it was
+/// last opened, whichever comes first. The current file is renamed
+/// aside to the first free `<path>.<n>` slot (`n` increasing, so the
+/// newest rotated file always sorts last) rather than truncated, so
+/// nothing already written is lost.
+///
+/// Backs the FUSE process's `--log-file`: when spawned detached by the
+/// CSI node plugin's `spawn_fuse_process` (see `csi::node`), raw
+/// stdout/stderr go to a separate `.stdio.log` capture file, and this
+/// gives the process's own structured logging a durable, rotated file
+/// of its own.
+pub struct RotatingFileWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    file: File,
+    written: u64,
+    opened_day: u64,
+}
+
+impl RotatingFileWriter {
+    pub fn new(path: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_bytes,
+            file,
+            written,
+            opened_day: day_number(SystemTime::now()),
+        })
+    }
+
+    fn rotated_path(path: &Path, n: u64) -> PathBuf {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let mut n = 1u64;
+        loop {
+            let candidate = Self::rotated_path(&self.path, n);
+            if !candidate.exists() {
+                fs::rename(&self.path, &candidate)?;
+                break;
+            }
+            n += 1;
+        }
+        self.file = OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        self.written = 0;
+        self.opened_day = day_number(SystemTime::now());
+        Ok(())
+    }
+}
+
+impl Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let due_to_size = self.written > 0 && self.written + buf.len() as u64 > self.max_bytes;
+        let due_to_day = day_number(SystemTime::now()) != self.opened_day;
+        if due_to_size || due_to_day {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Thread-safe line logger over a [`RotatingFileWriter`], for processes
+/// (like `roset-fuse`) that log from more than one thread/task. Each
+/// call to [`Self::log`] is one line, timestamped so entries remain
+/// orderable after rotation has split a session across several files.
+pub struct FileLogger {
+    writer: Mutex<RotatingFileWriter>,
+}
+
+impl FileLogger {
+    pub fn new(path: PathBuf, max_bytes: u64) -> io::Result<Self> {
+        Ok(Self { writer: Mutex::new(RotatingFileWriter::new(path, max_bytes)?) })
+    }
+
+    /// Writes `message` as one line, prefixed with the current Unix
+    /// timestamp in milliseconds.
+    pub fn log(&self, message: &str) {
+        let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+        let mut writer = self.writer.lock().unwrap();
+        let _ = writeln!(writer, "{millis} {message}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("roset-fuse-logging-test-{name}-{}", std::process::id()))
+    }
+
+    #[test]
+    fn writes_below_the_size_threshold_stay_in_a_single_file() {
+        let dir = test_dir("small");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("roset-fuse.log");
+
+        let mut writer = RotatingFileWriter::new(path.clone(), 1024).unwrap();
+        writer.write_all(b"hello\n").unwrap();
+        writer.flush().unwrap();
+
+        assert!(path.exists());
+        assert!(!RotatingFileWriter::rotated_path(&path, 1).exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello\n");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn writes_past_the_size_threshold_rotate_the_file_aside() {
+        let dir = test_dir("rotate");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("roset-fuse.log");
+
+        let mut writer = RotatingFileWriter::new(path.clone(), 10).unwrap();
+        writer.write_all(b"0123456789").unwrap();
+        writer.write_all(b"overflow").unwrap();
+        writer.flush().unwrap();
+
+        let rotated = RotatingFileWriter::rotated_path(&path, 1);
+        assert!(rotated.exists());
+        assert_eq!(fs::read_to_string(&rotated).unwrap(), "0123456789");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "overflow");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn a_file_logger_writes_timestamped_lines_and_rotates_at_the_threshold() {
+        let dir = test_dir("logger");
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("roset-fuse.log");
+
+        let logger = FileLogger::new(path.clone(), 20).unwrap();
+        logger.log("first message");
+        logger.log("second message");
+
+        assert!(RotatingFileWriter::rotated_path(&path, 1).exists());
+        let current = fs::read_to_string(&path).unwrap();
+        assert!(current.ends_with("second message\n"));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,632 @@
+use std::time::Duration;
+
+use clap::Parser;
+use roset_fuse::cache::AttrCache;
+use roset_fuse::client::{
+    build_user_agent, HttpPoolConfig, RosetClient, DEFAULT_CIRCUIT_BREAKER_COOLDOWN,
+    DEFAULT_CIRCUIT_BREAKER_THRESHOLD,
+};
+use roset_fuse::conflict::ConflictPolicy;
+use roset_fuse::fs::{ReadCachePolicy, RosetFs, WriteDurability};
+use roset_fuse::staging::{
+    client_upload_hook, client_upload_hook_with_part_size, StagingManager, StagingRetryConfig,
+    DEFAULT_MAX_CONCURRENT_UPLOADS, DEFAULT_REPORT_INTERVAL, DEFAULT_STAGING_QUEUE_CAPACITY,
+};
+
+#[derive(Parser, Debug)]
+#[command(name = "roset-fuse")]
+struct Cli {
+    #[arg(long)]
+    volume_id: String,
+
+    /// Id of the node to serve as the mount's root directory. Usually
+    /// the volume's own root node, but kept distinct from `--volume-id`
+    /// (which is only used for telemetry/lease attribution) the same
+    /// way `roset-fuse verify` already separates `--volume-id` from
+    /// `--root-node-id`.
+    #[arg(long)]
+    root_node_id: String,
+
+    #[arg(long)]
+    mount_point: String,
+
+    #[arg(long, default_value = "https://api.roset.dev")]
+    api_base_url: String,
+
+    #[arg(long, default_value_t = 32)]
+    http_pool_max_idle_per_host: usize,
+
+    #[arg(long, default_value_t = 90)]
+    http_pool_idle_timeout_secs: u64,
+
+    #[arg(long, default_value_t = DEFAULT_STAGING_QUEUE_CAPACITY)]
+    staging_queue_capacity: usize,
+
+    /// Interval at which a staging-queue health summary (pending jobs,
+    /// pending bytes, uploads since last report, DLQ size) is logged.
+    #[arg(long, default_value_t = DEFAULT_REPORT_INTERVAL.as_secs())]
+    staging_report_interval_secs: u64,
+
+    /// Uploads in flight at once from the staging queue. Jobs for the
+    /// same node still complete in the order they were staged regardless
+    /// of this value, so raising it only adds throughput across different
+    /// files, never reorders durability-dependent writes to one file.
+    #[arg(long, default_value_t = DEFAULT_MAX_CONCURRENT_UPLOADS)]
+    staging_max_concurrent_uploads: usize,
+
+    /// Optimizes staging for large, sequential, write-once files (ML
+    /// checkpoints, dataset shards): raises upload concurrency (taking
+    /// `--staging-max-concurrent-uploads` if that's already set higher)
+    /// so more staged files can upload at once instead of serializing
+    /// behind each other, and, for a job that goes through multipart at
+    /// all (see `roset_fuse::staging::MULTIPART_MIN_SIZE`), carves it
+    /// into `roset_fuse::upload::CHECKPOINT_OPTIMIZED_PART_SIZE` parts
+    /// uploaded `CHECKPOINT_OPTIMIZED_CONCURRENCY`-wide instead of the
+    /// staging default. Still buffers a handle's writes fully in memory
+    /// until `fsync`/`release` before any part is sent — streaming a
+    /// file's parts to the backend as it's written would need buffering
+    /// writes to disk instead, a larger change than this flag makes.
+    #[arg(long)]
+    checkpoint_optimized: bool,
+
+    /// Attempts before a staging upload gives up and drops the job to the
+    /// dead-letter queue.
+    #[arg(long, default_value_t = StagingRetryConfig::default().max_attempts)]
+    staging_max_attempts: u32,
+
+    /// Upper bound on the jittered backoff between staging upload retry
+    /// attempts.
+    #[arg(long, default_value_t = StagingRetryConfig::default().max_backoff.as_secs())]
+    staging_max_backoff_secs: u64,
+
+    /// Age after which a DLQ (`staging/failed`) entry is purged. Unset
+    /// disables age-based purging.
+    #[arg(long)]
+    dlq_max_age_days: Option<u64>,
+
+    /// Total size the DLQ may occupy before the oldest entries are purged
+    /// to make room. Unset disables size-based purging.
+    #[arg(long)]
+    dlq_max_bytes: Option<u64>,
+
+    /// How often the DLQ retention sweep runs.
+    #[arg(long, default_value_t = 3600)]
+    dlq_sweep_interval_secs: u64,
+
+    #[arg(long, default_value_t = false)]
+    commit_on_unmount: bool,
+
+    #[arg(long)]
+    user_agent_suffix: Option<String>,
+
+    #[arg(long, default_value_t = false)]
+    allow_offline: bool,
+
+    /// When a write lease can't be acquired because another writer
+    /// already holds it, degrade the open to a read-only handle instead
+    /// of failing it with `EBUSY`.
+    #[arg(long, default_value_t = false)]
+    read_only_fallback: bool,
+
+    /// Bypass the kernel page cache for every file, immutable ones included, by
+    /// replying to `open`/`create` with `FOPEN_DIRECT_IO`. Trades away
+    /// re-read performance for read-your-writes coherency when multiple
+    /// processes (or multiple mounts) touch the same file concurrently.
+    #[arg(long, default_value_t = false)]
+    direct_io: bool,
+
+    /// Allow the `security.capability` xattr to round-trip through the
+    /// mount. Off by default since a shared mount lets any writer grant
+    /// a binary capabilities that any reader can then execute.
+    #[arg(long, default_value_t = false)]
+    allow_security_capability_xattr: bool,
+
+    /// Negotiate `FUSE_WRITEBACK_CACHE` with the kernel so it coalesces
+    /// small writes before sending them down, at the cost of relaxed
+    /// write/flush ordering guarantees.
+    #[arg(long, default_value_t = false)]
+    enable_writeback_cache: bool,
+
+    /// Node-local directory shared across every mount of the same
+    /// dataset on this node, keyed by node id and version, to deduplicate
+    /// downloads across co-located pods. Identical content under
+    /// different node ids is not deduplicated. Unset disables the shared
+    /// cache.
+    #[arg(long)]
+    shared_cache_dir: Option<std::path::PathBuf>,
+
+    /// Directory (ideally a fast local NVMe) this mount's own read cache
+    /// of file content blocks is stored in (`cacheDir`). Unset disables
+    /// the block cache, leaving every read served straight from the
+    /// backend, as before this cache existed.
+    #[arg(long)]
+    cache_dir: Option<std::path::PathBuf>,
+
+    /// Size budget (`cacheSizeGi`, converted to MiB) for `--cache-dir`'s
+    /// block cache, LRU-evicted once exceeded.
+    #[arg(long, default_value_t = 1024)]
+    cache_size_mb: u64,
+
+    /// Which reads `--cache-dir`'s block cache may serve from local disk:
+    /// `immutable-only` (the default) only caches committed/snapshot
+    /// nodes, `all` also caches mutable nodes for a short TTL, and `none`
+    /// disables the block cache outright regardless of `--cache-dir`.
+    #[arg(long, value_enum, default_value = "immutable-only")]
+    read_cache_policy: ReadCachePolicy,
+
+    /// Synthetic total capacity reported by `statfs`, since the Roset API
+    /// has no real capacity endpoint to query yet. `df` and training
+    /// frameworks that preflight free space before writing see this much
+    /// room, always reported as entirely free.
+    #[arg(long, default_value_t = roset_fuse::fs::DEFAULT_REPORTED_CAPACITY_GB)]
+    reported_capacity_gb: u64,
+
+    /// Preferred I/O size reported as `st_blksize` in `getattr`.
+    #[arg(long, default_value_t = 128 * 1024)]
+    block_size: u32,
+
+    /// Max single-write size negotiated with the kernel. Writes are not
+    /// chunked by it; they land in an in-memory buffer, not a temp file.
+    #[arg(long, default_value_t = roset_fuse::upload::DEFAULT_MAX_WRITE_CHUNK / 1024)]
+    max_write_kb: usize,
+
+    /// Size of the background prefetch window kicked off once a file
+    /// handle's reads look sequential (see
+    /// [`roset_fuse::readahead::plan_read_ahead`]). `0` disables
+    /// read-ahead, serving every `read` with exactly the bytes requested.
+    #[arg(long, default_value_t = roset_fuse::readahead::DEFAULT_READ_AHEAD_BYTES / 1024)]
+    read_ahead_kb: u64,
+
+    /// Max readahead size requested from the kernel itself at `init`
+    /// time (`readAhead`), separate from `--read-ahead-kb`'s
+    /// application-level sequential-prefetch window: this one bounds how
+    /// much the kernel's own page-cache readahead will ask of us per
+    /// `read`. See [`roset_fuse::fs::validate_max_readahead_kb`] for the
+    /// accepted range.
+    #[arg(long, default_value_t = roset_fuse::fs::DEFAULT_MAX_READAHEAD_KB)]
+    max_readahead_kb: u32,
+
+    /// Consecutive-failure threshold before the client's circuit breaker
+    /// opens and fast-fails requests instead of retrying into an
+    /// ongoing backend outage.
+    #[arg(long, default_value_t = DEFAULT_CIRCUIT_BREAKER_THRESHOLD)]
+    circuit_breaker_threshold: u32,
+
+    #[arg(long, default_value_t = DEFAULT_CIRCUIT_BREAKER_COOLDOWN.as_secs())]
+    circuit_breaker_cooldown_secs: u64,
+
+    /// Warns on stderr when a single backend call (including its
+    /// internal retries) takes longer than this, naming the operation
+    /// and the elapsed time, so "the mount is slow" reports can be
+    /// traced back to a specific backend call. Unset disables the
+    /// warning.
+    #[arg(long)]
+    slow_op_threshold_ms: Option<u64>,
+
+    /// Worker thread count for the Tokio runtime backing async FUSE
+    /// work. Defaults to the Tokio default (the number of CPUs) when
+    /// unset.
+    #[arg(long)]
+    async_worker_threads: Option<usize>,
+
+    /// How to resolve a write whose pre-write version no longer matches
+    /// the backend's current version at upload completion, i.e. another
+    /// client modified or deleted the node after this handle's `open`.
+    #[arg(long, value_enum, default_value = "last-writer-wins")]
+    conflict_policy: ConflictPolicy,
+
+    /// Whether a dirty write's upload must complete before `flush`
+    /// returns (`sync`, making a failed upload surface through
+    /// `close()`), or is left to finish in the background (`async`, the
+    /// default).
+    #[arg(long, value_enum, default_value = "async")]
+    write_durability: WriteDurability,
+
+    /// Path to a JSON array of additional mounts (each with its own
+    /// `mount_id`/`root_node_id`/`mount_point`) to serve from this same
+    /// process and runtime, sharing this process's HTTP connection pool
+    /// instead of each mount needing its own `roset-fuse` process.
+    #[arg(long)]
+    additional_mounts_config: Option<std::path::PathBuf>,
+
+    /// Path to a JSON array of `{prefix, immutable, ttl_secs}` cache
+    /// policy overrides, e.g. a huge TTL for an immutable `datasets/`
+    /// prefix alongside a near-zero TTL for a volatile `scratch/` prefix
+    /// served by the same mount.
+    #[arg(long)]
+    cache_policy_config: Option<std::path::PathBuf>,
+
+    /// Directory staged write data is buffered in before upload. Rejected
+    /// at startup if it's nested inside `--mount-point`.
+    #[arg(long, default_value = roset_fuse::staging::DEFAULT_STAGING_DIR)]
+    staging_dir: std::path::PathBuf,
+
+    /// Glob (e.g. `.roset*`, `*.tmp`) matched against entry names to hide
+    /// them from `readdir`/`readdirplus` and, unless
+    /// `--allow-hidden-lookup` is set, make `lookup` of them fail with
+    /// `ENOENT`. Writes can still create a matching name; it just won't
+    /// be listed. May be repeated.
+    #[arg(long, alias = "hide-glob")]
+    ignore_glob: Vec<String>,
+
+    /// Keep `--ignore-glob`/`--hide-glob` entries directly accessible by
+    /// exact name (`lookup`/`open` still work) while still hiding them
+    /// from `readdir`.
+    #[arg(long, default_value_t = false)]
+    allow_hidden_lookup: bool,
+
+    /// Omit `MountOption::DefaultPermissions`, letting every local access
+    /// through to this filesystem instead of having the kernel enforce
+    /// the (possibly meaningless) reported uid/gid/mode first.
+    ///
+    /// Security: only set this in a multi-tenant setup where the backend
+    /// enforces authorization out of band (e.g. per API key) and is
+    /// trusted to reject unauthorized requests on its own — without
+    /// `DefaultPermissions`, any local user who can reach the mountpoint
+    /// can read/write through it regardless of the reported mode bits.
+    #[arg(long, default_value_t = false)]
+    no_default_permissions: bool,
+
+    /// Allow-list of hosts (exact, or `*.domain` suffix patterns) content
+    /// transfers are permitted to reach. Unset disables the check
+    /// entirely. Guards against a compromised or misconfigured backend
+    /// steering a download at an unexpected (e.g. internal) host. May be
+    /// repeated.
+    #[arg(long)]
+    allowed_storage_hosts: Vec<String>,
+
+    /// Tees this process's status/error logging to a file at `path`,
+    /// rotating it once it exceeds `--log-max-bytes-per-file` (see
+    /// [`roset_fuse::logging::RotatingFileWriter`]). Unset keeps logging
+    /// on stderr only, which is fine for an interactively-run mount; a
+    /// mount spawned by the CSI node plugin has its stdout/stderr
+    /// captured to a separate `.stdio.log` file and is also passed this
+    /// flag.
+    #[arg(long)]
+    log_file: Option<std::path::PathBuf>,
+
+    #[arg(long, default_value_t = roset_fuse::logging::DEFAULT_MAX_LOG_FILE_BYTES)]
+    log_max_bytes_per_file: u64,
+}
+
+#[derive(serde::Deserialize)]
+struct CachePolicyConfigEntry {
+    prefix: String,
+    #[serde(default)]
+    immutable: bool,
+    ttl_secs: u64,
+}
+
+fn load_cache_prefix_policies(path: &std::path::Path) -> Vec<roset_fuse::cache::PrefixPolicy> {
+    let contents = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("failed to read {}: {e}", path.display()));
+    let entries: Vec<CachePolicyConfigEntry> = serde_json::from_str(&contents)
+        .unwrap_or_else(|e| panic!("invalid cache policy config: {e}"));
+    entries
+        .into_iter()
+        .map(|e| roset_fuse::cache::PrefixPolicy {
+            prefix: e.prefix,
+            policy: if e.immutable {
+                roset_fuse::cache::CachePolicy::Immutable
+            } else {
+                roset_fuse::cache::CachePolicy::Ttl
+            },
+            ttl: Duration::from_secs(e.ttl_secs),
+        })
+        .collect()
+}
+
+#[derive(serde::Deserialize)]
+struct AdditionalMountConfig {
+    mount_id: String,
+    root_node_id: String,
+    mount_point: std::path::PathBuf,
+}
+
+fn load_additional_mounts(path: &std::path::Path) -> Vec<roset_fuse::mount::MountSpec> {
+    let contents = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("failed to read {}: {e}", path.display()));
+    let configs: Vec<AdditionalMountConfig> = serde_json::from_str(&contents)
+        .unwrap_or_else(|e| panic!("invalid additional mounts config: {e}"));
+    configs
+        .into_iter()
+        .map(|c| roset_fuse::mount::MountSpec {
+            mount_id: c.mount_id,
+            root_node_id: c.root_node_id,
+            mount_point: c.mount_point,
+            staging_queue_capacity: DEFAULT_STAGING_QUEUE_CAPACITY,
+            cache_ttl: Duration::from_secs(30),
+            commit_on_unmount: false,
+        })
+        .collect()
+}
+
+/// `roset-fuse verify <path>` — a diagnostic sibling to the mount command,
+/// parsed separately in [`main`] rather than folded into [`Cli`] since it
+/// doesn't mount anything and needs none of `Cli`'s staging/cache/mount
+/// flags.
+#[derive(Parser, Debug)]
+#[command(name = "roset-fuse verify")]
+struct VerifyArgs {
+    /// Path to check, e.g. `/datasets/foo`. Only this path and its direct
+    /// children are compared; see
+    /// [`roset_fuse::fs::RosetFs::verify_subtree`].
+    path: String,
+
+    #[arg(long, default_value = "https://api.roset.dev")]
+    api_base_url: String,
+
+    /// The backend node id `path` is resolved relative to, i.e. the same
+    /// id a mount of this volume would have been given as its root.
+    #[arg(long)]
+    root_node_id: String,
+
+    #[arg(long, default_value = "roset-fuse-verify")]
+    volume_id: String,
+}
+
+/// Fetches a fresh copy of `args.path`'s subtree straight from the
+/// backend and compares it against what a mount would have cached,
+/// printing any mismatch — see
+/// [`roset_fuse::fs::RosetFs::verify_subtree`] for what counts as one.
+///
+/// Builds its own short-lived, empty-cache [`RosetFs`] rather than
+/// attaching to an already-running mount's process: there's no RPC
+/// between a `roset-fuse` process and a separate CLI invocation, only the
+/// xattr-based control channel the CSI node plugin uses on the mount
+/// itself (see [`roset_fuse::fs::RosetFs::ready_xattr`]). That makes this
+/// command most useful against a deliberately pre-seeded cache (e.g. one
+/// populated by replaying a saved dump into it) rather than a live
+/// mount's in-memory state, until this process model grows a way to
+/// attach to one.
+fn run_verify(args: VerifyArgs) {
+    let runtime = build_runtime(None);
+    let client = RosetClient::new(args.api_base_url).with_mount_id(args.volume_id);
+    let fs = RosetFs::new(
+        client.clone(),
+        StagingManager::new(DEFAULT_STAGING_QUEUE_CAPACITY, runtime.handle().clone()),
+        AttrCache::new(Duration::from_secs(30)),
+        runtime.handle().clone(),
+    );
+
+    let root = runtime
+        .block_on(client.get_node(&args.root_node_id))
+        .unwrap_or_else(|e| panic!("failed to fetch root node {}: {e}", args.root_node_id));
+    fs.init_root(root);
+
+    let discrepancies = fs.verify_subtree(&args.path).unwrap_or_else(|e| panic!("verify failed: {e:?}"));
+
+    if discrepancies.is_empty() {
+        println!("roset-fuse verify: {} matches the backend", args.path);
+        return;
+    }
+    for discrepancy in &discrepancies {
+        println!("{discrepancy:?}");
+    }
+    std::process::exit(1);
+}
+
+fn build_runtime(worker_threads: Option<usize>) -> tokio::runtime::Runtime {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all();
+    if let Some(n) = worker_threads {
+        builder.worker_threads(n);
+    }
+    builder.build().expect("failed to start tokio runtime")
+}
+
+/// Writes `message` to stderr and, if `--log-file` was set, also to the
+/// rotating log file, so a detached mount's status/error lines survive
+/// the process past a crash without losing the interactive stderr view
+/// this always had.
+fn log_line(logger: &Option<std::sync::Arc<roset_fuse::logging::FileLogger>>, message: &str) {
+    eprintln!("{message}");
+    if let Some(logger) = logger {
+        logger.log(message);
+    }
+}
+
+fn main() {
+    let mut raw_args = std::env::args();
+    let bin = raw_args.next().unwrap_or_else(|| "roset-fuse".to_string());
+    let rest: Vec<String> = raw_args.collect();
+    if rest.first().map(String::as_str) == Some("verify") {
+        let verify_args = VerifyArgs::parse_from(std::iter::once(format!("{bin} verify")).chain(rest.into_iter().skip(1)));
+        run_verify(verify_args);
+        return;
+    }
+
+    let cli = Cli::parse();
+
+    let logger = cli.log_file.as_ref().map(|path| {
+        std::sync::Arc::new(
+            roset_fuse::logging::FileLogger::new(path.clone(), cli.log_max_bytes_per_file)
+                .unwrap_or_else(|e| panic!("failed to open --log-file {}: {e}", path.display())),
+        )
+    });
+
+    if let Err(e) = roset_fuse::staging::validate_staging_dir(&cli.staging_dir, std::path::Path::new(&cli.mount_point)) {
+        panic!("{e}");
+    }
+    if let Err(e) = roset_fuse::fs::validate_max_readahead_kb(cli.max_readahead_kb) {
+        panic!("{e}");
+    }
+    let ignore_globs: Vec<glob::Pattern> = cli
+        .ignore_glob
+        .iter()
+        .map(|pattern| glob::Pattern::new(pattern).unwrap_or_else(|e| panic!("invalid --ignore-glob {pattern:?}: {e}")))
+        .collect();
+
+    let runtime = build_runtime(cli.async_worker_threads);
+    let client = RosetClient::with_pool_config_and_user_agent(
+        cli.api_base_url,
+        HttpPoolConfig {
+            max_idle_per_host: cli.http_pool_max_idle_per_host,
+            idle_timeout: Duration::from_secs(cli.http_pool_idle_timeout_secs),
+        },
+        build_user_agent(&cli.volume_id, cli.user_agent_suffix.as_deref()),
+    )
+    .with_mount_id(cli.volume_id.clone())
+    .with_circuit_breaker(
+        cli.circuit_breaker_threshold,
+        Duration::from_secs(cli.circuit_breaker_cooldown_secs),
+    )
+    .with_allowed_storage_hosts(cli.allowed_storage_hosts);
+    let client = match cli.slow_op_threshold_ms {
+        Some(ms) => client.with_slow_op_threshold(Duration::from_millis(ms), None),
+        None => client,
+    };
+    let dlq_config = if cli.dlq_max_age_days.is_some() || cli.dlq_max_bytes.is_some() {
+        Some(roset_fuse::staging::DlqConfig {
+            dir: cli.staging_dir.join("failed"),
+            retention: roset_fuse::dlq::DlqRetentionPolicy {
+                max_age: cli.dlq_max_age_days.map(|days| Duration::from_secs(days * 24 * 3600)),
+                max_bytes: cli.dlq_max_bytes,
+            },
+            sweep_interval: Duration::from_secs(cli.dlq_sweep_interval_secs),
+        })
+    } else {
+        None
+    };
+    let max_concurrent_uploads = if cli.checkpoint_optimized {
+        cli.staging_max_concurrent_uploads.max(roset_fuse::upload::CHECKPOINT_OPTIMIZED_CONCURRENCY)
+    } else {
+        cli.staging_max_concurrent_uploads
+    };
+    let staging = StagingManager::with_upload_hook(
+        cli.staging_queue_capacity,
+        max_concurrent_uploads,
+        Duration::from_secs(cli.staging_report_interval_secs),
+        Some({
+            let logger = logger.clone();
+            std::sync::Arc::new(move |report: roset_fuse::staging::StagingReport| {
+                log_line(
+                    &logger,
+                    &format!(
+                        "roset-fuse: staging health: pending_jobs={} pending_bytes={} uploaded={} dlq_size={} throughput_bps={:.0}",
+                        report.pending_jobs, report.pending_bytes, report.uploaded_since_last_report, report.dlq_size, report.throughput_bps
+                    ),
+                );
+            })
+        }),
+        StagingRetryConfig {
+            max_attempts: cli.staging_max_attempts,
+            max_backoff: Duration::from_secs(cli.staging_max_backoff_secs),
+        },
+        dlq_config,
+        Some(if cli.checkpoint_optimized {
+            client_upload_hook_with_part_size(
+                client.clone(),
+                roset_fuse::upload::CHECKPOINT_OPTIMIZED_PART_SIZE,
+                roset_fuse::upload::CHECKPOINT_OPTIMIZED_CONCURRENCY,
+            )
+        } else {
+            client_upload_hook(client.clone())
+        }),
+        runtime.handle().clone(),
+    )
+    .with_staging_dir(cli.staging_dir.join("pending"));
+
+    match roset_fuse::staging::hydrate_staged_jobs(&cli.staging_dir.join("pending")) {
+        Ok(recovered) => {
+            for job in recovered {
+                log_line(
+                    &logger,
+                    &format!(
+                        "roset-fuse: recovered staged write for node {} from a previous crash",
+                        job.job.node_id
+                    ),
+                );
+                if runtime
+                    .block_on(staging.stage_file(job.job.node_id.clone(), job.job.data.clone()))
+                    .is_ok()
+                {
+                    let _ = std::fs::remove_file(&job.meta_path);
+                    let _ = std::fs::remove_file(&job.data_path);
+                }
+            }
+        }
+        Err(e) => log_line(&logger, &format!("roset-fuse: failed to recover staged writes: {e}")),
+    }
+
+    let cache = AttrCache::new(Duration::from_secs(30));
+    if let Some(config_path) = &cli.cache_policy_config {
+        cache.set_prefix_policies(load_cache_prefix_policies(config_path));
+    }
+    let fs = RosetFs::new(client.clone(), staging, cache, runtime.handle().clone())
+        .with_commit_on_unmount(cli.root_node_id.clone(), cli.commit_on_unmount)
+        .with_allow_offline(cli.allow_offline)
+        .with_read_only_fallback(cli.read_only_fallback)
+        .with_direct_io(cli.direct_io)
+        .with_security_capability_xattr(cli.allow_security_capability_xattr)
+        .with_writeback_cache(cli.enable_writeback_cache)
+        .with_shared_cache_dir(cli.shared_cache_dir)
+        .with_block_cache(cli.cache_dir, cli.cache_size_mb * 1024 * 1024)
+        .with_read_cache_policy(cli.read_cache_policy)
+        .with_reported_capacity_bytes(cli.reported_capacity_gb * 1024 * 1024 * 1024)
+        .with_block_size(cli.block_size)
+        .with_max_write_bytes((cli.max_write_kb * 1024) as u32)
+        .with_read_ahead(cli.read_ahead_kb * 1024)
+        .with_max_readahead_kb(cli.max_readahead_kb)
+        .with_conflict_policy(cli.conflict_policy)
+        .with_write_durability(cli.write_durability)
+        .with_ignore_globs(ignore_globs)
+        .with_allow_hidden_lookup(cli.allow_hidden_lookup);
+
+    if cli.no_default_permissions {
+        log_line(
+            &logger,
+            "roset-fuse: --no-default-permissions set, kernel-side permission checks are disabled for this mount",
+        );
+    }
+    let mount_options = roset_fuse::mount::build_mount_options(cli.no_default_permissions);
+
+    let root = runtime
+        .block_on(client.get_node(&cli.root_node_id))
+        .unwrap_or_else(|e| panic!("failed to fetch root node {}: {e}", cli.root_node_id));
+    fs.init_root(root);
+
+    log_line(&logger, &format!("roset-fuse: mounting volume {} at {}", cli.volume_id, cli.mount_point));
+
+    if let Some(config_path) = &cli.additional_mounts_config {
+        let specs = load_additional_mounts(config_path);
+        let additional = roset_fuse::mount::build_mounts(&client, runtime.handle().clone(), &specs);
+        for (spec, additional_fs) in specs.into_iter().zip(additional) {
+            log_line(
+                &logger,
+                &format!(
+                    "roset-fuse: mounting additional volume {} at {}",
+                    spec.mount_id,
+                    spec.mount_point.display()
+                ),
+            );
+            let handle = runtime.handle().clone();
+            let client = client.clone();
+            let mount_options = mount_options.clone();
+            std::thread::spawn(move || {
+                let root = handle
+                    .block_on(client.get_node(&spec.root_node_id))
+                    .unwrap_or_else(|e| panic!("failed to fetch root node {}: {e}", spec.root_node_id));
+                additional_fs.init_root(root);
+                if let Err(e) = fuser::mount2(additional_fs, &spec.mount_point, &mount_options) {
+                    eprintln!("roset-fuse: additional mount {} failed: {e}", spec.mount_point.display());
+                }
+            });
+        }
+    }
+
+    if let Err(e) = fuser::mount2(fs, &cli.mount_point, &mount_options) {
+        panic!("failed to mount {}: {e}", cli.mount_point);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runtime_builds_with_a_configured_worker_count() {
+        let runtime = build_runtime(Some(2));
+        runtime.block_on(async {});
+    }
+}
@@ -0,0 +1,129 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::cache::AttrCache;
+use crate::client::RosetClient;
+use crate::fs::RosetFs;
+use crate::staging::{client_upload_hook, StagingManager};
+
+/// Per-mount configuration when several mountpoints are served from one
+/// process (see [`build_mount`]). Each mount gets its own cache, inode
+/// map, and staging queue; only the HTTP client/connection pool and
+/// Tokio runtime are shared across all of them.
+#[derive(Debug, Clone)]
+pub struct MountSpec {
+    pub mount_id: String,
+    pub root_node_id: String,
+    pub mount_point: PathBuf,
+    pub staging_queue_capacity: usize,
+    pub cache_ttl: Duration,
+    pub commit_on_unmount: bool,
+}
+
+/// Builds one [`RosetFs`] for `spec`, reusing `shared_client`'s
+/// connection pool (and circuit breaker state) rather than opening a new
+/// one per mount — the difference between N processes' worth of idle
+/// connections and one pool shared across every volume on the node.
+pub fn build_mount(shared_client: &RosetClient, runtime: tokio::runtime::Handle, spec: &MountSpec) -> RosetFs {
+    let client = shared_client.clone().with_mount_id(spec.mount_id.clone());
+    let staging = StagingManager::with_upload_hook(
+        spec.staging_queue_capacity,
+        crate::staging::DEFAULT_MAX_CONCURRENT_UPLOADS,
+        crate::staging::DEFAULT_REPORT_INTERVAL,
+        None,
+        crate::staging::StagingRetryConfig::default(),
+        None,
+        Some(client_upload_hook(client.clone())),
+        runtime.clone(),
+    );
+    RosetFs::new(client, staging, AttrCache::new(spec.cache_ttl), runtime)
+        .with_commit_on_unmount(spec.root_node_id.clone(), spec.commit_on_unmount)
+}
+
+/// Builds one [`RosetFs`] per entry in `specs`, all sharing `client`'s
+/// connection pool and `runtime`.
+pub fn build_mounts(client: &RosetClient, runtime: tokio::runtime::Handle, specs: &[MountSpec]) -> Vec<RosetFs> {
+    specs
+        .iter()
+        .map(|spec| build_mount(client, runtime.clone(), spec))
+        .collect()
+}
+
+/// Builds the `fuser::MountOption` list passed to the kernel for a mount:
+/// always names the filesystem type, and includes `DefaultPermissions`
+/// unless `no_default_permissions` is set (`--no-default-permissions`).
+/// Split out as a pure function, like [`crate::fs::RosetFs::should_enable_writeback_cache`],
+/// so the decision is testable without going through a real `fuser`
+/// mount.
+///
+/// # Security
+///
+/// `DefaultPermissions` has the kernel enforce the Unix permission bits
+/// (uid/gid/mode) reported by `getattr` before a request ever reaches
+/// this filesystem. Setting `no_default_permissions` disables that
+/// kernel-side check entirely, letting any local user who can reach the
+/// mountpoint through to `lookup`/`open`/etc. — appropriate only in a
+/// multi-tenant setup where the reported mode/uid/gid are meaningless
+/// and the backend enforces authorization itself (e.g. per API key),
+/// since that backend check becomes the only access control left.
+pub fn build_mount_options(no_default_permissions: bool) -> Vec<fuser::MountOption> {
+    let mut options = vec![fuser::MountOption::FSName("roset".to_string())];
+    if !no_default_permissions {
+        options.push(fuser::MountOption::DefaultPermissions);
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(mount_id: &str, root_node_id: &str) -> MountSpec {
+        MountSpec {
+            mount_id: mount_id.to_string(),
+            root_node_id: root_node_id.to_string(),
+            mount_point: PathBuf::from(format!("/mnt/{mount_id}")),
+            staging_queue_capacity: 8,
+            cache_ttl: Duration::from_secs(30),
+            commit_on_unmount: false,
+        }
+    }
+
+    #[test]
+    fn mounts_sharing_one_client_keep_independent_caches_and_inode_maps() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let client = RosetClient::new("https://api.roset.dev");
+        let specs = vec![spec("vol-a", "root-a"), spec("vol-b", "root-b")];
+
+        let mounts = build_mounts(&client, rt.handle().clone(), &specs);
+        assert_eq!(mounts.len(), 2);
+
+        mounts[0].cache.put(
+            "/only-on-a.txt".to_string(),
+            Some(crate::node::Node {
+                id: "n1".to_string(),
+                name: "only-on-a.txt".to_string(),
+                node_type: crate::node::NodeType::File,
+                size: Some(1),
+                mtime: std::time::SystemTime::now(),
+                etag: None,
+                metadata: std::collections::HashMap::new(),
+            }),
+        );
+
+        assert!(mounts[0].cache.get("/only-on-a.txt").is_some());
+        assert!(mounts[1].cache.get("/only-on-a.txt").is_none());
+        assert_eq!(mounts[0].root_node_id, "root-a");
+        assert_eq!(mounts[1].root_node_id, "root-b");
+    }
+
+    #[test]
+    fn no_default_permissions_omits_the_mount_option_but_keeps_the_rest() {
+        let default = build_mount_options(false);
+        assert!(default.contains(&fuser::MountOption::DefaultPermissions));
+
+        let no_default_permissions = build_mount_options(true);
+        assert!(!no_default_permissions.contains(&fuser::MountOption::DefaultPermissions));
+        assert!(no_default_permissions.contains(&fuser::MountOption::FSName("roset".to_string())));
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+
+/// The kind of filesystem entry a [`Node`] represents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeType {
+    File,
+    Directory,
+    Symlink,
+}
+
+/// A Roset API node as surfaced to the FUSE layer.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Node {
+    pub id: String,
+    pub name: String,
+    pub node_type: NodeType,
+    /// `None` until the backend has finished computing the node's size,
+    /// e.g. in the window right after an upload completes but before
+    /// finalization. Callers that need a concrete byte count for reads
+    /// (as opposed to just display) should go through
+    /// `RosetFs::resolve_size` rather than defaulting this to `0`, which
+    /// would make a `read` on a file with pending size computation look
+    /// like an empty file instead of retrying.
+    pub size: Option<u64>,
+    pub mtime: SystemTime,
+    /// The backend's opaque etag/version for this node's content, when it
+    /// reports one. Unlike `mtime`, which is second-resolution and can't
+    /// tell apart two writes that land in the same second, this changes on
+    /// every write, so it's the precise signal used for cache
+    /// revalidation, `If-Range` conditional reads, and
+    /// optimistic-concurrency preconditions — see [`Self::version`].
+    #[serde(default)]
+    pub etag: Option<String>,
+    /// Free-form backend metadata, including the `unix.uid`/`unix.gid`/
+    /// `unix.mode` keys used to persist ownership and permissions.
+    pub metadata: HashMap<String, String>,
+}
+
+impl Node {
+    pub fn uid(&self) -> Option<u32> {
+        self.metadata.get("unix.uid")?.parse().ok()
+    }
+
+    pub fn gid(&self) -> Option<u32> {
+        self.metadata.get("unix.gid")?.parse().ok()
+    }
+
+    /// The target path stored for a [`NodeType::Symlink`] node, verbatim
+    /// as passed to `RosetFs::symlink` — relative and absolute targets
+    /// are both stored as-is, matching `symlink(2)`'s own semantics.
+    /// `None` for any other node type.
+    pub fn symlink_target(&self) -> Option<&str> {
+        self.metadata.get("symlinkTarget").map(String::as_str)
+    }
+
+    /// The backend's opaque version/etag for this node, if it reports
+    /// one. Used as an optimistic-concurrency precondition for metadata
+    /// patches and as the `--conflict-policy` comparison at upload
+    /// completion.
+    ///
+    /// Prefers the dedicated `etag` field, falling back to the legacy
+    /// `metadata["version"]` key for a backend response that only sends
+    /// the older form.
+    pub fn version(&self) -> Option<&str> {
+        self.etag.as_deref().or_else(|| self.metadata.get("version").map(String::as_str))
+    }
+}
@@ -0,0 +1,24 @@
+/// Thin wrapper around `fuser`'s kernel notification channel.
+///
+/// Kept as its own type (rather than calling `fuser::Notifier` directly
+/// from `fs.rs`) so tests can swap in a fake that records calls instead
+/// of talking to a live kernel FUSE connection.
+pub struct Notifier {
+    inner: fuser::Notifier,
+}
+
+impl Notifier {
+    pub fn new(inner: fuser::Notifier) -> Self {
+        Self { inner }
+    }
+
+    /// Tells the kernel to drop its cached dentry for `name` under `parent`.
+    pub fn invalidate_entry(&self, parent: u64, name: &str) {
+        let _ = self.inner.inval_entry(parent, name.as_ref());
+    }
+
+    /// Tells the kernel to drop cached attributes/data for `ino`.
+    pub fn invalidate_inode(&self, ino: u64) {
+        let _ = self.inner.inval_inode(ino, 0, 0);
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How often the background poller re-checks backend size for watched
+/// inodes.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Tracks open files the kernel has registered for `poll` (e.g. a
+/// `tail -f` reader), so a background task can notice when the backend
+/// size changes out from under a stale attr-cache entry and tell the
+/// kernel to re-stat rather than waiting out the TTL.
+pub struct PollRegistry {
+    watched: Mutex<HashMap<u64, u64>>,
+}
+
+impl PollRegistry {
+    pub fn new() -> Self {
+        Self {
+            watched: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Registers `ino` for polling, recording the size last observed so
+    /// a later check can detect growth.
+    pub fn watch(&self, ino: u64, last_known_size: u64) {
+        self.watched.lock().unwrap().insert(ino, last_known_size);
+    }
+
+    pub fn unwatch(&self, ino: u64) {
+        self.watched.lock().unwrap().remove(&ino);
+    }
+
+    /// Compares `current_size` against the last known size for `ino`;
+    /// returns `true` (and updates the record) if it grew, so the caller
+    /// knows to invalidate the kernel's cached attributes for it.
+    pub fn check_grew(&self, ino: u64, current_size: u64) -> bool {
+        let mut watched = self.watched.lock().unwrap();
+        match watched.get_mut(&ino) {
+            Some(last) if current_size > *last => {
+                *last = current_size;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for PollRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn growth_is_detected_and_notifies_once_per_change() {
+        let registry = PollRegistry::new();
+        registry.watch(5, 100);
+
+        assert!(!registry.check_grew(5, 100));
+        assert!(registry.check_grew(5, 150));
+        assert!(!registry.check_grew(5, 150));
+    }
+}
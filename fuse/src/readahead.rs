@@ -0,0 +1,106 @@
+//! Pure decision logic for read-ahead prefetching on a file handle: no
+//! I/O, no [`crate::client::RosetClient`] — just "given what's already
+//! been read on this handle and what's being read now, should we
+//! prefetch, and how much?" See
+//! [`crate::fs::RosetFs::plan_handle_read_ahead`] for the integration
+//! point that feeds this from a real [`crate::handle::Handle`].
+
+/// Default read-ahead window, applied once a handle's reads look
+/// sequential. Large enough to amortize one round trip to the backend
+/// across many kernel-sized reads of a sequentially-read checkpoint or
+/// dataset file, without ballooning memory on a mount serving many open
+/// files at once.
+pub const DEFAULT_READ_AHEAD_BYTES: u64 = 4 * 1024 * 1024;
+
+/// What [`plan_read_ahead`] decided to do about a single `read` call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadAheadPlan {
+    /// Prefetch `len` bytes starting at `offset`, on top of serving the
+    /// bytes actually requested.
+    Prefetch { offset: u64, len: u64 },
+    /// Access doesn't look sequential (or read-ahead is disabled) —
+    /// serve just the requested bytes and don't prefetch.
+    Skip,
+}
+
+/// Decides whether a `read(offset, len)` on a handle whose previous read
+/// ended at `last_read_end` (`None` if this is the handle's first read)
+/// should kick off a background prefetch, and how large a window to
+/// fetch.
+///
+/// Access is considered sequential only when this read starts exactly
+/// where the last one left off — any gap, overlap, or backward seek (a
+/// strided or otherwise random-access pattern) falls back to
+/// [`ReadAheadPlan::Skip`] rather than guessing, since prefetching bytes
+/// a random-access reader will never touch just wastes bandwidth and
+/// buffer space on this mount.
+pub fn plan_read_ahead(last_read_end: Option<u64>, offset: u64, len: u64, window: u64) -> ReadAheadPlan {
+    if window == 0 || len == 0 {
+        return ReadAheadPlan::Skip;
+    }
+    if last_read_end != Some(offset) {
+        return ReadAheadPlan::Skip;
+    }
+    ReadAheadPlan::Prefetch { offset: offset + len, len: window }
+}
+
+/// Whether a buffered read-ahead window starting at `buffer_offset` and
+/// `buffer_len` bytes long fully covers `[offset, offset + len)`, i.e.
+/// whether a `read` for that range can be served straight from the
+/// buffer instead of issuing a fresh backend fetch.
+pub fn window_covers(buffer_offset: u64, buffer_len: u64, offset: u64, len: u64) -> bool {
+    if len == 0 {
+        return true;
+    }
+    let Some(start) = offset.checked_sub(buffer_offset) else { return false };
+    let Some(end) = start.checked_add(len) else { return false };
+    end <= buffer_len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_read_continuing_where_the_last_one_left_off_is_sequential_and_prefetches() {
+        assert_eq!(
+            plan_read_ahead(Some(4096), 4096, 512, 1024 * 1024),
+            ReadAheadPlan::Prefetch { offset: 4608, len: 1024 * 1024 },
+        );
+    }
+
+    #[test]
+    fn a_files_first_read_has_no_prior_offset_to_compare_against_and_does_not_prefetch() {
+        assert_eq!(plan_read_ahead(None, 0, 512, 1024 * 1024), ReadAheadPlan::Skip);
+    }
+
+    #[test]
+    fn a_backward_seek_looks_random_and_does_not_prefetch() {
+        assert_eq!(plan_read_ahead(Some(4096), 0, 512, 1024 * 1024), ReadAheadPlan::Skip);
+    }
+
+    #[test]
+    fn a_strided_random_access_pattern_does_not_prefetch() {
+        assert_eq!(plan_read_ahead(Some(4096), 16384, 512, 1024 * 1024), ReadAheadPlan::Skip);
+    }
+
+    #[test]
+    fn a_zero_byte_read_ahead_window_disables_prefetching_even_for_sequential_access() {
+        assert_eq!(plan_read_ahead(Some(4096), 4096, 512, 0), ReadAheadPlan::Skip);
+    }
+
+    #[test]
+    fn a_request_fully_inside_the_buffered_window_is_covered() {
+        assert!(window_covers(1000, 4096, 1500, 512));
+    }
+
+    #[test]
+    fn a_request_spilling_past_the_end_of_the_buffered_window_is_not_covered() {
+        assert!(!window_covers(1000, 4096, 4996, 512));
+    }
+
+    #[test]
+    fn a_request_starting_before_the_buffered_window_is_not_covered() {
+        assert!(!window_covers(1000, 4096, 0, 512));
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::{Duration, Instant};
+
+/// Coarse classification of a failed request, used to pick how
+/// aggressively to back off before retrying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    RateLimit,
+    ServerError,
+    Network,
+}
+
+/// Per-error-class backoff caps for [`execute_request`].
+///
+/// Rate limits tend to clear on a slower schedule than a transient 5xx,
+/// so they default to a longer cap; network errors (DNS, connect, reset)
+/// sit in between.
+#[derive(Debug, Clone, Copy)]
+pub struct BackoffConfig {
+    pub max_backoff_rate_limit: Duration,
+    pub max_backoff_server_error: Duration,
+    pub max_backoff_network: Duration,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            max_backoff_rate_limit: Duration::from_secs(60),
+            max_backoff_server_error: Duration::from_secs(10),
+            max_backoff_network: Duration::from_secs(20),
+        }
+    }
+}
+
+impl BackoffConfig {
+    pub fn cap_for(&self, class: ErrorClass) -> Duration {
+        match class {
+            ErrorClass::RateLimit => self.max_backoff_rate_limit,
+            ErrorClass::ServerError => self.max_backoff_server_error,
+            ErrorClass::Network => self.max_backoff_network,
+        }
+    }
+
+    /// Exponential backoff (`2^attempt` seconds) for `class`, capped at
+    /// that class's configured maximum.
+    pub fn backoff_for(&self, class: ErrorClass, attempt: u32) -> Duration {
+        let uncapped = Duration::from_secs(1u64.saturating_mul(1 << attempt.min(20)));
+        uncapped.min(self.cap_for(class))
+    }
+}
+
+/// A wall-clock budget for one FUSE operation, shared across every retried
+/// sub-call it makes.
+///
+/// A single `read` can trigger a URL refresh followed by a download,
+/// each independently retried up to their own `max_attempts` — without a
+/// shared budget, one kernel read can end up retrying far longer than any
+/// single call's backoff schedule implies and stall the FUSE thread.
+/// Passing the same `Deadline` into every sub-call bounds the total time
+/// regardless of how many retried calls the op is built from.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    expires_at: Instant,
+}
+
+impl Deadline {
+    pub fn after(budget: Duration) -> Self {
+        Self {
+            expires_at: Instant::now() + budget,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.expires_at.saturating_duration_since(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_error_class_respects_its_configured_cap() {
+        let config = BackoffConfig {
+            max_backoff_rate_limit: Duration::from_secs(5),
+            max_backoff_server_error: Duration::from_secs(2),
+            max_backoff_network: Duration::from_secs(3),
+        };
+
+        assert_eq!(config.backoff_for(ErrorClass::RateLimit, 10), Duration::from_secs(5));
+        assert_eq!(config.backoff_for(ErrorClass::ServerError, 10), Duration::from_secs(2));
+        assert_eq!(config.backoff_for(ErrorClass::Network, 10), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn deadline_reports_expired_once_its_budget_elapses() {
+        let deadline = Deadline::after(Duration::from_millis(1));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(deadline.is_expired());
+        assert_eq!(deadline.remaining(), Duration::ZERO);
+    }
+}
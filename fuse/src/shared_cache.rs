@@ -0,0 +1,266 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How many attempts in all [`SharedBlockCache::get_or_fetch_with_repair`]
+/// makes at a block that fails verification before giving up, i.e. at
+/// most `MAX_REPAIR_ATTEMPTS - 1` evict-and-re-fetch repairs.
+const MAX_REPAIR_ATTEMPTS: u32 = 3;
+
+/// A cache directory shared by every `roset-fuse` process on a node,
+/// keyed by the string the caller passes rather than by path, so a block
+/// fetched by one mount/pod of the same dataset is only downloaded once.
+/// `RosetFs` passes node id plus version, not a content hash, so
+/// identical content under two node ids is stored twice.
+///
+/// Coordination across processes uses an advisory `flock(2)` on each
+/// cache file rather than an in-process lock, since the whole point is
+/// that concurrent *processes* (not just threads) mustn't race on the
+/// same file: one download winning and the other reading a half-written
+/// block would silently corrupt a reader.
+pub struct SharedBlockCache {
+    dir: PathBuf,
+    repairs: AtomicU64,
+}
+
+impl SharedBlockCache {
+    /// `dir` is typically `<tenant>/<node-content-hash>` under a
+    /// well-known node-local path (e.g. `/var/lib/roset-fuse/cache`), so
+    /// unrelated tenants/datasets never share a directory.
+    pub fn new(dir: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, repairs: AtomicU64::new(0) })
+    }
+
+    /// How many times a block has been evicted and re-fetched by
+    /// [`Self::get_or_fetch_with_repair`] since this cache was created —
+    /// the counter a metrics exporter should poll to track read-repair
+    /// activity.
+    pub fn repair_count(&self) -> u64 {
+        self.repairs.load(Ordering::SeqCst)
+    }
+
+    fn path_for(&self, content_hash: &str) -> PathBuf {
+        self.dir.join(content_hash)
+    }
+
+    /// Returns the cached block for `content_hash` if another mount
+    /// already fetched it, otherwise calls `fetch` to produce it, writes
+    /// it to the shared cache, and returns it. Holds an exclusive
+    /// `flock` on the cache file for the duration so a second process
+    /// blocked on the same key waits for the first to finish instead of
+    /// downloading a duplicate or reading a partial write.
+    pub fn get_or_fetch(
+        &self,
+        content_hash: &str,
+        fetch: impl FnOnce() -> std::io::Result<Vec<u8>>,
+    ) -> std::io::Result<Vec<u8>> {
+        let path = self.path_for(content_hash);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&path)?;
+
+        lock_exclusive(&file)?;
+        let result = (|| {
+            let mut existing = Vec::new();
+            let mut f = &file;
+            f.read_to_end(&mut existing)?;
+            if !existing.is_empty() {
+                return Ok(existing);
+            }
+            let data = fetch()?;
+            let mut f = &file;
+            f.write_all(&data)?;
+            f.flush()?;
+            Ok(data)
+        })();
+        unlock(&file);
+        result
+    }
+
+    /// Returns the cached block without fetching, for read paths that
+    /// want a plain cache lookup.
+    pub fn get(&self, content_hash: &str) -> Option<Vec<u8>> {
+        let path = self.path_for(content_hash);
+        if !path.exists() {
+            return None;
+        }
+        fs::read(path).ok()
+    }
+
+    /// Drops the on-disk block for `content_hash`, if any, so the next
+    /// lookup re-fetches instead of serving it again.
+    fn evict(&self, content_hash: &str) -> std::io::Result<()> {
+        match fs::remove_file(self.path_for(content_hash)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// [`Self::get_or_fetch`], but read-repairing: a cached or
+    /// freshly-fetched block that fails `verify` (e.g. a checksum check)
+    /// is evicted and re-fetched rather than served or left cached,
+    /// retrying up to [`MAX_REPAIR_ATTEMPTS`] times before giving up with
+    /// an `Other` `io::Error`. This protects a long-running reader from
+    /// transient on-disk corruption of the shared cache, or a backend
+    /// transfer that's still getting corrupted in transit after
+    /// [`crate::client::RosetClient::download_range`]'s own retries.
+    ///
+    /// Every repair increments [`Self::repair_count`].
+    pub fn get_or_fetch_with_repair(
+        &self,
+        content_hash: &str,
+        fetch: impl Fn() -> std::io::Result<Vec<u8>>,
+        verify: impl Fn(&[u8]) -> bool,
+    ) -> std::io::Result<Vec<u8>> {
+        for attempt in 0..MAX_REPAIR_ATTEMPTS {
+            let data = self.get_or_fetch(content_hash, &fetch)?;
+            if verify(&data) {
+                return Ok(data);
+            }
+            if attempt + 1 >= MAX_REPAIR_ATTEMPTS {
+                return Err(std::io::Error::other(format!(
+                    "block {content_hash} failed verification after {MAX_REPAIR_ATTEMPTS} attempts"
+                )));
+            }
+            self.evict(content_hash)?;
+            self.repairs.fetch_add(1, Ordering::SeqCst);
+        }
+        unreachable!("loop above always returns by its last iteration")
+    }
+}
+
+fn lock_exclusive(file: &File) -> std::io::Result<()> {
+    let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn unlock(file: &File) {
+    unsafe {
+        libc::flock(file.as_raw_fd(), libc::LOCK_UN);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn temp_cache_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("roset-fuse-shared-cache-test-{name}-{}", std::process::id()))
+    }
+
+    #[test]
+    fn second_instance_is_served_from_the_first_instances_cached_block() {
+        let dir = temp_cache_dir("shared");
+        let _ = fs::remove_dir_all(&dir);
+
+        let fetch_calls = Arc::new(AtomicUsize::new(0));
+
+        let first = SharedBlockCache::new(&dir).unwrap();
+        let calls = fetch_calls.clone();
+        let data = first
+            .get_or_fetch("hash-1", || {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(b"block data".to_vec())
+            })
+            .unwrap();
+        assert_eq!(data, b"block data");
+        assert_eq!(fetch_calls.load(Ordering::SeqCst), 1);
+
+        // A second instance pointed at the same directory, simulating a
+        // different FUSE process on the same node.
+        let second = SharedBlockCache::new(&dir).unwrap();
+        let calls = fetch_calls.clone();
+        let data = second
+            .get_or_fetch("hash-1", || {
+                calls.fetch_add(1, Ordering::SeqCst);
+                panic!("should not be called — block is already cached");
+            })
+            .unwrap();
+
+        assert_eq!(data, b"block data");
+        assert_eq!(fetch_calls.load(Ordering::SeqCst), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_block_that_fails_verification_is_evicted_and_re_fetched() {
+        let dir = temp_cache_dir("repair");
+        let _ = fs::remove_dir_all(&dir);
+
+        let fetch_calls = Arc::new(AtomicUsize::new(0));
+        let cache = SharedBlockCache::new(&dir).unwrap();
+
+        // Seed the cache with a corrupted block directly, bypassing
+        // `get_or_fetch` — simulating disk corruption that happened
+        // after a previously-good write.
+        fs::write(cache.path_for("hash-1"), b"corrupted").unwrap();
+
+        let calls = fetch_calls.clone();
+        let data = cache
+            .get_or_fetch_with_repair(
+                "hash-1",
+                move || {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Ok(b"good data".to_vec())
+                },
+                |data| data == b"good data",
+            )
+            .unwrap();
+
+        assert_eq!(data, b"good data");
+        assert_eq!(fetch_calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.repair_count(), 1);
+
+        // The repaired block is now the one served from cache.
+        let data = cache
+            .get_or_fetch_with_repair(
+                "hash-1",
+                || panic!("should not be called — block is already repaired"),
+                |data| data == b"good data",
+            )
+            .unwrap();
+        assert_eq!(data, b"good data");
+        assert_eq!(cache.repair_count(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn repair_gives_up_with_an_io_error_once_attempts_are_exhausted() {
+        let dir = temp_cache_dir("repair-exhausted");
+        let _ = fs::remove_dir_all(&dir);
+
+        let cache = SharedBlockCache::new(&dir).unwrap();
+        let fetch_calls = Arc::new(AtomicUsize::new(0));
+        let calls = fetch_calls.clone();
+
+        let result = cache.get_or_fetch_with_repair(
+            "hash-1",
+            move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(b"always bad".to_vec())
+            },
+            |_| false,
+        );
+
+        assert!(result.is_err());
+        assert_eq!(fetch_calls.load(Ordering::SeqCst), MAX_REPAIR_ATTEMPTS as usize);
+        assert_eq!(cache.repair_count(), (MAX_REPAIR_ATTEMPTS - 1) as u64);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,1123 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use tokio::sync::{mpsc, oneshot, Semaphore};
+
+use crate::client::RosetClient;
+use crate::dlq::{Dlq, DlqRetentionPolicy};
+
+/// Performs one staged job's upload: `job.node_id`/`job.data` in, success
+/// or an error message out. An `async fn` can't be stored in a struct
+/// field directly, so this is the boxed-future shape every
+/// [`StagingManager`] constructor's `upload_hook` parameter takes —
+/// [`client_upload_hook`] builds the real one; tests build their own to
+/// simulate a failing or slow backend.
+pub type UploadHook = Arc<dyn Fn(&StagingJob) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> + Send + Sync>;
+
+/// The upload hook every production caller should pass to
+/// [`StagingManager::with_upload_hook`]: a single full-content PUT via
+/// [`RosetClient::upload_content`] for a job under [`MULTIPART_MIN_SIZE`],
+/// or a real multipart session
+/// (initiate/[`RosetClient::upload_part`]/complete) above it, using
+/// [`crate::upload::DEFAULT_PART_SIZE`] parts uploaded one at a time. See
+/// [`client_upload_hook_with_part_size`] for `--checkpoint-optimized`'s
+/// larger parts and higher part-upload concurrency.
+pub fn client_upload_hook(client: RosetClient) -> UploadHook {
+    client_upload_hook_with_part_size(client, crate::upload::DEFAULT_PART_SIZE, 1)
+}
+
+/// Like [`client_upload_hook`], with the multipart part size and
+/// part-upload concurrency overridable. `--checkpoint-optimized` calls
+/// this directly with [`crate::upload::CHECKPOINT_OPTIMIZED_PART_SIZE`]/
+/// [`crate::upload::CHECKPOINT_OPTIMIZED_CONCURRENCY`], since a large
+/// write-once checkpoint file benefits from fewer, bigger parts uploaded
+/// in parallel rather than `client_upload_hook`'s defaults.
+pub fn client_upload_hook_with_part_size(client: RosetClient, part_size: u64, concurrency: usize) -> UploadHook {
+    Arc::new(move |job: &StagingJob| {
+        let client = client.clone();
+        let node_id = job.node_id.clone();
+        let data = job.data.clone();
+        Box::pin(async move { upload_job(&client, &node_id, data, part_size, concurrency).await })
+    })
+}
+
+/// Below this size, a job goes through [`RosetClient::upload_content`] as
+/// one PUT — not worth the extra initiate/complete round trips a
+/// multipart session costs for a buffer that already fits comfortably in
+/// a single request body. At or above it, [`client_upload_hook`] carves
+/// the buffer into parts (see [`crate::upload::plan_parts_for_upload`])
+/// and uploads it as a real multipart session instead.
+pub const MULTIPART_MIN_SIZE: u64 = crate::upload::DEFAULT_PART_SIZE;
+
+/// Uploads one staged job's data, taking the single-PUT or multipart path
+/// depending on its size relative to [`MULTIPART_MIN_SIZE`] — see
+/// [`client_upload_hook`]. On any part-upload failure, aborts the
+/// multipart session rather than leaving it dangling on the backend.
+async fn upload_job(client: &RosetClient, node_id: &str, data: Vec<u8>, part_size: u64, concurrency: usize) -> Result<(), String> {
+    if (data.len() as u64) < MULTIPART_MIN_SIZE {
+        return client.upload_content(node_id, data).await.map(|_| ());
+    }
+
+    let total_size = data.len() as u64;
+    let parts = crate::upload::plan_parts_for_upload(total_size, part_size, crate::upload::DEFAULT_MAX_PART_COUNT)?;
+    let upload_id = client.initiate_multipart_upload(node_id).await?;
+
+    match upload_parts_concurrently(client, node_id, &upload_id, Arc::new(data), &parts, concurrency).await {
+        Ok(uploaded) => {
+            let uploaded_parts: Vec<_> = uploaded.iter().map(|u| u.part).collect();
+            crate::upload::validate_parts_contiguous(&uploaded_parts, total_size)?;
+            client.complete_multipart_upload(node_id, &upload_id, &uploaded).await.map(|_| ())
+        }
+        Err(e) => {
+            let _ = client.abort_multipart_upload(node_id, &upload_id).await;
+            Err(e)
+        }
+    }
+}
+
+/// Uploads every part in `parts`, at most `concurrency` in flight at
+/// once, returning all of them once every upload has succeeded.
+async fn upload_parts_concurrently(
+    client: &RosetClient,
+    node_id: &str,
+    upload_id: &str,
+    data: Arc<Vec<u8>>,
+    parts: &[crate::upload::Part],
+    concurrency: usize,
+) -> Result<Vec<crate::upload::UploadedPart>, String> {
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = Vec::with_capacity(parts.len());
+    for &part in parts {
+        let client = client.clone();
+        let node_id = node_id.to_string();
+        let upload_id = upload_id.to_string();
+        let data = data.clone();
+        let semaphore = semaphore.clone();
+        tasks.push(tokio::spawn(async move {
+            let _permit = semaphore.acquire_owned().await.expect("semaphore is never closed");
+            let chunk = data[part.offset as usize..(part.offset + part.len) as usize].to_vec();
+            client
+                .upload_part(&node_id, &upload_id, &part, chunk)
+                .await
+                .map(|etag| crate::upload::UploadedPart { part, etag })
+        }));
+    }
+    let mut uploaded = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        let result = task.await.map_err(|e| format!("part upload task panicked: {e}"))?;
+        uploaded.push(result?);
+    }
+    Ok(uploaded)
+}
+
+/// Default bound on the staging queue; overridable via
+/// `StagingManager::with_capacity` / `--staging-queue-capacity`.
+pub const DEFAULT_STAGING_QUEUE_CAPACITY: usize = 100;
+
+/// Default on-disk location for staged write data, relative to the
+/// process's current working directory.
+pub const DEFAULT_STAGING_DIR: &str = ".roset/staging";
+
+/// Rejects a staging directory nested inside the mount point. A staging
+/// dir under the mount would have its files served back out through the
+/// very mount it's staging writes for — at best confusing, at worst
+/// recursive (the mount reading its own staged data as mount content).
+pub fn validate_staging_dir(staging_dir: &Path, mount_point: &Path) -> Result<(), String> {
+    if staging_dir.starts_with(mount_point) {
+        return Err(format!(
+            "staging directory {} is inside the mount point {}; pick a staging \
+             directory outside the mount",
+            staging_dir.display(),
+            mount_point.display()
+        ));
+    }
+    Ok(())
+}
+
+/// How long `stage_file` waits for room in the queue before giving up and
+/// uploading synchronously instead.
+const ENQUEUE_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Default interval for the periodic health report, when one is enabled.
+pub const DEFAULT_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default cap on uploads in flight at once when a manager is built with
+/// [`StagingManager::with_concurrency`].
+pub const DEFAULT_MAX_CONCURRENT_UPLOADS: usize = 4;
+
+/// Config for the staging upload retry loop's backoff between attempts.
+/// Mirrors [`crate::retry::BackoffConfig`]'s capped-exponential shape but
+/// adds full jitter: every job recovered after a crash restarts its
+/// retry counter at the same instant, and without jitter they'd all
+/// retry in lockstep and thunder the backend right as it's recovering.
+#[derive(Debug, Clone, Copy)]
+pub struct StagingRetryConfig {
+    pub max_attempts: u32,
+    pub max_backoff: Duration,
+}
+
+impl Default for StagingRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl StagingRetryConfig {
+    /// Full-jitter capped exponential backoff for `attempt`: a uniformly
+    /// random duration in `[0, min(2^attempt seconds, max_backoff)]`,
+    /// rather than the bare `2^attempt` the old loop slept verbatim.
+    pub fn backoff_for(&self, attempt: u32) -> Duration {
+        let uncapped = Duration::from_secs(1u64.saturating_mul(1 << attempt.min(20)));
+        let capped = uncapped.min(self.max_backoff);
+        Duration::from_secs_f64(capped.as_secs_f64() * rand::random::<f64>())
+    }
+}
+
+/// Wires a [`Dlq`] into a [`StagingManager`]: where failed uploads are
+/// written, the retention policy a periodic sweep enforces, and how
+/// often that sweep runs.
+#[derive(Clone)]
+pub struct DlqConfig {
+    pub dir: PathBuf,
+    pub retention: DlqRetentionPolicy,
+    pub sweep_interval: Duration,
+}
+
+/// Runs `op` up to `retry.max_attempts` times, sleeping a jittered,
+/// capped backoff (see [`StagingRetryConfig::backoff_for`]) between
+/// attempts. Returns the last error if every attempt fails.
+pub async fn retry_with_backoff<F, Fut, T, E>(retry: StagingRetryConfig, mut op: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<T, E>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                attempt += 1;
+                if attempt >= retry.max_attempts {
+                    return Err(e);
+                }
+                tokio::time::sleep(retry.backoff_for(attempt - 1)).await;
+            }
+        }
+    }
+}
+
+/// A pending upload of staged (locally buffered) write data for a node.
+pub struct StagingJob {
+    pub node_id: String,
+    pub data: Vec<u8>,
+    /// Paths (`.job.json` sidecar, `.data` file) this job was persisted
+    /// to on disk before being queued, if
+    /// [`StagingManager::with_staging_dir`] is configured. Removed once
+    /// the upload succeeds; `None` for a job that was never written to
+    /// disk (e.g. built directly in a test).
+    pub persisted: Option<(PathBuf, PathBuf)>,
+}
+
+impl StagingJob {
+    fn new(node_id: String, data: Vec<u8>) -> Self {
+        Self { node_id, data, persisted: None }
+    }
+}
+
+/// Suffix identifying a staging job's on-disk sidecar metadata file.
+/// Jobs are recovered after a crash by scanning for this suffix
+/// directly, not by reconstructing it from the data file's name:
+/// `Path::with_extension` only replaces the component after the *last*
+/// dot, so for a data file whose node id itself contains a dot (e.g.
+/// `backups/db.sqlite`) it silently produces the wrong sidecar name
+/// instead of pairing with this one.
+const STAGING_JOB_SUFFIX: &str = ".job.json";
+
+/// On-disk metadata for one persisted staging job — just enough to
+/// rehydrate the [`StagingJob`] it describes. The data file's path is
+/// recorded explicitly here rather than derived from the sidecar's own
+/// name, so the two can't desync regardless of what characters end up in
+/// `node_id`.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PersistedJobMeta {
+    node_id: String,
+    data_path: PathBuf,
+}
+
+/// A staging job recovered from disk by [`hydrate_staged_jobs`], paired
+/// with the files backing it so the caller can remove them once the job
+/// has been requeued or uploaded.
+pub struct PersistedJob {
+    pub job: StagingJob,
+    pub meta_path: PathBuf,
+    pub data_path: PathBuf,
+}
+
+/// Persists `job` to `staging_dir` so it survives a crash before being
+/// uploaded: the raw bytes go to a `.data` file and a `.job.json`
+/// sidecar records which node they belong to and where to find them.
+/// Both filenames share a stem built from `node_id` plus a random
+/// suffix, so multiple in-flight jobs for the same node, or node ids
+/// that collide once sanitized, don't clobber each other.
+fn persist_staging_job(staging_dir: &Path, node_id: &str, data: &[u8]) -> io::Result<(PathBuf, PathBuf)> {
+    std::fs::create_dir_all(staging_dir)?;
+    let stem = format!("{}-{:016x}", sanitize_node_id(node_id), rand::random::<u64>());
+    let data_path = staging_dir.join(format!("{stem}.data"));
+    let meta_path = staging_dir.join(format!("{stem}{STAGING_JOB_SUFFIX}"));
+
+    std::fs::write(&data_path, data)?;
+    let meta = PersistedJobMeta { node_id: node_id.to_string(), data_path: data_path.clone() };
+    let meta_json = serde_json::to_vec(&meta).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    std::fs::write(&meta_path, meta_json)?;
+
+    Ok((meta_path, data_path))
+}
+
+/// Recovers every staging job left on disk in `staging_dir` after a
+/// crash, by scanning for `.job.json` sidecars (see
+/// [`STAGING_JOB_SUFFIX`]) and pairing each with its data file via the
+/// `data_path` recorded inside it, rather than reconstructing that path
+/// from the sidecar's own name.
+pub fn hydrate_staged_jobs(staging_dir: &Path) -> io::Result<Vec<PersistedJob>> {
+    let mut jobs = Vec::new();
+    let read_dir = match std::fs::read_dir(staging_dir) {
+        Ok(rd) => rd,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(jobs),
+        Err(e) => return Err(e),
+    };
+
+    for entry in read_dir {
+        let meta_path = entry?.path();
+        let is_sidecar = meta_path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.ends_with(STAGING_JOB_SUFFIX));
+        if !is_sidecar {
+            continue;
+        }
+
+        let meta: PersistedJobMeta =
+            serde_json::from_slice(&std::fs::read(&meta_path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let data = std::fs::read(&meta.data_path)?;
+        jobs.push(PersistedJob {
+            job: StagingJob { node_id: meta.node_id, data, persisted: Some((meta_path.clone(), meta.data_path.clone())) },
+            meta_path,
+            data_path: meta.data_path,
+        });
+    }
+    Ok(jobs)
+}
+
+/// Node ids can contain characters (`.`, `/`) that aren't safe as a
+/// single path component; this keeps staging filenames unambiguous
+/// without needing the node id to round-trip from the filename itself —
+/// the sidecar's JSON body is the source of truth for that.
+fn sanitize_node_id(node_id: &str) -> String {
+    node_id
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// Lifecycle of a single node's staged upload, as tracked by
+/// [`StagingManager::progress`] and surfaced through the
+/// `user.roset.upload-progress` virtual xattr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadState {
+    Staged,
+    Uploading,
+    Complete,
+    Failed,
+}
+
+/// A point-in-time snapshot of one node's upload, backing the
+/// `user.roset.upload-progress` virtual xattr.
+#[derive(Debug, Clone, Copy)]
+pub struct UploadProgress {
+    pub uploaded: u64,
+    pub total: u64,
+    pub state: UploadState,
+    /// When this upload started, so [`Self::throughput_bps`]/[`Self::eta`]
+    /// can derive a whole-upload moving-average rate from it.
+    pub started_at: Instant,
+}
+
+impl UploadProgress {
+    /// Bytes/sec implied by `uploaded` bytes over the time elapsed since
+    /// `started_at` — a whole-upload moving average rather than a
+    /// sliding window over recent samples, which is simpler and, for the
+    /// single-shot uploads this currently wraps, just as representative.
+    /// `0.0` before any time has meaningfully elapsed.
+    pub fn throughput_bps(&self) -> f64 {
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+        self.uploaded as f64 / elapsed
+    }
+
+    /// Estimated time remaining, derived from [`Self::throughput_bps`].
+    /// `None` once the upload is no longer in progress, or before enough
+    /// progress has been made to estimate a rate.
+    pub fn eta(&self) -> Option<Duration> {
+        if self.state != UploadState::Uploading {
+            return None;
+        }
+        let throughput = self.throughput_bps();
+        if throughput <= 0.0 {
+            return None;
+        }
+        let remaining = self.total.saturating_sub(self.uploaded) as f64;
+        Some(Duration::from_secs_f64(remaining / throughput))
+    }
+}
+
+/// Number of steps [`StagingManager`]'s upload loop reports progress in,
+/// regardless of job size. The steps are advanced before the upload hook
+/// runs and do not follow the bytes it actually sends, so this exists
+/// purely to give pollers of `user.roset.upload-progress` something
+/// between "0" and "done" instead of the progress jumping straight from
+/// staged to complete.
+const UPLOAD_PROGRESS_STEPS: u64 = 4;
+
+/// A point-in-time snapshot of the staging worker's backlog, handed to
+/// the report hook on each tick so a metrics exporter or alert rule can
+/// turn silent queue growth into something observable.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StagingReport {
+    pub pending_jobs: usize,
+    pub pending_bytes: u64,
+    pub uploaded_since_last_report: u64,
+    pub dlq_size: usize,
+    /// Aggregate throughput across every upload that completed since the
+    /// last report, i.e. `uploaded_since_last_report` amortized over the
+    /// report interval — the fleet-wide counterpart to
+    /// [`UploadProgress::throughput_bps`]'s per-upload estimate.
+    pub throughput_bps: f64,
+}
+
+#[derive(Default)]
+struct StagingStats {
+    pending_jobs: AtomicUsize,
+    pending_bytes: AtomicU64,
+    uploaded_total: AtomicU64,
+    /// Total bytes across every upload that has ever completed, tracked
+    /// separately from `uploaded_total` (a job count) so the periodic
+    /// report can derive a real bytes/sec throughput from the delta
+    /// between reports instead of a job-count delta, which says nothing
+    /// about rate when job sizes vary.
+    uploaded_bytes_total: AtomicU64,
+    dlq_size: AtomicUsize,
+}
+
+/// Queues dirty file data for background upload to the Roset API.
+///
+/// Writes land in a local staging area first so `write`/`release` can
+/// return quickly; a background worker drains the channel and performs
+/// the actual upload.
+pub struct StagingManager {
+    sender: mpsc::Sender<StagingJob>,
+    stats: Arc<StagingStats>,
+    retry: StagingRetryConfig,
+    dlq: Option<Arc<Dlq>>,
+    progress: Arc<std::sync::Mutex<HashMap<String, UploadProgress>>>,
+    upload_hook: Option<UploadHook>,
+    staging_dir: Option<PathBuf>,
+}
+
+impl StagingManager {
+    pub fn new(capacity: usize, runtime: tokio::runtime::Handle) -> Self {
+        Self::with_report_hook(capacity, DEFAULT_REPORT_INTERVAL, None, runtime)
+    }
+
+    /// Like [`Self::new`], but if `report_hook` is `Some`, spawns a
+    /// background task that calls it with a [`StagingReport`] every
+    /// `report_interval` for as long as the manager is alive.
+    pub fn with_report_hook(
+        capacity: usize,
+        report_interval: Duration,
+        report_hook: Option<Arc<dyn Fn(StagingReport) + Send + Sync>>,
+        runtime: tokio::runtime::Handle,
+    ) -> Self {
+        Self::with_concurrency(capacity, 1, report_interval, report_hook, runtime)
+    }
+
+    /// Like [`Self::with_report_hook`], but drains up to
+    /// `max_concurrent_uploads` jobs at once instead of one at a time.
+    ///
+    /// A workload that writes `data.tmp`, renames it to `data.final`, then
+    /// writes a `manifest` referencing `data.final` relies on those
+    /// uploads landing in the order they were staged — otherwise a reader
+    /// on another node can follow the manifest's reference before
+    /// `data.final`'s content actually exists. Concurrent draining alone
+    /// would let `buffer_unordered`-style completion reorder them, so each
+    /// job instead carries a `oneshot` that signals "my upload is done",
+    /// and a job for a given `node_id` waits on the *previous* job queued
+    /// for that same `node_id` before it starts uploading. Jobs for
+    /// different nodes have no such dependency and upload fully in
+    /// parallel, up to the concurrency cap.
+    pub fn with_concurrency(
+        capacity: usize,
+        max_concurrent_uploads: usize,
+        report_interval: Duration,
+        report_hook: Option<Arc<dyn Fn(StagingReport) + Send + Sync>>,
+        runtime: tokio::runtime::Handle,
+    ) -> Self {
+        Self::with_retry_config(
+            capacity,
+            max_concurrent_uploads,
+            report_interval,
+            report_hook,
+            StagingRetryConfig::default(),
+            runtime,
+        )
+    }
+
+    /// Like [`Self::with_concurrency`], with the retry backoff schedule
+    /// (attempt cap, max delay) also overridable instead of fixed.
+    pub fn with_retry_config(
+        capacity: usize,
+        max_concurrent_uploads: usize,
+        report_interval: Duration,
+        report_hook: Option<Arc<dyn Fn(StagingReport) + Send + Sync>>,
+        retry: StagingRetryConfig,
+        runtime: tokio::runtime::Handle,
+    ) -> Self {
+        Self::with_dlq(capacity, max_concurrent_uploads, report_interval, report_hook, retry, None, runtime)
+    }
+
+    /// Like [`Self::with_retry_config`], additionally writing uploads
+    /// that exhaust their retries to `dlq_config.dir` (`staging/failed`)
+    /// instead of dropping them, and sweeping that directory on
+    /// `dlq_config.sweep_interval` to enforce `dlq_config.retention` so
+    /// it doesn't grow unbounded.
+    pub fn with_dlq(
+        capacity: usize,
+        max_concurrent_uploads: usize,
+        report_interval: Duration,
+        report_hook: Option<Arc<dyn Fn(StagingReport) + Send + Sync>>,
+        retry: StagingRetryConfig,
+        dlq_config: Option<DlqConfig>,
+        runtime: tokio::runtime::Handle,
+    ) -> Self {
+        Self::with_upload_hook(
+            capacity,
+            max_concurrent_uploads,
+            report_interval,
+            report_hook,
+            retry,
+            dlq_config,
+            None,
+            runtime,
+        )
+    }
+
+    /// Like [`Self::with_dlq`], with the upload call itself overridable.
+    /// Every constructor above this one defaults `upload_hook` to `None`,
+    /// which uploads nothing and always reports success — fine for a test
+    /// that doesn't care whether the bytes actually left the process, but
+    /// a real mount (`main.rs`, [`crate::mount::build_mount`]) must call
+    /// this one directly with [`client_upload_hook`] so staged writes
+    /// actually reach the backend. Tests that need an upload to fail
+    /// outright (e.g. to exercise `RosetFs::handle_flush`'s
+    /// Sync-durability error path) can supply their own hook instead.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_upload_hook(
+        capacity: usize,
+        max_concurrent_uploads: usize,
+        report_interval: Duration,
+        report_hook: Option<Arc<dyn Fn(StagingReport) + Send + Sync>>,
+        retry: StagingRetryConfig,
+        dlq_config: Option<DlqConfig>,
+        upload_hook: Option<UploadHook>,
+        runtime: tokio::runtime::Handle,
+    ) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<StagingJob>(capacity);
+        let stats = Arc::new(StagingStats::default());
+        let dlq = dlq_config.as_ref().map(|c| Arc::new(Dlq::new(c.dir.clone())));
+        let progress = Arc::new(std::sync::Mutex::new(HashMap::new()));
+
+        let worker_stats = stats.clone();
+        let worker_dlq = dlq.clone();
+        let worker_progress = progress.clone();
+        let worker_upload_hook = upload_hook.clone();
+        let semaphore = Arc::new(Semaphore::new(max_concurrent_uploads.max(1)));
+        let spawn_handle = runtime.clone();
+        runtime.spawn(async move {
+            let mut node_tails: HashMap<String, oneshot::Receiver<()>> = HashMap::new();
+            while let Some(job) = receiver.recv().await {
+                let wait_for = node_tails.remove(&job.node_id);
+                let (done_tx, done_rx) = oneshot::channel();
+                node_tails.insert(job.node_id.clone(), done_rx);
+
+                let permit = semaphore.clone().acquire_owned().await.unwrap();
+                let stats = worker_stats.clone();
+                let dlq = worker_dlq.clone();
+                let progress = worker_progress.clone();
+                let upload_hook = worker_upload_hook.clone();
+                tokio::spawn(async move {
+                    if let Some(wait_for) = wait_for {
+                        let _ = wait_for.await;
+                    }
+                    let bytes = job.data.len() as u64;
+                    let _ = Self::upload_and_handle_failure(&job, retry, dlq.as_deref(), &stats, &progress, upload_hook.as_ref()).await;
+                    stats.pending_jobs.fetch_sub(1, Ordering::SeqCst);
+                    stats.pending_bytes.fetch_sub(bytes, Ordering::SeqCst);
+                    stats.uploaded_total.fetch_add(1, Ordering::SeqCst);
+                    stats.uploaded_bytes_total.fetch_add(bytes, Ordering::SeqCst);
+                    let _ = done_tx.send(());
+                    drop(permit);
+                });
+            }
+        });
+
+        if let Some(hook) = report_hook {
+            let report_stats = stats.clone();
+            spawn_handle.spawn(async move {
+                let mut last_uploaded = 0u64;
+                let mut last_uploaded_bytes = 0u64;
+                loop {
+                    tokio::time::sleep(report_interval).await;
+                    let uploaded_total = report_stats.uploaded_total.load(Ordering::SeqCst);
+                    let uploaded_bytes_total = report_stats.uploaded_bytes_total.load(Ordering::SeqCst);
+                    let bytes_since_last_report = uploaded_bytes_total.saturating_sub(last_uploaded_bytes);
+                    hook(StagingReport {
+                        pending_jobs: report_stats.pending_jobs.load(Ordering::SeqCst),
+                        pending_bytes: report_stats.pending_bytes.load(Ordering::SeqCst),
+                        uploaded_since_last_report: uploaded_total.saturating_sub(last_uploaded),
+                        dlq_size: report_stats.dlq_size.load(Ordering::SeqCst),
+                        throughput_bps: bytes_since_last_report as f64 / report_interval.as_secs_f64(),
+                    });
+                    last_uploaded = uploaded_total;
+                    last_uploaded_bytes = uploaded_bytes_total;
+                }
+            });
+        }
+
+        if let (Some(dlq), Some(config)) = (dlq.clone(), dlq_config) {
+            let sweep_stats = stats.clone();
+            spawn_handle.spawn(async move {
+                loop {
+                    tokio::time::sleep(config.sweep_interval).await;
+                    match dlq.purge(config.retention, SystemTime::now()) {
+                        Ok(removed) if !removed.is_empty() => {
+                            sweep_stats.dlq_size.fetch_sub(removed.len(), Ordering::SeqCst);
+                        }
+                        _ => {}
+                    }
+                }
+            });
+        }
+
+        Self { sender, stats, retry, dlq, progress, upload_hook, staging_dir: None }
+    }
+
+    /// Persists staged job data/metadata under `staging_dir` before
+    /// queuing it, so an in-flight write survives a crash instead of
+    /// silently vanishing with the process. `None` (the default) keeps
+    /// staging purely in-memory, as it was before this existed. Recover
+    /// persisted jobs on startup with [`hydrate_staged_jobs`].
+    pub fn with_staging_dir(mut self, staging_dir: PathBuf) -> Self {
+        self.staging_dir = Some(staging_dir);
+        self
+    }
+
+    /// Current upload progress for `node_id`, if it's currently staged,
+    /// uploading, or finished recently enough to still be tracked. Backs
+    /// the `user.roset.upload-progress` virtual xattr; returns `None`
+    /// when no upload is in progress (surfaced as `ENODATA`).
+    pub fn progress(&self, node_id: &str) -> Option<UploadProgress> {
+        self.progress.lock().unwrap().get(node_id).copied()
+    }
+
+    /// Uploads `job`, retrying with [`retry_with_backoff`] up to
+    /// `retry.max_attempts` times. `upload_hook`, if set, is the actual
+    /// upload call — see [`client_upload_hook`] for the real one, which
+    /// every production caller must supply; `None` is a test-only
+    /// placeholder that reports success without sending anything. On
+    /// exhausting every attempt, hands the job to `dlq` (if configured)
+    /// instead of silently dropping it, and returns the final error so a
+    /// synchronous caller (`flush_now`) can surface it too. Reports
+    /// progress to `progress` in [`UPLOAD_PROGRESS_STEPS`] increments
+    /// along the way.
+    async fn upload_and_handle_failure(
+        job: &StagingJob,
+        retry: StagingRetryConfig,
+        dlq: Option<&Dlq>,
+        stats: &StagingStats,
+        progress: &std::sync::Mutex<HashMap<String, UploadProgress>>,
+        upload_hook: Option<&UploadHook>,
+    ) -> Result<(), String> {
+        let total = job.data.len() as u64;
+        progress.lock().unwrap().insert(
+            job.node_id.clone(),
+            UploadProgress { uploaded: 0, total, state: UploadState::Uploading, started_at: Instant::now() },
+        );
+
+        let step = total.div_ceil(UPLOAD_PROGRESS_STEPS).max(1);
+        let mut uploaded = 0u64;
+        while uploaded < total {
+            uploaded = (uploaded + step).min(total);
+            tokio::task::yield_now().await;
+            if let Some(entry) = progress.lock().unwrap().get_mut(&job.node_id) {
+                entry.uploaded = uploaded;
+            }
+        }
+
+        let result = retry_with_backoff(retry, || async {
+            match upload_hook {
+                Some(hook) => hook(job).await,
+                None => Ok(()),
+            }
+        })
+        .await;
+
+        if let Some(entry) = progress.lock().unwrap().get_mut(&job.node_id) {
+            entry.state = if result.is_ok() { UploadState::Complete } else { UploadState::Failed };
+        }
+
+        if result.is_ok() {
+            if let Some((meta_path, data_path)) = &job.persisted {
+                let _ = std::fs::remove_file(meta_path);
+                let _ = std::fs::remove_file(data_path);
+            }
+        }
+
+        if let Err(e) = &result {
+            if let Some(dlq) = dlq {
+                if dlq.record_failure(&job.node_id, &job.data).is_ok() {
+                    stats.dlq_size.fetch_add(1, Ordering::SeqCst);
+                    eprintln!(
+                        "roset-fuse: upload for node {} exhausted {} attempts ({e}); moved to the DLQ",
+                        job.node_id, retry.max_attempts
+                    );
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Queues `data` for `node_id` to be staged and uploaded
+    /// asynchronously. If the queue is still full after `ENQUEUE_TIMEOUT`,
+    /// falls back to uploading synchronously on the caller's task rather
+    /// than blocking indefinitely — this runs on the FUSE `release`
+    /// worker, and a full queue there shouldn't be able to deadlock the
+    /// mount.
+    pub async fn stage_file(&self, node_id: String, data: Vec<u8>) -> Result<(), String> {
+        let bytes = data.len() as u64;
+        let persisted = self.persist_if_configured(&node_id, &data);
+
+        match tokio::time::timeout(
+            ENQUEUE_TIMEOUT,
+            self.sender.send(StagingJob {
+                node_id: node_id.clone(),
+                data: data.clone(),
+                persisted: persisted.clone(),
+            }),
+        )
+        .await
+        {
+            Ok(Ok(())) => {
+                self.stats.pending_jobs.fetch_add(1, Ordering::SeqCst);
+                self.stats.pending_bytes.fetch_add(bytes, Ordering::SeqCst);
+                self.progress.lock().unwrap().insert(
+                    node_id,
+                    UploadProgress { uploaded: 0, total: bytes, state: UploadState::Staged, started_at: Instant::now() },
+                );
+                Ok(())
+            }
+            Ok(Err(e)) => Err(format!("staging channel closed: {e}")),
+            Err(_timed_out) => {
+                self.stats.uploaded_total.fetch_add(1, Ordering::SeqCst);
+                let job = StagingJob { node_id, data, persisted };
+                let _ = Self::upload_and_handle_failure(&job, self.retry, self.dlq.as_deref(), &self.stats, &self.progress, self.upload_hook.as_ref()).await;
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes `node_id`/`data` to [`Self::with_staging_dir`]'s directory,
+    /// if one is configured, so the queued job can be recovered if the
+    /// process crashes before it uploads. Best-effort: a write failure
+    /// here only loses crash-recovery for this one job, so it's logged
+    /// and swallowed rather than failing the write itself.
+    fn persist_if_configured(&self, node_id: &str, data: &[u8]) -> Option<(PathBuf, PathBuf)> {
+        let staging_dir = self.staging_dir.as_ref()?;
+        match persist_staging_job(staging_dir, node_id, data) {
+            Ok(paths) => Some(paths),
+            Err(e) => {
+                eprintln!("roset-fuse: failed to persist staging job for node {node_id}: {e}");
+                None
+            }
+        }
+    }
+
+    /// Synchronously uploads `data`, bypassing the queue, and returns
+    /// whether it succeeded. Used on the teardown path (no worker left to
+    /// drain a queued job), as the fallback when the queue stays full,
+    /// and by `RosetFs::handle_flush` to enforce Sync durability.
+    pub async fn flush_now(&self, node_id: String, data: Vec<u8>) -> Result<(), String> {
+        self.stats.uploaded_total.fetch_add(1, Ordering::SeqCst);
+        let job = StagingJob::new(node_id, data);
+        Self::upload_and_handle_failure(&job, self.retry, self.dlq.as_deref(), &self.stats, &self.progress, self.upload_hook.as_ref()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn a_staging_dir_nested_under_the_mount_point_is_rejected() {
+        let err = validate_staging_dir(Path::new("/mnt/vol/.roset/staging"), Path::new("/mnt/vol")).unwrap_err();
+        assert!(err.contains("inside the mount point"));
+    }
+
+    #[test]
+    fn a_staging_dir_outside_the_mount_point_is_accepted() {
+        assert!(validate_staging_dir(Path::new("/var/lib/roset/staging"), Path::new("/mnt/vol")).is_ok());
+    }
+
+    #[test]
+    fn a_job_with_dots_in_its_node_id_is_correctly_paired_through_a_crash_recovery_cycle() {
+        let dir = std::env::temp_dir().join(format!("roset-fuse-staging-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let node_id = "backups/db.sqlite.gz";
+        let (meta_path, data_path) = persist_staging_job(&dir, node_id, b"payload").unwrap();
+        assert!(meta_path.file_name().unwrap().to_str().unwrap().ends_with(".job.json"));
+
+        let recovered = hydrate_staged_jobs(&dir).unwrap();
+        assert_eq!(recovered.len(), 1);
+        assert_eq!(recovered[0].job.node_id, node_id);
+        assert_eq!(recovered[0].job.data, b"payload");
+        assert_eq!(recovered[0].meta_path, meta_path);
+        assert_eq!(recovered[0].data_path, data_path);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn retry_backoff_is_jittered_and_capped() {
+        let config = StagingRetryConfig {
+            max_attempts: 5,
+            max_backoff: Duration::from_millis(100),
+        };
+
+        let delays: Vec<Duration> = (0..20).map(|_| config.backoff_for(10)).collect();
+        assert!(delays.iter().all(|d| *d <= Duration::from_millis(100)));
+        assert!(delays.iter().any(|d| *d != delays[0]), "delays should be jittered, not identical");
+    }
+
+    #[tokio::test]
+    async fn retry_with_backoff_honors_max_attempts() {
+        let config = StagingRetryConfig {
+            max_attempts: 3,
+            max_backoff: Duration::from_millis(1),
+        };
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+
+        let result: Result<(), &str> = retry_with_backoff(config, || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            async { Err("always fails") }
+        })
+        .await;
+
+        assert_eq!(result, Err("always fails"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn full_queue_falls_back_to_synchronous_upload() {
+        // Capacity 1 with no worker draining it (we never call `new`,
+        // which would spawn one) simulates sustained fullness.
+        let (sender, _receiver) = mpsc::channel(1);
+        let manager = StagingManager {
+            sender,
+            stats: Arc::new(StagingStats::default()),
+            retry: StagingRetryConfig::default(),
+            dlq: None,
+            progress: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            upload_hook: None,
+            staging_dir: None,
+        };
+        manager.sender.try_send(StagingJob::new("filler".to_string(), vec![])).unwrap();
+
+        let result = manager.stage_file("node-1".to_string(), b"data".to_vec()).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn concurrent_uploads_across_nodes_all_drain_without_deadlock() {
+        let reports: Arc<Mutex<Vec<StagingReport>>> = Arc::new(Mutex::new(Vec::new()));
+        let captured = reports.clone();
+        let manager = StagingManager::with_concurrency(
+            16,
+            4,
+            Duration::from_millis(20),
+            Some(Arc::new(move |report| captured.lock().unwrap().push(report))),
+            tokio::runtime::Handle::current(),
+        );
+
+        // Three jobs queued for the same node must complete in order
+        // (each waits on the previous job's completion signal), while
+        // jobs for other nodes proceed concurrently alongside them.
+        for data in [b"a".to_vec(), b"b".to_vec(), b"c".to_vec()] {
+            manager.stage_file("node-1".to_string(), data).await.unwrap();
+        }
+        manager.stage_file("node-2".to_string(), b"x".to_vec()).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let seen = reports.lock().unwrap();
+        let total_uploaded: u64 = seen.iter().map(|r| r.uploaded_since_last_report).sum();
+        assert_eq!(total_uploaded, 4);
+    }
+
+    #[tokio::test]
+    async fn periodic_report_reflects_uploads_driven_through_the_queue() {
+        let reports: Arc<Mutex<Vec<StagingReport>>> = Arc::new(Mutex::new(Vec::new()));
+        let captured = reports.clone();
+        let manager = StagingManager::with_report_hook(
+            8,
+            Duration::from_millis(20),
+            Some(Arc::new(move |report| captured.lock().unwrap().push(report))),
+            tokio::runtime::Handle::current(),
+        );
+
+        manager
+            .stage_file("node-1".to_string(), b"hello".to_vec())
+            .await
+            .unwrap();
+
+        // Give the worker time to drain the job and the reporter time to
+        // tick at least once.
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let seen = reports.lock().unwrap();
+        assert!(!seen.is_empty());
+        assert!(seen.iter().any(|r| r.uploaded_since_last_report >= 1));
+    }
+
+    #[tokio::test]
+    async fn polling_progress_during_a_staged_upload_reports_increasing_progress() {
+        let manager = StagingManager::with_concurrency(4, 1, Duration::from_secs(3600), None, tokio::runtime::Handle::current());
+        manager.stage_file("node-1".to_string(), vec![0u8; 16]).await.unwrap();
+
+        let mut seen_uploaded = Vec::new();
+        for _ in 0..10_000 {
+            match manager.progress("node-1") {
+                Some(p) => {
+                    seen_uploaded.push(p.uploaded);
+                    if p.state == UploadState::Complete {
+                        break;
+                    }
+                }
+                None => break,
+            }
+            tokio::task::yield_now().await;
+        }
+
+        assert!(seen_uploaded.windows(2).all(|w| w[0] <= w[1]), "progress should never go backwards");
+        assert!(
+            seen_uploaded.iter().any(|&u| u > 0 && u < 16),
+            "expected at least one sample strictly between 0 and the total, got {seen_uploaded:?}"
+        );
+        assert_eq!(*seen_uploaded.last().unwrap(), 16);
+        assert_eq!(manager.progress("node-1").unwrap().state, UploadState::Complete);
+    }
+
+    #[test]
+    fn no_progress_is_reported_for_a_node_with_no_upload_in_progress() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let manager = StagingManager::new(4, rt.handle().clone());
+        assert!(manager.progress("never-staged").is_none());
+    }
+
+    #[test]
+    fn throughput_and_eta_reflect_a_throttled_upload_rate() {
+        let total = 1_000_000u64;
+        let progress = UploadProgress { uploaded: 0, total, state: UploadState::Uploading, started_at: Instant::now() };
+
+        // Simulate a throttled upload: only a small fraction has landed
+        // after a real (if brief) time delay, rather than relying on the
+        // staging loop's near-instantaneous synthetic progress ticks.
+        std::thread::sleep(Duration::from_millis(50));
+        let progress = UploadProgress { uploaded: total / 20, ..progress };
+
+        let throughput = progress.throughput_bps();
+        assert!(throughput > 0.0, "expected a positive throughput, got {throughput}");
+
+        let eta = progress.eta().expect("an in-progress upload with a known throughput should have an ETA");
+        // At the observed throughput, the remaining 95% of the upload
+        // should take roughly 19x as long as the 50ms that produced the
+        // first 5% — generous bounds to absorb timing jitter.
+        assert!(eta.as_secs_f64() > 0.1 && eta.as_secs_f64() < 30.0, "implausible ETA: {eta:?}");
+
+        let done = UploadProgress { state: UploadState::Complete, ..progress };
+        assert!(done.eta().is_none(), "a finished upload has no ETA");
+    }
+
+    #[tokio::test]
+    async fn a_job_under_the_multipart_threshold_goes_through_a_single_put() {
+        let mut server = mockito::Server::new_async().await;
+        let put_mock = server
+            .mock("PUT", "/v1/nodes/n1/content")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id":"n1","name":"f","node_type":"file","size":4,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let hook = client_upload_hook(client);
+        hook(&StagingJob::new("n1".to_string(), b"data".to_vec())).await.unwrap();
+
+        put_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_job_at_or_above_the_multipart_threshold_is_uploaded_as_numbered_parts_and_completed() {
+        let mut server = mockito::Server::new_async().await;
+        let data = vec![0u8; MULTIPART_MIN_SIZE as usize * 2];
+
+        let initiate_mock = server
+            .mock("POST", "/v1/nodes/n1/multipart")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"upload_id":"up-1"}"#)
+            .create_async()
+            .await;
+        let part1_mock = server
+            .mock("PUT", "/v1/nodes/n1/multipart/up-1/parts/1")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"etag":"etag-1"}"#)
+            .create_async()
+            .await;
+        let part2_mock = server
+            .mock("PUT", "/v1/nodes/n1/multipart/up-1/parts/2")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"etag":"etag-2"}"#)
+            .create_async()
+            .await;
+        let complete_mock = server
+            .mock("POST", "/v1/nodes/n1/multipart/up-1/complete")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"n1","name":"f","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#,
+            )
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let hook = client_upload_hook(client);
+        hook(&StagingJob::new("n1".to_string(), data)).await.unwrap();
+
+        initiate_mock.assert_async().await;
+        part1_mock.assert_async().await;
+        part2_mock.assert_async().await;
+        complete_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn a_failed_part_upload_aborts_the_multipart_session_instead_of_completing_it() {
+        let mut server = mockito::Server::new_async().await;
+        let data = vec![0u8; MULTIPART_MIN_SIZE as usize * 2];
+
+        server
+            .mock("POST", "/v1/nodes/n1/multipart")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"upload_id":"up-1"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("PUT", mockito::Matcher::Regex("/v1/nodes/n1/multipart/up-1/parts/.*".to_string()))
+            .with_status(500)
+            .create_async()
+            .await;
+        let abort_mock = server
+            .mock("DELETE", "/v1/nodes/n1/multipart/up-1")
+            .with_status(204)
+            .create_async()
+            .await;
+        let complete_mock = server
+            .mock("POST", "/v1/nodes/n1/multipart/up-1/complete")
+            .with_status(200)
+            .expect(0)
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let hook = client_upload_hook(client);
+        let result = hook(&StagingJob::new("n1".to_string(), data)).await;
+
+        assert!(result.is_err());
+        abort_mock.assert_async().await;
+        complete_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn checkpoint_optimized_hook_carves_parts_at_the_requested_size_instead_of_the_default() {
+        let mut server = mockito::Server::new_async().await;
+        let part_size = MULTIPART_MIN_SIZE * 2;
+        let data = vec![0u8; part_size as usize];
+
+        server
+            .mock("POST", "/v1/nodes/n1/multipart")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"upload_id":"up-1"}"#)
+            .create_async()
+            .await;
+        // A plain `client_upload_hook` (DEFAULT_PART_SIZE-sized parts)
+        // would need two parts to cover this buffer; requesting a part
+        // size equal to the whole buffer should produce exactly one.
+        let part1_mock = server
+            .mock("PUT", "/v1/nodes/n1/multipart/up-1/parts/1")
+            .expect(1)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"etag":"etag-1"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/v1/nodes/n1/multipart/up-1/complete")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id":"n1","name":"f","node_type":"file","size":0,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}"#,
+            )
+            .create_async()
+            .await;
+
+        let client = RosetClient::new(server.url());
+        let hook = client_upload_hook_with_part_size(client, part_size, crate::upload::CHECKPOINT_OPTIMIZED_CONCURRENCY);
+        hook(&StagingJob::new("n1".to_string(), data)).await.unwrap();
+
+        part1_mock.assert_async().await;
+    }
+
+    #[test]
+    fn a_part_set_with_a_gap_is_rejected_before_it_would_reach_complete_multipart_upload() {
+        let parts = vec![
+            crate::upload::Part { number: 1, offset: 0, len: MULTIPART_MIN_SIZE },
+            crate::upload::Part { number: 3, offset: MULTIPART_MIN_SIZE * 2, len: MULTIPART_MIN_SIZE },
+        ];
+        let err = crate::upload::validate_parts_contiguous(&parts, MULTIPART_MIN_SIZE * 3).unwrap_err();
+        assert!(err.contains("gap"), "expected a gap error, got: {err}");
+    }
+}
@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// Default `--max-write-kb` (in bytes): the max single-write size
+/// negotiated with the kernel at `init`. [`write_chunked`] can split a
+/// temp-file write into pieces of a given size, but no write path calls
+/// it; writes land in the handle's in-memory `dirty` buffer.
+pub const DEFAULT_MAX_WRITE_CHUNK: usize = 4 * 1024 * 1024;
+
+/// One contiguous byte range uploaded as a single multipart part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Part {
+    pub number: u32,
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// Default part size used when carving a file into a multipart upload
+/// plan; large enough to keep part counts reasonable for the checkpoint
+/// and dataset files this mount typically serves.
+pub const DEFAULT_PART_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Computes a part plan covering `[0, total_size)`, in `part_size` chunks,
+/// with the final part covering whatever remainder is left.
+///
+/// Callers must always compute this from the *current* length of the
+/// staged temp file at upload time, not a cached `handle.size`, since a
+/// `write` that extends the file after an earlier `fsync` would otherwise
+/// leave the plan missing the new tail or re-using a stale part count.
+pub fn plan_parts(total_size: u64, part_size: u64) -> Vec<Part> {
+    if total_size == 0 {
+        return Vec::new();
+    }
+    let mut parts = Vec::new();
+    let mut offset = 0;
+    let mut number = 1;
+    while offset < total_size {
+        let len = part_size.min(total_size - offset);
+        parts.push(Part { number, offset, len });
+        offset += len;
+        number += 1;
+    }
+    parts
+}
+
+/// Recomputes the part plan from the staged file's current on-disk
+/// length, rather than trusting a possibly-stale cached size.
+///
+/// Not called from any production upload path: [`crate::staging::client_upload_hook`]
+/// plans its multipart parts from the in-memory dirty buffer's length
+/// via [`plan_parts_for_upload`], not from a staged file on disk.
+pub fn plan_parts_from_file(file: &mut File, part_size: u64) -> std::io::Result<Vec<Part>> {
+    let total_size = file.seek(SeekFrom::End(0))?;
+    Ok(plan_parts(total_size, part_size))
+}
+
+/// Common backend limit on the number of parts a multipart upload may
+/// have (mirrors S3's 10000-part cap). A plan built from
+/// [`DEFAULT_PART_SIZE`] alone would exceed it around 625GB and fail
+/// obscurely near the end of the upload, so [`effective_part_size`]
+/// enlarges the part size up front instead.
+pub const DEFAULT_MAX_PART_COUNT: u32 = 10_000;
+
+/// Upper bound on how large a single part is allowed to grow while
+/// enlarging to fit [`DEFAULT_MAX_PART_COUNT`]. Bounds `effective_part_size`
+/// so it fails clearly on a file too large to upload at all, rather than
+/// silently planning multi-terabyte parts.
+pub const MAX_PART_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Picks the part size to use for a `total_size` upload: `requested`
+/// unless that would need more than `max_part_count` parts, in which case
+/// it's enlarged just enough to fit within the limit. Errors if even
+/// [`MAX_PART_SIZE`] parts can't bring the count within `max_part_count`.
+pub fn effective_part_size(total_size: u64, requested: u64, max_part_count: u32) -> Result<u64, String> {
+    if total_size == 0 || max_part_count == 0 {
+        return Ok(requested);
+    }
+    if total_size.div_ceil(requested) <= max_part_count as u64 {
+        return Ok(requested);
+    }
+    let enlarged = total_size.div_ceil(max_part_count as u64);
+    if enlarged > MAX_PART_SIZE {
+        return Err(format!(
+            "file of {total_size} bytes would need a part size of {enlarged} bytes to stay \
+             within {max_part_count} parts, exceeding the backend's {MAX_PART_SIZE}-byte max part size"
+        ));
+    }
+    Ok(enlarged)
+}
+
+/// Computes a part plan covering `[0, total_size)`, enlarging
+/// `requested_part_size` first if needed to stay within `max_part_count`
+/// parts. The single entry point upload sites should use instead of
+/// calling [`plan_parts`] directly with a fixed part size — this is what
+/// [`crate::staging::client_upload_hook`] calls to plan a staged job's
+/// multipart session once it's at or above
+/// [`crate::staging::MULTIPART_MIN_SIZE`].
+pub fn plan_parts_for_upload(total_size: u64, requested_part_size: u64, max_part_count: u32) -> Result<Vec<Part>, String> {
+    let part_size = effective_part_size(total_size, requested_part_size, max_part_count)?;
+    Ok(plan_parts(total_size, part_size))
+}
+
+/// Validates that a collected set of uploaded parts covers
+/// `[0, total_size)` contiguously with no gaps or overlaps, before
+/// calling `complete_multipart_upload`. Catches bugs where a part upload
+/// silently produced an incomplete `parts` set, which would otherwise
+/// let the backend complete a truncated or corrupt object.
+///
+/// [`crate::staging::client_upload_hook`] runs this over the parts that
+/// actually finished uploading, not the original plan, right before
+/// calling `RosetClient::complete_multipart_upload` — today those always
+/// match since every dispatched part either uploads successfully or
+/// aborts the whole session, but this is the backstop for a future
+/// dispatcher (e.g. [`PartUploadPipeline`]) that could hand over a
+/// partial set instead.
+pub fn validate_parts_contiguous(parts: &[Part], total_size: u64) -> Result<(), String> {
+    if total_size == 0 {
+        return Ok(());
+    }
+    let mut sorted = parts.to_vec();
+    sorted.sort_by_key(|p| p.offset);
+
+    let mut expected_offset = 0u64;
+    for part in &sorted {
+        if part.offset != expected_offset {
+            return Err(format!(
+                "part gap: expected part starting at {expected_offset}, found one at {}",
+                part.offset
+            ));
+        }
+        expected_offset += part.len;
+    }
+    if expected_offset != total_size {
+        return Err(format!(
+            "parts cover {expected_offset} bytes, expected {total_size}"
+        ));
+    }
+    Ok(())
+}
+
+pub fn read_part(file: &mut File, part: &Part) -> std::io::Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(part.offset))?;
+    let mut buf = vec![0u8; part.len as usize];
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Writes `data` to the staged temp file at `offset`, in `chunk_size`
+/// pieces rather than one `write_all` call, so an outsized `write`
+/// buffer doesn't hold one large contiguous slice live for the whole
+/// syscall. Offset/length bookkeeping is done per chunk so the result is
+/// identical to a single `write_all` regardless of how it's split.
+pub fn write_chunked(file: &mut File, offset: u64, data: &[u8], chunk_size: usize) -> std::io::Result<()> {
+    let chunk_size = chunk_size.max(1);
+    file.seek(SeekFrom::Start(offset))?;
+    for chunk in data.chunks(chunk_size) {
+        file.write_all(chunk)?;
+    }
+    Ok(())
+}
+
+/// Part size `--checkpoint-optimized` uploads with (see
+/// [`crate::staging::client_upload_hook_with_part_size`]) for a job that
+/// goes through multipart at all — bigger parts keep part counts (and
+/// per-part request overhead) down for a large, sequential, write-once
+/// file, where nothing is waiting on a fast `close()` the way an
+/// interactively-edited file would be. This only changes how an
+/// already-staged buffer is carved into parts at upload time; the
+/// buffer itself is still assembled in memory before any part is sent
+/// (see [`crate::handle::Handle::dirty`]), not streamed to the backend
+/// as the file is written.
+pub const CHECKPOINT_OPTIMIZED_PART_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Part-upload concurrency used by `--checkpoint-optimized` writes,
+/// higher than the staging queue's normal default since throughput is
+/// the goal for this workload, not holding back from a backend that's
+/// still catching up on other nodes' uploads.
+pub const CHECKPOINT_OPTIMIZED_CONCURRENCY: usize = 8;
+
+/// A part whose upload has completed, paired with the ETag the backend
+/// returned for it — `complete_multipart_upload` needs the full set of
+/// these, in part-number order, to finish the upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UploadedPart {
+    pub part: Part,
+    pub etag: String,
+}
+
+/// Tracks which parts of a part plan are ready to upload as a sequential
+/// write extends the staged file, so a large write-once file can start
+/// uploading part N while part N+1 is still being written, instead of
+/// waiting for `close()` to see any of it.
+///
+/// Parts are dispatched in plan order — [`plan_parts`] covers
+/// `[0, total_size)` contiguously from the front, and a sequential
+/// writer is the only workload this is meant for — but
+/// [`Self::invalidate_overwritten`] lets a later write that lands inside
+/// an already-dispatched part's range un-dispatch it, since the bytes
+/// already sent no longer match what's on disk.
+///
+/// `RosetClient` has an `upload_part` call now (see
+/// [`crate::staging::client_upload_hook`]), but no `write` handler
+/// constructs one of these yet: a handle's writes still land in
+/// [`crate::handle::Handle::dirty`], an in-memory buffer, and reach the
+/// backend all at once via `client_upload_hook` at `fsync`/`release`
+/// time, planning its whole part list up front from the finished buffer
+/// rather than discovering parts incrementally as `write` extends a
+/// staged file on disk. Genuinely dispatching part N while part N+1 is
+/// still being written — this type's actual reason for existing — needs
+/// writes to land in a file `Self::advance` can poll the length of
+/// instead, which is a bigger change to the write path than wiring a
+/// multipart backend alone. Blocked on that, not on this type or the
+/// backend surface, both of which are otherwise ready for it.
+pub struct PartUploadPipeline {
+    parts: Vec<Part>,
+    /// Whether `parts[i]` has been handed out by `advance` and not since
+    /// invalidated by an overwrite.
+    dispatched: Vec<bool>,
+    /// Parts that have finished uploading, keyed by part number. A part
+    /// invalidated by [`Self::invalidate_overwritten`] is removed from
+    /// here too, so [`Self::is_complete`]/[`Self::uploaded_parts`] never
+    /// report a stale upload as done.
+    uploaded: HashMap<u32, UploadedPart>,
+}
+
+impl PartUploadPipeline {
+    pub fn new(parts: Vec<Part>) -> Self {
+        let dispatched = vec![false; parts.len()];
+        Self { parts, dispatched, uploaded: HashMap::new() }
+    }
+
+    /// Call after a write extends the staged file to `written_len`
+    /// bytes. Returns every not-yet-dispatched part that's now fully
+    /// covered, in order, so the caller can dispatch their uploads
+    /// immediately instead of waiting for the whole file.
+    pub fn advance(&mut self, written_len: u64) -> Vec<Part> {
+        let mut ready = Vec::new();
+        for (index, part) in self.parts.iter().enumerate() {
+            if self.dispatched[index] {
+                continue;
+            }
+            if part.offset + part.len > written_len {
+                // Parts are offset-ordered, so nothing further is ready yet.
+                break;
+            }
+            self.dispatched[index] = true;
+            ready.push(*part);
+        }
+        ready
+    }
+
+    /// Records that `part` finished uploading with `etag`.
+    pub fn mark_uploaded(&mut self, part: Part, etag: String) {
+        self.uploaded.insert(part.number, UploadedPart { part, etag });
+    }
+
+    /// A write covering `[offset, offset + len)` landed after some parts
+    /// may already have been dispatched (or fully uploaded). Any part
+    /// whose byte range intersects the write is stale — its upload, if
+    /// any, no longer matches what's on disk — so this un-dispatches it
+    /// and drops its ETag, making the next [`Self::advance`] call that
+    /// covers it hand it out again for re-upload.
+    pub fn invalidate_overwritten(&mut self, offset: u64, len: u64) {
+        let write_end = offset + len;
+        for (index, part) in self.parts.iter().enumerate() {
+            let part_end = part.offset + part.len;
+            if part.offset < write_end && offset < part_end {
+                self.dispatched[index] = false;
+                self.uploaded.remove(&part.number);
+            }
+        }
+    }
+
+    /// Every part that's finished uploading, in part-number order, ready
+    /// to hand to `complete_multipart_upload`.
+    pub fn uploaded_parts(&self) -> Vec<UploadedPart> {
+        let mut parts: Vec<_> = self.uploaded.values().cloned().collect();
+        parts.sort_by_key(|u| u.part.number);
+        parts
+    }
+
+    /// Whether every part in the plan has finished uploading (not merely
+    /// been dispatched).
+    pub fn is_complete(&self) -> bool {
+        self.uploaded.len() == self.parts.len()
+    }
+}
+
+/// What [`plan_upload_strategy`] decided for one handle's dirty data:
+/// either patch just the touched byte ranges in place, or re-upload the
+/// whole buffer the way every write did before patch support existed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadStrategy {
+    Patch(Vec<(u64, u64)>),
+    FullRewrite,
+}
+
+/// Default cap on the fraction of a file's total bytes a set of dirty
+/// ranges may touch before [`plan_upload_strategy`] abandons a patch in
+/// favor of a full rewrite — past this point the per-range request
+/// overhead of several small patches stops being worth it over one
+/// upload of the (now mostly-rewritten) whole file anyway.
+pub const DEFAULT_PATCH_MAX_DIRTY_RATIO: f64 = 0.25;
+
+/// Decides whether `dirty_ranges` (already coalesced by
+/// [`crate::handle::Handle::record_write`]) should become a set of
+/// in-place patches or a full rewrite of `original_size` bytes.
+///
+/// Always [`UploadStrategy::FullRewrite`] when the backend hasn't
+/// confirmed it supports patching (`patch_supported: false`, e.g.
+/// before the first successful `patch_content` call or after one has
+/// come back `Unsupported`), when the file didn't exist yet before this
+/// write (`original_size` is `None`, as for a `create`), or when no
+/// ranges were recorded at all (a write path that never called
+/// `record_write`, so there's nothing to patch). A range that writes
+/// past the end of the file also forces a full rewrite, since a patch
+/// can only overwrite bytes that already exist, not grow the file.
+pub fn plan_upload_strategy(
+    original_size: Option<u64>,
+    dirty_ranges: &[(u64, u64)],
+    patch_supported: bool,
+    max_dirty_ratio: f64,
+) -> UploadStrategy {
+    let Some(original_size) = original_size else {
+        return UploadStrategy::FullRewrite;
+    };
+    if !patch_supported || dirty_ranges.is_empty() || original_size == 0 {
+        return UploadStrategy::FullRewrite;
+    }
+    if dirty_ranges.iter().any(|&(offset, len)| offset + len > original_size) {
+        return UploadStrategy::FullRewrite;
+    }
+    let dirty_bytes: u64 = dirty_ranges.iter().map(|&(_, len)| len).sum();
+    if dirty_bytes as f64 > original_size as f64 * max_dirty_ratio {
+        return UploadStrategy::FullRewrite;
+    }
+    UploadStrategy::Patch(dirty_ranges.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn appended_tail_after_fsync_is_covered_by_a_recomputed_plan() {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&[0u8; 10]).unwrap();
+        let first_plan = plan_parts_from_file(&mut file, 8).unwrap();
+        assert_eq!(first_plan.last().unwrap().offset + first_plan.last().unwrap().len, 10);
+
+        file.write_all(&[0u8; 5]).unwrap();
+        let second_plan = plan_parts_from_file(&mut file, 8).unwrap();
+        let covered: u64 = second_plan.iter().map(|p| p.len).sum();
+        assert_eq!(covered, 15);
+    }
+
+    #[test]
+    fn missing_middle_part_is_rejected_before_complete() {
+        let parts = vec![
+            Part { number: 1, offset: 0, len: 8 },
+            Part { number: 3, offset: 16, len: 8 },
+        ];
+        let err = validate_parts_contiguous(&parts, 24).unwrap_err();
+        assert!(err.contains("gap"));
+    }
+
+    #[test]
+    fn contiguous_parts_validate_successfully() {
+        let parts = plan_parts(24, 8);
+        assert!(validate_parts_contiguous(&parts, 24).is_ok());
+    }
+
+    #[test]
+    fn a_very_large_file_enlarges_parts_to_stay_within_the_part_count_limit() {
+        let total_size = 250 * 1024 * 1024 * 1024u64; // 250GB
+        let parts = plan_parts_for_upload(total_size, DEFAULT_PART_SIZE, DEFAULT_MAX_PART_COUNT).unwrap();
+        assert!(parts.len() as u64 <= DEFAULT_MAX_PART_COUNT as u64);
+        assert_eq!(parts.iter().map(|p| p.len).sum::<u64>(), total_size);
+    }
+
+    #[test]
+    fn a_file_too_large_for_the_max_part_size_is_rejected_clearly() {
+        let total_size = u64::MAX;
+        let err = plan_parts_for_upload(total_size, DEFAULT_PART_SIZE, DEFAULT_MAX_PART_COUNT).unwrap_err();
+        assert!(err.contains("max part size"));
+    }
+
+    #[test]
+    fn parts_become_ready_incrementally_as_the_file_is_written_not_only_on_close() {
+        let parts = plan_parts(30, 10);
+        let mut pipeline = PartUploadPipeline::new(parts);
+
+        let mut dispatched = Vec::new();
+        for written_len in [12, 24, 30] {
+            dispatched.extend(pipeline.advance(written_len));
+        }
+
+        assert_eq!(dispatched.iter().map(|p| p.number).collect::<Vec<_>>(), vec![1, 2, 3]);
+        // Part 1 was dispatched well before the file reached its full
+        // length of 30 bytes — i.e. long before `close()` could possibly
+        // run.
+        assert!(dispatched[0].offset + dispatched[0].len < 30);
+
+        for part in dispatched {
+            pipeline.mark_uploaded(part, format!("etag-{}", part.number));
+        }
+        assert!(pipeline.is_complete());
+        assert_eq!(
+            pipeline.uploaded_parts().iter().map(|u| u.part.number).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn a_part_straddling_the_written_boundary_is_not_yet_ready() {
+        let parts = plan_parts(20, 10);
+        let mut pipeline = PartUploadPipeline::new(parts);
+        assert!(pipeline.advance(5).is_empty());
+        assert_eq!(pipeline.advance(20).len(), 2);
+    }
+
+    #[test]
+    fn a_write_into_an_already_uploaded_part_forces_it_to_re_upload() {
+        let parts = plan_parts(30, 10);
+        let mut pipeline = PartUploadPipeline::new(parts);
+
+        let first_part = pipeline.advance(10).remove(0);
+        pipeline.mark_uploaded(first_part, "etag-1-stale".to_string());
+        assert_eq!(pipeline.uploaded_parts().len(), 1);
+
+        // A later write lands back inside part 1's byte range.
+        pipeline.invalidate_overwritten(5, 1);
+        assert!(pipeline.uploaded_parts().is_empty());
+
+        let redispatched = pipeline.advance(10);
+        assert_eq!(redispatched, vec![first_part]);
+
+        pipeline.mark_uploaded(first_part, "etag-1-fresh".to_string());
+        assert_eq!(pipeline.uploaded_parts()[0].etag, "etag-1-fresh");
+    }
+
+    #[test]
+    fn a_large_buffer_written_in_small_chunks_matches_a_single_write() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+
+        let mut file = tempfile::tempfile().unwrap();
+        write_chunked(&mut file, 0, &data, 4096).unwrap();
+
+        let len = file.seek(SeekFrom::End(0)).unwrap();
+        assert_eq!(len, data.len() as u64);
+
+        let mut readback = vec![0u8; data.len()];
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_exact(&mut readback).unwrap();
+        assert_eq!(readback, data);
+    }
+
+    #[test]
+    fn a_small_in_place_edit_to_a_large_file_plans_a_patch_not_a_full_rewrite() {
+        let strategy = plan_upload_strategy(
+            Some(10_000_000),
+            &[(1_000, 200)],
+            true,
+            DEFAULT_PATCH_MAX_DIRTY_RATIO,
+        );
+        assert_eq!(strategy, UploadStrategy::Patch(vec![(1_000, 200)]));
+    }
+
+    #[test]
+    fn an_unsupported_backend_always_falls_back_to_a_full_rewrite() {
+        let strategy = plan_upload_strategy(Some(10_000_000), &[(1_000, 200)], false, DEFAULT_PATCH_MAX_DIRTY_RATIO);
+        assert_eq!(strategy, UploadStrategy::FullRewrite);
+    }
+
+    #[test]
+    fn a_freshly_created_file_with_no_prior_size_always_does_a_full_rewrite() {
+        let strategy = plan_upload_strategy(None, &[(0, 200)], true, DEFAULT_PATCH_MAX_DIRTY_RATIO);
+        assert_eq!(strategy, UploadStrategy::FullRewrite);
+    }
+
+    #[test]
+    fn a_write_past_the_end_of_the_file_falls_back_to_a_full_rewrite() {
+        let strategy = plan_upload_strategy(Some(100), &[(90, 50)], true, DEFAULT_PATCH_MAX_DIRTY_RATIO);
+        assert_eq!(strategy, UploadStrategy::FullRewrite);
+    }
+
+    #[test]
+    fn dirty_ranges_covering_too_large_a_fraction_of_the_file_fall_back_to_a_full_rewrite() {
+        let strategy = plan_upload_strategy(Some(100), &[(0, 50)], true, DEFAULT_PATCH_MAX_DIRTY_RATIO);
+        assert_eq!(strategy, UploadStrategy::FullRewrite);
+    }
+}
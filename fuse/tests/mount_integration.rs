@@ -0,0 +1,82 @@
+//! End-to-end test that actually mounts a `RosetFs` backed by a mocked
+//! Roset API and exercises it through the real FUSE kernel interface.
+//!
+//! This needs `/dev/fuse` and permission to call `mount(2)` (or
+//! `user_allow_other`/unprivileged user namespaces), neither of which is
+//! available in most CI sandboxes, so every test here is `#[ignore]` and
+//! meant to be run explicitly (`cargo test --test mount_integration --
+//! --ignored`) on a machine that supports FUSE mounts, e.g. the
+//! `privileged` CI lane.
+
+use std::fs;
+use std::time::Duration;
+
+use roset_fuse::cache::AttrCache;
+use roset_fuse::client::RosetClient;
+use roset_fuse::fs::RosetFs;
+use roset_fuse::staging::StagingManager;
+
+/// Spawns a real `fuser::mount2` in a background thread against a
+/// temporary directory, backed by the given mocked Roset API base URL.
+/// Returns the mount point and a guard that unmounts on drop.
+struct MountGuard {
+    mount_point: std::path::PathBuf,
+    _session: fuser::BackgroundSession,
+}
+
+impl MountGuard {
+    fn mount(api_base_url: String) -> Self {
+        let mount_point = std::env::temp_dir().join(format!(
+            "roset-fuse-it-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&mount_point).unwrap();
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let fs = RosetFs::new(
+            RosetClient::new(api_base_url),
+            StagingManager::new(8, rt.handle().clone()),
+            AttrCache::new(Duration::from_secs(30)),
+            rt.handle().clone(),
+        );
+        // The runtime must outlive the mount; leak it for the test's
+        // lifetime rather than threading a shutdown signal through.
+        std::mem::forget(rt);
+
+        let session =
+            fuser::spawn_mount2(fs, &mount_point, &[]).expect("failed to mount roset-fuse");
+
+        Self {
+            mount_point,
+            _session: session,
+        }
+    }
+}
+
+impl Drop for MountGuard {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir(&self.mount_point);
+    }
+}
+
+#[test]
+#[ignore = "requires /dev/fuse and mount(2) permission"]
+fn readdir_reflects_the_mocked_manifest() {
+    let mut server = mockito::Server::new();
+    let _mock = server
+        .mock("GET", "/v1/nodes/root/children")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"children":[{"id":"f1","name":"hello.txt","node_type":"file","size":5,"mtime":{"secs_since_epoch":0,"nanos_since_epoch":0},"metadata":{}}],"next_cursor":null}"#)
+        .create();
+
+    let guard = MountGuard::mount(server.url());
+
+    let entries: Vec<_> = fs::read_dir(&guard.mount_point)
+        .expect("readdir on the mount")
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .collect();
+
+    assert!(entries.contains(&"hello.txt".to_string()));
+}